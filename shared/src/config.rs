@@ -9,34 +9,452 @@ pub trait Preset {
     fn preset() -> Self;
 }
 
+/// 配置文件格式的版本，增删配置项后递增，读到旧版本时用预设值补全后写回，原有的注释保留
+pub const CONFIG_VERSION: u32 = 1;
+
+/// 不认识的配置项会被忽略，不会导致整个文件读取失败
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Config {
+    /// 配置文件格式的版本，没有该项的视为 0
+    #[serde(default)]
+    pub version: u32,
     #[serde(default)]
     pub scroll_threshold: f64,
+    /// 反转滚轮方向，适合开启了自然滚动的系统
+    #[serde(default)]
+    pub invert_scroll: bool,
     pub loading_bar_height: String,
+    /// 进度条配色
+    #[serde(default)]
+    pub bar_colors: BarColors,
     pub toast_stacked: bool,
+    /// 打开漫画期间阻止屏幕休眠
+    #[serde(default)]
+    pub keep_awake: bool,
+    /// 将单张跨页大图拆成左右两页显示
+    #[serde(default)]
+    pub split_wide_pages: bool,
+    /// 宽高比超过该值的单张页面视为跨页，多页同屏时单独占一屏，其后的页顺延配对，0 表示不检测
+    #[serde(default = "default_spread_ratio_threshold")]
+    pub spread_ratio_threshold: f64,
+    /// 将压缩包内附带的 pdf 展开，接在图片之后阅读
+    #[serde(default)]
+    pub expand_embedded_pdf: bool,
+    /// 固实压缩包解压与写缓存之间的缓冲页数
+    #[serde(default = "default_solid_channel_capacity")]
+    pub solid_channel_capacity: usize,
+    /// 固实压缩包写缓存另起任务，与解压并行
+    #[serde(default)]
+    pub solid_parallel_write: bool,
+    /// 以较低的系统优先级解压、解码页面，核心少的机器上翻页更流畅
+    #[serde(default)]
+    pub decode_low_priority: bool,
+    /// 可随机读取的书最多保留的缓存页数，超出时丢弃离当前页最远的，0 表示不限制
+    #[serde(default)]
+    pub max_cached_pages: usize,
+    /// 预载范围的倍数，0 表示按书的格式自动选择（文件夹、zip 为 2，其余为 1）
+    #[serde(default)]
+    pub prefetch_multiplier: usize,
+    /// 单页解压后的大小上限（字节），超过时放弃该页，防止压缩炸弹耗尽内存，0 表示不限制
+    #[serde(default = "default_max_page_bytes")]
+    pub max_page_bytes: u64,
+    /// mobi 中短边小于该值（像素）的图片视为封面缩略图等，不作为单独的页
+    #[serde(default = "default_mobi_min_image_side")]
+    pub mobi_min_image_side: u32,
+    /// 没有图片的 pdf 页面渲染的高度（像素），越大文字越清晰，渲染也越慢
+    #[serde(default = "default_pdf_page_height")]
+    pub pdf_page_height: u32,
+    /// pdf 以两倍分辨率渲染后缩小，文字边缘更平滑，渲染耗时约为四倍
+    #[serde(default)]
+    pub pdf_antialias: bool,
+    /// 页面排序时忽略文件名大小写，仅大小写不同的同名文件大写在前
+    #[serde(default)]
+    pub case_insensitive_sort: bool,
+    /// 同目录各卷与内嵌 pdf 排序时识别中文数字，使 `第二卷` 排在 `第十卷` 之前
+    #[serde(default)]
+    pub cjk_numeral_sort: bool,
+    /// 打开文件夹时，含有图片的子文件夹各作为一章，按名称自然排序，而不是只读取文件夹本身的图片
+    #[serde(default = "default_treat_subdirs_as_chapters")]
+    pub treat_subdirs_as_chapters: bool,
+    /// 向后翻页时跳过近乎纯色的空白页（扫描件的衬页、隔页等），页面载入后才能识别
+    #[serde(default)]
+    pub skip_blank_pages: bool,
+    /// 连续滚动模式下，向后翻页（默认空格）改为向下滚动约一屏，滚到底部后才接着打开下一本
+    #[serde(default = "default_space_scrolls_in_continuous")]
+    pub space_scrolls_in_continuous: bool,
+    /// 关闭漫画后保留页面缓存，再次打开同一本时直接沿用
+    #[serde(default)]
+    pub persist_cache: bool,
+    /// 保留的缓存超过该天数未使用时，启动时清理，0 表示不限制
+    #[serde(default = "default_cache_max_age_days")]
+    pub cache_max_age_days: u32,
+    /// 保留的缓存总大小上限（字节），启动时从最久未用的开始清理，0 表示不限制
+    #[serde(default = "default_cache_max_bytes")]
+    pub cache_max_bytes: u64,
+    /// 需要转码时（如 pdf 渲染、WebView 无法显示的格式）使用的输出格式
+    #[serde(default)]
+    pub transcode_format: TranscodeFormat,
+    /// 转码为 JPEG 时的质量，1 ~ 100
+    #[serde(default = "default_transcode_jpeg_quality")]
+    pub transcode_jpeg_quality: u8,
+    /// 超过该高度（像素）的长图纵向切成多块显示，避免超出 WebView 的图片尺寸限制，0 表示不切
+    #[serde(default = "default_tile_height")]
+    pub tile_height: u32,
+    /// 页面任一边超过该值（像素）时，写缓存前等比缩小，减少内存与磁盘占用；不填时保持原图
+    ///
+    /// 开启长图切块时高度交给切块处理，只限制宽度
+    #[serde(default)]
+    pub max_page_dimension: Option<u32>,
+    /// 体积较大的书只识别出不超过该页数时提示可能漏识别图片，0 表示不提示
+    #[serde(default = "default_small_book_warning_pages")]
+    pub small_book_warning_pages: usize,
+    /// 显示页码时减去的页数，用于跳过封面等前页，使正文第一页显示为 1；只影响页码显示与跳转输入
+    #[serde(default)]
+    pub page_number_offset: i64,
+    /// 循环切换同屏页数时依次使用的值
+    #[serde(default = "default_page_count_presets")]
+    pub page_count_presets: Vec<usize>,
+    /// 书内搜索区分大小写
+    #[serde(default)]
+    pub search_case_sensitive: bool,
+    /// 页面未载入时，等待这么多毫秒后才显示载入动画，避免快速载入时闪一下
+    #[serde(default = "default_loading_gif_delay_ms")]
+    pub loading_gif_delay_ms: u32,
+    /// 自动翻页的间隔（秒）
+    #[serde(default = "default_slideshow_interval_secs")]
+    pub slideshow_interval_secs: u32,
+    /// 显示文件名时把其中的数字补零到该宽度，只影响显示，0 表示不补
+    #[serde(default)]
+    pub display_pad_width: usize,
+    /// 页码与进度条离窗口边缘的额外距离（像素），用于避开圆角或系统界面
+    #[serde(default)]
+    pub ui_margin: u32,
+    /// 页面图片缩放时的插值方式，像素画或低分辨率扫描可用 `Pixelated` 避免模糊
+    #[serde(default)]
+    pub image_rendering: ImageRendering,
+    /// 反色显示时的亮度上限（0 到 1），避免白底反成黑底后文字过于刺眼
+    #[serde(default = "default_invert_brightness")]
+    pub invert_brightness: f64,
+    /// 启动时开启暗角：阅读区域四周渐暗，减轻 OLED 屏幕边缘的刺眼感，可用按键随时开关
+    #[serde(default)]
+    pub vignette: bool,
+    /// 多页同屏时只有当前页在载入时显示动画，其余未载入的页显示静态占位图
+    #[serde(default)]
+    pub spinner_current_only: bool,
+    /// 翻页时通过读屏软件朗读当前页码
+    #[serde(default = "default_announce_page_changes")]
+    pub announce_page_changes: bool,
+    /// 关闭所有动画；不填时跟随系统的“减少动态效果”设置
+    #[serde(default)]
+    pub reduced_motion: Option<bool>,
+    /// 启动时是否全屏；不填时沿用上次关闭时的状态
+    #[serde(default)]
+    pub fullscreen_on_start: Option<bool>,
+    /// 打开时可以一次选择多个文件，第一本之外的依次排队，读完一本后打开下一本
+    #[serde(default)]
+    pub pick_multiple_files: bool,
+    /// 启动时恢复上次未读完的阅读队列
+    #[serde(default)]
+    pub resume_queue: bool,
+    /// 允许把文件拖进窗口打开，关闭后只能通过选择文件打开，避免误拖丢失进度
+    #[serde(default = "default_enable_drag_drop")]
+    pub enable_drag_drop: bool,
+    /// 翻过最后一页时的行为，队列中还有书时先打开队列中的
+    #[serde(default)]
+    pub end_of_book_action: EndOfBookAction,
+    /// 鼠标右键的行为：翻页、弹出菜单或不处理
+    #[serde(default)]
+    pub right_click_action: RightClickAction,
     pub launch_config: LaunchConfig,
     pub key_bind: KeyBind,
 }
 
 impl Preset for Config {
     fn preset() -> Self {
+        let version = CONFIG_VERSION;
         let scroll_threshold = 3.0;
+        let invert_scroll = false;
         let loading_bar_height = String::from("min(3vh, 16px)");
+        let bar_colors = Preset::preset();
         let toast_stacked = false;
+        let keep_awake = false;
+        let split_wide_pages = false;
+        let spread_ratio_threshold = default_spread_ratio_threshold();
+        let expand_embedded_pdf = false;
+        let solid_channel_capacity = default_solid_channel_capacity();
+        let solid_parallel_write = false;
+        let decode_low_priority = false;
+        let max_cached_pages = 0;
+        let prefetch_multiplier = 0;
+        let max_page_bytes = default_max_page_bytes();
+        let mobi_min_image_side = default_mobi_min_image_side();
+        let pdf_page_height = default_pdf_page_height();
+        let pdf_antialias = false;
+        let case_insensitive_sort = false;
+        let cjk_numeral_sort = false;
+        let treat_subdirs_as_chapters = default_treat_subdirs_as_chapters();
+        let skip_blank_pages = false;
+        let space_scrolls_in_continuous = default_space_scrolls_in_continuous();
+        let persist_cache = false;
+        let cache_max_age_days = default_cache_max_age_days();
+        let cache_max_bytes = default_cache_max_bytes();
+        let transcode_format = TranscodeFormat::Png;
+        let transcode_jpeg_quality = default_transcode_jpeg_quality();
+        let tile_height = default_tile_height();
+        let max_page_dimension = None;
+        let small_book_warning_pages = default_small_book_warning_pages();
+        let page_number_offset = 0;
+        let page_count_presets = default_page_count_presets();
+        let search_case_sensitive = false;
+        let loading_gif_delay_ms = default_loading_gif_delay_ms();
+        let slideshow_interval_secs = default_slideshow_interval_secs();
+        let display_pad_width = 0;
+        let ui_margin = 0;
+        let image_rendering = ImageRendering::Auto;
+        let invert_brightness = default_invert_brightness();
+        let vignette = false;
+        let spinner_current_only = false;
+        let announce_page_changes = default_announce_page_changes();
+        let reduced_motion = None;
+        let fullscreen_on_start = None;
+        let pick_multiple_files = false;
+        let resume_queue = false;
+        let enable_drag_drop = default_enable_drag_drop();
+        let end_of_book_action = EndOfBookAction::Stay;
+        let right_click_action = RightClickAction::Page;
         let launch_config = Preset::preset();
         let key_bind = Preset::preset();
 
         Self {
+            version,
             scroll_threshold,
+            invert_scroll,
             loading_bar_height,
+            bar_colors,
             toast_stacked,
+            keep_awake,
+            split_wide_pages,
+            spread_ratio_threshold,
+            expand_embedded_pdf,
+            solid_channel_capacity,
+            solid_parallel_write,
+            decode_low_priority,
+            max_cached_pages,
+            prefetch_multiplier,
+            max_page_bytes,
+            mobi_min_image_side,
+            pdf_page_height,
+            pdf_antialias,
+            case_insensitive_sort,
+            cjk_numeral_sort,
+            treat_subdirs_as_chapters,
+            skip_blank_pages,
+            space_scrolls_in_continuous,
+            persist_cache,
+            cache_max_age_days,
+            cache_max_bytes,
+            transcode_format,
+            transcode_jpeg_quality,
+            tile_height,
+            max_page_dimension,
+            small_book_warning_pages,
+            page_number_offset,
+            page_count_presets,
+            search_case_sensitive,
+            loading_gif_delay_ms,
+            slideshow_interval_secs,
+            display_pad_width,
+            ui_margin,
+            image_rendering,
+            invert_brightness,
+            vignette,
+            spinner_current_only,
+            announce_page_changes,
+            reduced_motion,
+            fullscreen_on_start,
+            pick_multiple_files,
+            resume_queue,
+            enable_drag_drop,
+            end_of_book_action,
+            right_click_action,
             launch_config,
             key_bind,
         }        
     }
 }
 
+fn default_pdf_page_height() -> u32 {
+    1280
+}
+
+fn default_treat_subdirs_as_chapters() -> bool {
+    true
+}
+
+fn default_space_scrolls_in_continuous() -> bool {
+    true
+}
+
+fn default_announce_page_changes() -> bool {
+    true
+}
+
+fn default_invert_brightness() -> f64 {
+    1.0
+}
+
+fn default_enable_drag_drop() -> bool {
+    true
+}
+
+fn default_spread_ratio_threshold() -> f64 {
+    1.0
+}
+
+fn default_solid_channel_capacity() -> usize {
+    200
+}
+
+fn default_max_page_bytes() -> u64 {
+    256 << 20
+}
+
+fn default_cache_max_age_days() -> u32 {
+    30
+}
+
+fn default_cache_max_bytes() -> u64 {
+    2 << 30
+}
+
+fn default_mobi_min_image_side() -> u32 {
+    300
+}
+
+fn default_transcode_jpeg_quality() -> u8 {
+    90
+}
+
+fn default_tile_height() -> u32 {
+    8000
+}
+
+fn default_small_book_warning_pages() -> usize {
+    2
+}
+
+fn default_page_count_presets() -> Vec<usize> {
+    vec![1, 2, 4]
+}
+
+fn default_slideshow_interval_secs() -> u32 {
+    5
+}
+
+fn default_loading_gif_delay_ms() -> u32 {
+    150
+}
+
+impl Config {
+    /// 将不合法的配置项恢复为预设值，返回每一处修正的说明
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut messages = Vec::new();
+        let preset = BarColors::preset();
+        for (name, color, preset) in [
+            ("bar_colors.unloaded", &mut self.bar_colors.unloaded, preset.unloaded),
+            ("bar_colors.loaded", &mut self.bar_colors.loaded, preset.loaded),
+            ("bar_colors.current", &mut self.bar_colors.current, preset.current),
+        ] {
+            if !is_valid_color(color.as_str()) {
+                messages.push(format!("{} = {:?} 不是合法的颜色，改用 {}", name, color, preset));
+                *color = preset;
+            }
+        }
+        if self.scroll_threshold.is_nan() || self.scroll_threshold < 0. {
+            let preset = Self::preset().scroll_threshold;
+            messages.push(format!("scroll_threshold = {} 不能为负数，改用 {}", self.scroll_threshold, preset));
+            self.scroll_threshold = preset;
+        }
+        if self.pdf_page_height == 0 {
+            let preset = default_pdf_page_height();
+            messages.push(format!("pdf_page_height 不能为 0，改用 {}", preset));
+            self.pdf_page_height = preset;
+        }
+        if self.max_page_dimension == Some(0) {
+            messages.push(String::from("max_page_dimension 不能为 0，改为不限制"));
+            self.max_page_dimension = None;
+        }
+        if self.slideshow_interval_secs == 0 {
+            let preset = default_slideshow_interval_secs();
+            messages.push(format!("slideshow_interval_secs 不能为 0，改用 {}", preset));
+            self.slideshow_interval_secs = preset;
+        }
+        if !(0. ..=1.).contains(&self.invert_brightness) {
+            let preset = default_invert_brightness();
+            messages.push(format!("invert_brightness = {} 应在 0 到 1 之间，改用 {}", self.invert_brightness, preset));
+            self.invert_brightness = preset;
+        }
+        if self.spread_ratio_threshold.is_nan() || self.spread_ratio_threshold < 0. {
+            let preset = default_spread_ratio_threshold();
+            messages.push(format!("spread_ratio_threshold = {} 不能为负数，改用 {}", self.spread_ratio_threshold, preset));
+            self.spread_ratio_threshold = preset;
+        }
+        if !(1..=100).contains(&self.transcode_jpeg_quality) {
+            let clamped = self.transcode_jpeg_quality.clamp(1, 100);
+            messages.push(format!("transcode_jpeg_quality = {} 应在 1 到 100 之间，改用 {}", self.transcode_jpeg_quality, clamped));
+            self.transcode_jpeg_quality = clamped;
+        }
+        messages
+    }
+}
+
+/// 转成 JS 字符串字面量，Windows 路径中的反斜杠、引号都会被转义
+fn js_string_literal(s: &str) -> String {
+    serde_json::to_string(s).unwrap()
+}
+
+/// 只做粗略检查：`#` 加 3/4/6/8 位十六进制、`rgb()` 之类的函数写法，或颜色名
+fn is_valid_color(color: &str) -> bool {
+    let color = color.trim();
+    if let Some(hex) = color.strip_prefix('#') {
+        [3, 4, 6, 8].contains(&hex.len()) && hex.chars().all(|x| x.is_ascii_hexdigit())
+    } else if let Some((name, rest)) = color.split_once('(') {
+        ["rgb", "rgba", "hsl", "hsla"].contains(&name.to_ascii_lowercase().as_str()) && rest.ends_with(')')
+    } else {
+        !color.is_empty() && color.chars().all(|x| x.is_ascii_alphabetic())
+    }
+}
+
+impl Config {
+    /// 读取可能来自旧版本的配置，返回配置及原来的版本
+    ///
+    /// 版本低于 `CONFIG_VERSION` 时，缺少的项（包括键位、启动配置等嵌套表中的）用预设值补上，已有的项原样保留
+    pub fn migrate(s: &str) -> Result<(Self, u32), toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(s)?;
+        let version = table.get("version").and_then(toml::Value::as_integer).unwrap_or(0).max(0) as u32;
+        if version < CONFIG_VERSION {
+            if let Ok(toml::Value::Table(preset)) = toml::Value::try_from(Self::preset()) {
+                fill_missing(&mut table, preset);
+            }
+            table.insert(String::from("version"), toml::Value::Integer(CONFIG_VERSION as i64));
+        }
+        Ok((toml::Value::Table(table).try_into()?, version))
+    }
+}
+
+/// 把 `preset` 中有而 `table` 中没有的项补进 `table`，两边都是表的逐层合并
+fn fill_missing(table: &mut toml::Table, preset: toml::Table) {
+    for (key, value) in preset {
+        match (table.get_mut(key.as_str()), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(preset)) => fill_missing(existing, preset),
+            (Some(_), _) => {},
+            (None, value) => {
+                table.insert(key, value);
+            },
+        }
+    }
+}
+
 impl TryFrom<&str> for Config {
     type Error = toml::de::Error;
 
@@ -51,11 +469,125 @@ impl Display for Config {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BarColors {
+    /// 未载入的页
+    pub unloaded: String,
+    /// 已载入的页
+    pub loaded: String,
+    /// 当前显示的页
+    pub current: String,
+}
+
+impl Preset for BarColors {
+    fn preset() -> Self {
+        let unloaded = String::from("#bfc9d1");
+        let loaded = String::from("#39C5BB");
+        let current = String::from("#E14A96");
+
+        Self {
+            unloaded,
+            loaded,
+            current,
+        }
+    }
+}
+
+impl Default for BarColors {
+    fn default() -> Self {
+        Self::preset()
+    }
+}
+
+/// 转码输出格式，默认无损的 PNG，避免线稿被反复有损压缩
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TranscodeFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+/// 翻过最后一页时的处理方式
+///
+/// `Stay` 停在末页并提示，`NextVolume` 打开同目录中按自然顺序的下一个文件
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EndOfBookAction {
+    #[default]
+    Stay,
+    NextVolume,
+}
+
+/// 页面的缩放方式
+///
+/// `FitScreen` 整屏完整显示，`FitWidth` 占满宽度、纵向滚动，`FitHeight` 占满高度、横向滚动，`OriginalSize` 不缩放
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FitMode {
+    #[default]
+    FitScreen,
+    FitWidth,
+    FitHeight,
+    OriginalSize,
+}
+
+impl FitMode {
+    /// 按声明顺序循环
+    pub fn next(self) -> Self {
+        match self {
+            Self::FitScreen => Self::FitWidth,
+            Self::FitWidth => Self::FitHeight,
+            Self::FitHeight => Self::OriginalSize,
+            Self::OriginalSize => Self::FitScreen,
+        }
+    }
+}
+
+/// 页面图片的 CSS `image-rendering`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageRendering {
+    #[default]
+    Auto,
+    Pixelated,
+    Smooth,
+}
+
+impl ImageRendering {
+    pub fn css_value(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Pixelated => "pixelated",
+            Self::Smooth => "smooth",
+        }
+    }
+
+    /// 按声明顺序循环
+    pub fn next(self) -> Self {
+        match self {
+            Self::Auto => Self::Pixelated,
+            Self::Pixelated => Self::Smooth,
+            Self::Smooth => Self::Auto,
+        }
+    }
+}
+
+/// 右键按下时的处理方式
+///
+/// `Page` 按 `RightClick` 键位执行动作并屏蔽系统菜单，
+/// `ContextMenu` 弹出应用内菜单，`None` 不做处理、保留系统菜单
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RightClickAction {
+    #[default]
+    Page,
+    ContextMenu,
+    None,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
 pub struct LaunchConfig {
     pub reading_from_right_to_left: bool,
     pub show_page_number: bool,
     pub page_num_per_screen: usize,
+    #[serde(default)]
+    pub fit_mode: FitMode,
 }
 
 impl Preset for LaunchConfig {
@@ -63,11 +595,13 @@ impl Preset for LaunchConfig {
         let reading_from_right_to_left = true;
         let show_page_number = true;
         let page_num_per_screen = 2;
+        let fit_mode = FitMode::FitScreen;
 
         Self {
             reading_from_right_to_left,
             show_page_number,
             page_num_per_screen,
+            fit_mode,
         }
     }
 }
@@ -92,23 +626,67 @@ pub struct KeyBind {
     fullscreen: Vec<String>,
     show_help: Vec<String>,
     hide_page_number: Vec<String>,
+    #[serde(default)]
+    reset_view: Vec<String>,
+    #[serde(default)]
+    cycle_page_count: Vec<String>,
+    #[serde(default)]
+    search: Vec<String>,
+    #[serde(default)]
+    toggle_actual_size: Vec<String>,
+    #[serde(default)]
+    open_folder: Vec<String>,
+    #[serde(default)]
+    random_page: Vec<String>,
+    #[serde(default)]
+    toggle_scroll_mode: Vec<String>,
+    #[serde(default)]
+    cycle_fit_mode: Vec<String>,
+    #[serde(default)]
+    toggle_rendering: Vec<String>,
+    #[serde(default)]
+    zoom_in: Vec<String>,
+    #[serde(default)]
+    zoom_out: Vec<String>,
+    #[serde(default)]
+    zoom_reset: Vec<String>,
+    #[serde(default)]
+    show_thumbnails: Vec<String>,
+    #[serde(default)]
+    rotate_clockwise: Vec<String>,
+    #[serde(default)]
+    rotate_counter_clockwise: Vec<String>,
+    #[serde(default)]
+    toggle_grayscale: Vec<String>,
+    #[serde(default)]
+    toggle_invert: Vec<String>,
+    #[serde(default)]
+    toggle_slideshow: Vec<String>,
+    #[serde(default)]
+    recent_next: Vec<String>,
+    #[serde(default)]
+    recent_prev: Vec<String>,
+    #[serde(default)]
+    toggle_vignette: Vec<String>,
 }
 
 impl KeyBind {
     /// 生成一段只在 DOM 就绪后执行的极简替换脚本，
     /// 通过 .initialization_script() 注入即可。
-    pub fn to_replace_script(&self, config_path_path: String) -> String {
+    ///
+    /// 配置文件路径同时挂到 `window.__CONFIG_PATH__`，供指南页面打开配置文件使用
+    pub fn to_replace_script(&self, config_path: &str) -> String {
         use std::fmt::Write;
         let mut js = r#"window.addEventListener('DOMContentLoaded', ()=>{"#.to_string();
 
-        // 与 HTML 里 id 0..17 的顺序保持一致
+        // 与 HTML 里 id 0..38 的顺序保持一致
         let slots: &[&[String]] = &[
             &self.page_next,      // 0
             &self.page_last,      // 1
             &self.page_left,      // 2
             &self.page_right,     // 3
-            &[],                  // 4 未用
-            &[],                  // 5 未用
+            &self.page_step_next, // 4
+            &self.page_step_last, // 5
             &self.page_step_left, // 6
             &self.page_step_right,// 7
             &self.page_home,      // 8
@@ -121,6 +699,27 @@ impl KeyBind {
             &self.fullscreen,      //15
             &self.show_help,       //16
             &self.hide_page_number,//17
+            &self.reset_view,      //18
+            &self.cycle_page_count,//19
+            &self.search,          //20
+            &self.toggle_actual_size,//21
+            &self.open_folder,  //22
+            &self.random_page,  //23
+            &self.toggle_scroll_mode,//24
+            &self.cycle_fit_mode,//25
+            &self.toggle_rendering,//26
+            &self.zoom_in,      //27
+            &self.zoom_out,     //28
+            &self.zoom_reset,   //29
+            &self.show_thumbnails,//30
+            &self.rotate_clockwise,//31
+            &self.rotate_counter_clockwise,//32
+            &self.toggle_grayscale,//33
+            &self.toggle_invert,//34
+            &self.toggle_slideshow,//35
+            &self.recent_next,  //36
+            &self.recent_prev,  //37
+            &self.toggle_vignette,//38
         ];
 
         for (idx, keys) in slots.iter().enumerate() {
@@ -129,16 +728,34 @@ impl KeyBind {
             writeln!(&mut js, "document.getElementById('{}').textContent = `{}`;", idx, text).unwrap();
         }
 
-        
-        js.push_str("document.getElementById('filePath').value=");
-        js.push_str(config_path_path.as_str());
-        js.push(';');
-
+        js.push_str("document.getElementById('filePath').value=window.__CONFIG_PATH__;");
         js.push_str("});");
-        js
+        format!("window.__CONFIG_PATH__={};{}", js_string_literal(config_path), js)
     }
 
-    fn get_keys_set(&self) -> [HashSet<&str>; 18] {
+    /// 找出绑定到多个动作的键，每个冲突返回一条说明；同一动作里重复的键不算冲突
+    pub fn conflicts(&self) -> Vec<String> {
+        let Ok(toml::Value::Table(table)) = toml::Value::try_from(self) else {
+            return Vec::new();
+        };
+        let mut actions: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (action, keys) in table.iter() {
+            for key in keys.as_array().into_iter().flatten().filter_map(toml::Value::as_str) {
+                let bound = actions.entry(key).or_default();
+                if !bound.contains(&action.as_str()) {
+                    bound.push(action.as_str());
+                }
+            }
+        }
+        let mut conflicts: Vec<String> = actions.into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|(key, actions)| format!("{} 同时绑定了 {}", key, actions.join("、")))
+            .collect();
+        conflicts.sort_unstable();
+        conflicts
+    }
+
+    fn get_keys_set(&self) -> [HashSet<&str>; 39] {
         [
             &self.page_next,
             &self.page_last,
@@ -158,6 +775,27 @@ impl KeyBind {
             &self.fullscreen,
             &self.show_help,
             &self.hide_page_number,
+            &self.reset_view,
+            &self.cycle_page_count,
+            &self.search,
+            &self.toggle_actual_size,
+            &self.open_folder,
+            &self.random_page,
+            &self.toggle_scroll_mode,
+            &self.cycle_fit_mode,
+            &self.toggle_rendering,
+            &self.zoom_in,
+            &self.zoom_out,
+            &self.zoom_reset,
+            &self.show_thumbnails,
+            &self.rotate_clockwise,
+            &self.rotate_counter_clockwise,
+            &self.toggle_grayscale,
+            &self.toggle_invert,
+            &self.toggle_slideshow,
+            &self.recent_next,
+            &self.recent_prev,
+            &self.toggle_vignette,
         ].map(|v| v.iter().map(|s| s.as_str()).collect::<HashSet<&str>>())
     }
 }
@@ -245,6 +883,90 @@ impl Preset for KeyBind {
             String::from("KeyI"),
         ];
 
+        let reset_view = vec![
+            String::from("Backspace"),
+        ];
+
+        let cycle_page_count = vec![
+            String::from("KeyC"),
+        ];
+
+        let search = vec![
+            String::from("KeyS"),
+        ];
+
+        let toggle_actual_size = vec![
+            String::from("KeyZ"),
+        ];
+
+        let open_folder = vec![
+            String::from("KeyD"),
+        ];
+
+        let random_page = vec![
+            String::from("KeyX"),
+        ];
+
+        let toggle_scroll_mode = vec![
+            String::from("KeyW"),
+        ];
+
+        let cycle_fit_mode = vec![
+            String::from("KeyV"),
+        ];
+
+        let toggle_rendering = vec![
+            String::from("KeyP"),
+        ];
+
+        let zoom_in = vec![
+            String::from("BracketRight"),
+        ];
+
+        let zoom_out = vec![
+            String::from("BracketLeft"),
+        ];
+
+        let zoom_reset = vec![
+            String::from("Digit0"),
+        ];
+
+        let show_thumbnails = vec![
+            String::from("KeyG"),
+        ];
+
+        let rotate_clockwise = vec![
+            String::from("KeyE"),
+        ];
+
+        let rotate_counter_clockwise = vec![
+            String::from("KeyQ"),
+        ];
+
+        let toggle_grayscale = vec![
+            String::from("KeyB"),
+        ];
+
+        let toggle_invert = vec![
+            String::from("KeyN"),
+        ];
+
+        let toggle_slideshow = vec![
+            String::from("KeyA"),
+        ];
+
+        let recent_next = vec![
+            String::from("KeyT"),
+        ];
+
+        let recent_prev = vec![
+            String::from("KeyY"),
+        ];
+
+        let toggle_vignette = vec![
+            String::from("KeyK"),
+        ];
+
         Self {
             page_next,
             page_last,
@@ -264,6 +986,27 @@ impl Preset for KeyBind {
             fullscreen,
             show_help,
             hide_page_number,
+            reset_view,
+            cycle_page_count,
+            search,
+            toggle_actual_size,
+            open_folder,
+            random_page,
+            toggle_scroll_mode,
+            cycle_fit_mode,
+            toggle_rendering,
+            zoom_in,
+            zoom_out,
+            zoom_reset,
+            show_thumbnails,
+            rotate_clockwise,
+            rotate_counter_clockwise,
+            toggle_grayscale,
+            toggle_invert,
+            toggle_slideshow,
+            recent_next,
+            recent_prev,
+            toggle_vignette,
         }
     }
 }
@@ -344,6 +1087,90 @@ impl From<KeyBind> for HashMap<String, InputAction> {
             map.insert(key, InputAction::HidePageNumber);
         }
 
+        for key in value.reset_view {
+            map.insert(key, InputAction::ResetView);
+        }
+
+        for key in value.cycle_page_count {
+            map.insert(key, InputAction::CyclePageCount);
+        }
+
+        for key in value.search {
+            map.insert(key, InputAction::Search);
+        }
+
+        for key in value.toggle_actual_size {
+            map.insert(key, InputAction::Toggle1to1);
+        }
+
+        for key in value.open_folder {
+            map.insert(key, InputAction::OpenFolder);
+        }
+
+        for key in value.random_page {
+            map.insert(key, InputAction::RandomPage);
+        }
+
+        for key in value.toggle_scroll_mode {
+            map.insert(key, InputAction::ToggleScrollMode);
+        }
+
+        for key in value.cycle_fit_mode {
+            map.insert(key, InputAction::CycleFitMode);
+        }
+
+        for key in value.toggle_rendering {
+            map.insert(key, InputAction::ToggleRendering);
+        }
+
+        for key in value.zoom_in {
+            map.insert(key, InputAction::ZoomIn);
+        }
+
+        for key in value.zoom_out {
+            map.insert(key, InputAction::ZoomOut);
+        }
+
+        for key in value.zoom_reset {
+            map.insert(key, InputAction::ZoomReset);
+        }
+
+        for key in value.show_thumbnails {
+            map.insert(key, InputAction::ShowThumbnails);
+        }
+
+        for key in value.rotate_clockwise {
+            map.insert(key, InputAction::RotateClockwise);
+        }
+
+        for key in value.rotate_counter_clockwise {
+            map.insert(key, InputAction::RotateCounterClockwise);
+        }
+
+        for key in value.toggle_grayscale {
+            map.insert(key, InputAction::ToggleGrayscale);
+        }
+
+        for key in value.toggle_invert {
+            map.insert(key, InputAction::ToggleInvert);
+        }
+
+        for key in value.toggle_slideshow {
+            map.insert(key, InputAction::ToggleSlideshow);
+        }
+
+        for key in value.recent_next {
+            map.insert(key, InputAction::RecentNext);
+        }
+
+        for key in value.recent_prev {
+            map.insert(key, InputAction::RecentPrev);
+        }
+
+        for key in value.toggle_vignette {
+            map.insert(key, InputAction::ToggleVignette);
+        }
+
         map
     }
 }
@@ -376,4 +1203,131 @@ pub enum InputAction {
     Fullscreen = 15,
     ShowHelp = 16,
     HidePageNumber = 17,
+    ResetView = 18,
+    CyclePageCount = 19,
+    Search = 20,
+    Toggle1to1 = 21,
+    OpenFolder = 22,
+    RandomPage = 23,
+    ToggleScrollMode = 24,
+    CycleFitMode = 25,
+    ToggleRendering = 26,
+    ZoomIn = 27,
+    ZoomOut = 28,
+    ZoomReset = 29,
+    ShowThumbnails = 30,
+    RotateClockwise = 31,
+    RotateCounterClockwise = 32,
+    ToggleGrayscale = 33,
+    ToggleInvert = 34,
+    ToggleSlideshow = 35,
+    RecentNext = 36,
+    RecentPrev = 37,
+    ToggleVignette = 38,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_fills_missing_items_from_preset() {
+        let old = "invert_scroll = true\nloading_bar_height = \"8px\"\n\n[key_bind]\npage_next = [\"KeyX\"]\n";
+        let (config, version) = Config::migrate(old).unwrap();
+        let preset = Config::preset();
+        assert_eq!(version, 0);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.scroll_threshold, preset.scroll_threshold);
+        assert_eq!(config.toast_stacked, preset.toast_stacked);
+        assert!(config.invert_scroll);
+        assert_eq!(config.loading_bar_height, "8px");
+        assert_eq!(config.key_bind.page_next, ["KeyX"]);
+        assert_eq!(config.key_bind.page_last, preset.key_bind.page_last);
+    }
+
+    #[test]
+    fn validate_resets_invalid_items() {
+        let mut config = Config::preset();
+        assert!(config.validate().is_empty());
+        config.slideshow_interval_secs = 0;
+        config.invert_brightness = 2.;
+        config.transcode_jpeg_quality = 0;
+        let messages = config.validate();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(config.slideshow_interval_secs, default_slideshow_interval_secs());
+        assert_eq!(config.invert_brightness, default_invert_brightness());
+        assert_eq!(config.transcode_jpeg_quality, 1);
+        assert!(messages[2].contains("transcode_jpeg_quality"));
+        // 超过 100 时压到 100
+        config.transcode_jpeg_quality = 101;
+        assert_eq!(config.validate().len(), 1);
+        assert_eq!(config.transcode_jpeg_quality, 100);
+    }
+
+    #[test]
+    fn image_rendering_css_values() {
+        assert_eq!(ImageRendering::Auto.css_value(), "auto");
+        assert_eq!(ImageRendering::Pixelated.css_value(), "pixelated");
+        assert_eq!(ImageRendering::Smooth.css_value(), "smooth");
+        assert_eq!(ImageRendering::Smooth.next(), ImageRendering::Auto);
+    }
+
+    #[test]
+    fn migrate_keeps_current_version_as_is() {
+        let current = Config::preset().to_string();
+        let (config, version) = Config::migrate(current.as_str()).unwrap();
+        assert_eq!(version, CONFIG_VERSION);
+        assert_eq!(config, Config::preset());
+    }
+
+    #[test]
+    fn preset_round_trips_through_toml() {
+        let preset = Config::preset();
+        let text = preset.to_string();
+        let config: Config = toml::from_str(text.as_str()).unwrap();
+        assert_eq!(config, preset);
+        assert!(text.contains("[launch_config]"));
+    }
+
+    #[test]
+    fn replace_script_covers_every_guide_id() {
+        // 每个动作都绑上键
+        let toml::Value::Table(mut table) = toml::Value::try_from(Config::preset().key_bind).unwrap() else {
+            panic!("KeyBind 应序列化为表");
+        };
+        for (_, keys) in table.iter_mut() {
+            *keys = toml::Value::Array(vec![toml::Value::String("KeyQ".to_string())]);
+        }
+        let key_bind: KeyBind = toml::Value::Table(table).try_into().unwrap();
+        let script = key_bind.to_replace_script("config.toml");
+        let guide = include_str!("../../public/guide.html");
+        for id in 0..=InputAction::ToggleVignette as usize {
+            assert!(script.contains(format!("getElementById('{}')", id).as_str()), "脚本缺少 id {}", id);
+            assert!(guide.contains(format!("id=\"{}\"", id).as_str()), "指南缺少 id {}", id);
+        }
+        assert!(!script.contains(format!("getElementById('{}')", InputAction::ToggleVignette as usize + 1).as_str()));
+    }
+
+    #[test]
+    fn windows_config_path_round_trips_through_script() {
+        let path = r#"C:\Users\漫画\"quoted"\config.toml"#;
+        let script = Config::preset().key_bind.to_replace_script(path);
+        let literal = script.strip_prefix("window.__CONFIG_PATH__=").and_then(|x| x.split_once(";window.")).unwrap().0;
+        assert_eq!(literal, r#""C:\\Users\\漫画\\\"quoted\"\\config.toml""#);
+        assert_eq!(serde_json::from_str::<String>(literal).unwrap(), path);
+    }
+
+    #[test]
+    fn conflicting_key_binds_are_reported() {
+        let mut key_bind = Config::preset().key_bind;
+        assert!(key_bind.conflicts().is_empty());
+        // 同一动作里重复的键不算冲突
+        key_bind.page_jump = vec!["F9".to_string(), "F9".to_string()];
+        assert!(key_bind.conflicts().is_empty());
+        key_bind.page_jump.push(key_bind.page_next[0].clone());
+        let conflicts = key_bind.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].starts_with(key_bind.page_next[0].as_str()));
+        assert!(conflicts[0].contains("page_next") && conflicts[0].contains("page_jump"));
+    }
 }
@@ -5,6 +5,8 @@ use std::path::Path;
 const A4_ASPECT_RATIO: f64 = 210. / 297.;  // Source: public/no_data.svg
 pub const NO_DATA: &str = "public/no_data.svg";
 pub const LOADING_GIF: &str = "public/loading waiting GIF.gif";
+/// 不显示动画时的载入占位图，尺寸与 `NO_DATA` 相同
+pub const LOADING_STILL: &str = "public/loading_still.svg";
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
 pub enum ImageData {
@@ -12,6 +14,10 @@ pub enum ImageData {
     NoData,
     Loading,
     Loaded(String, f64),
+    /// 切块显示的长图：原图路径、整体宽高比、自上而下的各块路径
+    Tiled(String, f64, Vec<String>),
+    /// 载入失败的页及失败原因
+    Error(String),
 }
 
 impl ImageData {
@@ -20,11 +26,24 @@ impl ImageData {
         Self::Loaded(path, aspect_ratio)
     }
 
+    pub fn tiled(path: &Path, aspect_ratio: f64, tiles: &[impl AsRef<Path>]) -> Self {
+        let path = path.to_string_lossy().to_string();
+        let tiles = tiles.iter().map(|x| x.as_ref().to_string_lossy().to_string()).collect();
+        Self::Tiled(path, aspect_ratio, tiles)
+    }
+
     pub fn aspect_ratio(&self) -> f64 {
-        if let Self::Loaded(_, x) = self {
-            *x
-        } else {
-            A4_ASPECT_RATIO
+        match self {
+            Self::Loaded(_, x) | Self::Tiled(_, x, _) => *x,
+            _ => A4_ASPECT_RATIO,
+        }
+    }
+
+    /// 已载入页面的原图路径
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::Loaded(path, _) | Self::Tiled(path, _, _) => Some(path.as_str()),
+            _ => None,
         }
     }
 }
@@ -32,13 +51,42 @@ impl ImageData {
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
 pub struct LoadPage {
     pub sha256: [u8; 32],
+    pub epoch: u64,
     pub index: usize, 
     pub len: usize,
     pub image_data: ImageData,
+    /// 页面近乎纯色，只在开启跳过空白页时检测
+    #[serde(default)]
+    pub blank: bool,
 }
 
 impl LoadPage {
-    pub fn new(sha256: [u8; 32], index: usize, len: usize, image_data: ImageData) -> Self {
-        Self { sha256, index, len, image_data }
+    pub fn new(sha256: [u8; 32], epoch: u64, index: usize, len: usize, image_data: ImageData) -> Self {
+        Self { sha256, epoch, index, len, image_data, blank: false }
+    }
+
+    pub fn with_blank(mut self, blank: bool) -> Self {
+        self.blank = blank;
+        self
+    }
+
+    /// 同一文件重新打开时哈希相同，需再比较载入批次以丢弃旧事件
+    pub fn belongs_to(&self, sha256: &[u8; 32], epoch: u64) -> bool {
+        self.sha256 == *sha256 && self.epoch == epoch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn older_epoch_of_same_book_is_ignored() {
+        let sha256 = [7; 32];
+        let stale = LoadPage::new(sha256, 1, 0, 10, ImageData::Loading);
+        let current = LoadPage::new(sha256, 2, 0, 10, ImageData::Loading);
+        assert!(!stale.belongs_to(&sha256, 2));
+        assert!(current.belongs_to(&sha256, 2));
+        assert!(!current.belongs_to(&[8; 32], 2));
     }
 }
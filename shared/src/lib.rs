@@ -14,7 +14,15 @@ pub const EXT_TAR: &str = "tar";
 pub const EXT_XZ: &str = "xz";
 pub const EXT_GZ: &str = "gz";
 pub const EXT_BZ2: &str = "bz2";
-pub const SUPPORTED_FILE_FORMATS: &[&str; 10] = &[EXT_ZIP, EXT_EPUB, EXT_7Z, EXT_PDF, EXT_CBZ, EXT_MOBI, EXT_TAR, EXT_XZ, EXT_GZ, EXT_BZ2];
+pub const EXT_CBT: &str = "cbt";
+pub const EXT_TGZ: &str = "tgz";
+pub const EXT_TXZ: &str = "txz";
+pub const EXT_TBZ2: &str = "tbz2";
+pub const EXT_RAR: &str = "rar";
+pub const EXT_CBR: &str = "cbr";
+/// 按字节切分的分卷的第一卷，如 `name.zip.001`
+pub const EXT_SPLIT: &str = "001";
+pub const SUPPORTED_FILE_FORMATS: &[&str; 17] = &[EXT_ZIP, EXT_EPUB, EXT_7Z, EXT_PDF, EXT_CBZ, EXT_MOBI, EXT_TAR, EXT_XZ, EXT_GZ, EXT_BZ2, EXT_CBT, EXT_TGZ, EXT_TXZ, EXT_TBZ2, EXT_RAR, EXT_CBR, EXT_SPLIT];
 pub const SUPPORTED_IMG_FORMATS: &[&str; 7] = &[
     "jpg",
     "jpeg",
@@ -27,7 +35,7 @@ pub const SUPPORTED_IMG_FORMATS: &[&str; 7] = &[
 
 pub mod config;
 mod image_data;
-pub use image_data::{ImageData, LoadPage, NO_DATA, LOADING_GIF};
+pub use image_data::{ImageData, LoadPage, NO_DATA, LOADING_GIF, LOADING_STILL};
 
 #[derive(Debug)]
 pub struct NeedPassword;
@@ -40,9 +48,116 @@ impl fmt::Display for NeedPassword {
 
 impl std::error::Error for NeedPassword {}
 
+/// 页面源支持的特性，前端据此启用或禁用相应功能
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// 可以按任意顺序读取页面
+    pub random_access: bool,
+    /// 固实压缩，只能从头到尾依次解压
+    pub solid: bool,
+    /// 带有目录信息
+    pub has_toc: bool,
+    /// 能划分出章节
+    pub has_chapters: bool,
+    /// 可能需要密码
+    pub supports_password: bool,
+    /// 内容可能在阅读期间变化，可以监视
+    pub watchable: bool,
+}
+
+impl Capabilities {
+    pub fn new(solid: bool, has_chapters: bool) -> Self {
+        Self {
+            random_access: !solid,
+            solid,
+            has_chapters,
+            ..Default::default()
+        }
+    }
+}
+
+/// 一种支持的文件格式，供指南等处展示
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FormatInfo {
+    /// 小写后缀名，不含点
+    pub ext: String,
+    pub label: String,
+    /// 压缩包或归档，而非文档
+    pub is_archive: bool,
+    pub supports_password: bool,
+    /// 本次构建是否包含该格式
+    pub enabled: bool,
+}
+
+/// 显示页对应源页的哪一部分，拆分跨页大图时一张源页对应左右两个显示页
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PagePart {
+    #[default]
+    Whole,
+    Left,
+    Right,
+}
+
+/// 当前画面中的一页，导出整个画面时按阅读顺序传给后端
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SpreadPage {
+    /// 缓存中的图片路径
+    pub path: String,
+    pub index: usize,
+    pub part: PagePart,
+}
+
+/// 后端记录的当前画面，页码均为源页索引
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ViewState {
+    /// 画面中的第一页
+    pub current_page: usize,
+    /// 画面中的页数
+    pub size: usize,
+    /// 是否从右到左阅读
+    pub reading_direction: bool,
+    pub page_count: usize,
+}
+
+/// 命令行参数指定要打开的书，`page` 为从 1 开始的页码
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct LaunchTarget {
+    pub path: String,
+    pub page: Option<usize>,
+}
+
+/// 多选打开时的阅读队列，关闭程序时保存以便下次继续
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SavedQueue {
+    /// 正在读的书
+    pub current: String,
+    /// 之后排队的书
+    pub remaining: Vec<String>,
+}
+
+/// 书内搜索的一条结果
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    pub page: usize,
+    /// 匹配处及其前后的文字
+    pub snippet: String,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub enum CreateMangaResult {
-    Success([u8; 32], usize),
+    Success {
+        sha256: [u8; 32],
+        /// 载入批次，后端每次打开漫画时递增
+        epoch: u64,
+        page_count: usize,
+        /// 各章节起始页的索引，供进度条绘制章节分隔
+        chapter_starts: Vec<usize>,
+        capabilities: Capabilities,
+        /// 书本身建议的阅读方向，`None` 时沿用配置
+        right_to_left: Option<bool>,
+        /// 上次看到的页，可能超出当前页数，由前端截断
+        last_page: Option<usize>,
+    },
     NeedPassword,
     Other(String),
 }
@@ -50,7 +165,7 @@ pub enum CreateMangaResult {
 impl From<anyhow::Result<([u8; 32], usize)>> for CreateMangaResult {
     fn from(value: anyhow::Result<([u8; 32], usize)>) -> Self {
         match value {
-            Ok((sha256, x)) => CreateMangaResult::Success(sha256, x),
+            Ok((sha256, page_count)) => CreateMangaResult::Success { sha256, epoch: 0, page_count, chapter_starts: Vec::new(), capabilities: Default::default(), right_to_left: None, last_page: None },
             Err(e) => match e.downcast::<NeedPassword>() {
                 Ok(_) => CreateMangaResult::NeedPassword,
                 Err(e) => CreateMangaResult::Other(e.to_string()),
@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::walk_files;
+
+/// 缓存目录中记录上次打开时间的空文件，保留缓存时写入，也让目录在关闭时不被当作空目录删掉
+const LAST_USED: &str = "last_used";
+
+/// 一本书的缓存目录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedBook {
+    pub dir: PathBuf,
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+pub fn touch(dir: &Path) {
+    if let Err(e) = std::fs::write(dir.join(LAST_USED), "") {
+        eprintln!("记录缓存使用时间失败：{}", e);
+    }
+}
+
+/// 以目录内最新的修改时间作为上次使用时间
+pub fn scan(cache_root: &Path) -> Vec<CachedBook> {
+    let Ok(entries) = std::fs::read_dir(cache_root) else {
+        return Vec::new();
+    };
+
+    entries.flatten().filter(|x| x.file_type().is_ok_and(|x| x.is_dir())).map(|entry| {
+        let dir = entry.path();
+        let mut size = 0;
+        let mut last_used = SystemTime::UNIX_EPOCH;
+        walk_files(dir.as_path(), &mut |_, metadata| {
+            size += metadata.len();
+            if let Ok(modified) = metadata.modified() {
+                last_used = last_used.max(modified);
+            }
+        });
+        CachedBook { dir, size, last_used }
+    }).collect()
+}
+
+/// 选出要清理的缓存目录：先清理超过 `max_age` 未使用的，剩余总大小仍超过 `max_bytes` 时从最久未用的开始清理
+///
+/// `max_age` 为 `None`、`max_bytes` 为 0 时不做对应的限制
+pub fn select_evictions(mut books: Vec<CachedBook>, now: SystemTime, max_age: Option<Duration>, max_bytes: u64) -> Vec<PathBuf> {
+    books.sort_by_key(|x| x.last_used);
+    let expired = books.iter().take_while(|x| {
+        max_age.is_some_and(|max_age| now.duration_since(x.last_used).is_ok_and(|age| age > max_age))
+    }).count();
+
+    let mut total: u64 = books[expired..].iter().map(|x| x.size).sum();
+    let mut evicted = expired;
+    while max_bytes > 0 && total > max_bytes && evicted < books.len() {
+        total -= books[evicted].size;
+        evicted += 1;
+    }
+
+    books.into_iter().take(evicted).map(|x| x.dir).collect()
+}
+
+pub fn evict(cache_root: &Path, max_age_days: u32, max_bytes: u64) {
+    let max_age = (max_age_days > 0).then(|| Duration::from_secs(max_age_days as u64 * 24 * 60 * 60));
+    for dir in select_evictions(scan(cache_root), SystemTime::now(), max_age, max_bytes) {
+        match std::fs::remove_dir_all(dir.as_path()) {
+            Ok(()) => eprintln!("清理缓存 {}", dir.to_string_lossy()),
+            Err(e) => eprintln!("清理缓存 {} 失败：{}", dir.to_string_lossy(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn book(name: &str, size: u64, days_ago: u32, now: SystemTime) -> CachedBook {
+        CachedBook { dir: PathBuf::from(name), size, last_used: now - DAY * days_ago }
+    }
+
+    #[test]
+    fn expired_then_least_recent_books_are_evicted() {
+        let now = SystemTime::now();
+        let books = vec![book("new", 100, 0, now), book("old", 10, 40, now), book("mid", 300, 5, now), book("recent", 200, 1, now)];
+        // 只按时间：超过 30 天的清理
+        assert_eq!(select_evictions(books.clone(), now, Some(DAY * 30), 0), [PathBuf::from("old")]);
+        // 再按大小：剩下 600 字节超过 350，从最久未用的 mid 开始清理
+        assert_eq!(select_evictions(books.clone(), now, Some(DAY * 30), 350), [PathBuf::from("old"), PathBuf::from("mid")]);
+        assert_eq!(select_evictions(books.clone(), now, None, 0), Vec::<PathBuf>::new());
+        assert_eq!(select_evictions(Vec::new(), now, Some(DAY), 1), Vec::<PathBuf>::new());
+    }
+}
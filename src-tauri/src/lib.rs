@@ -1,38 +1,139 @@
-use tauri::{AppHandle, Emitter, Manager, State};
-use tauri::async_runtime::{Mutex, JoinHandle, spawn, block_on, channel};
-use tokio::sync::watch;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tauri::async_runtime::{Mutex, JoinHandle, Receiver, spawn, spawn_blocking, block_on, channel};
+use tokio::sync::{MutexGuard, watch};
 use notify::{Event, EventKind, RecursiveMode, Watcher, RecommendedWatcher};
 
+use std::collections::HashSet;
 use std::future::Future;
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
 use std::time::Duration;
 
-use shared::{CreateMangaResult, ImageData, LoadPage, SUPPORTED_FILE_FORMATS};
-use shared::config::{Config, Preset};
+use serde::Serialize;
+
+use shared::{Capabilities, CreateMangaResult, FormatInfo, ImageData, LaunchTarget, LoadPage, PagePart, SavedQueue, SearchHit, SpreadPage, ViewState, SUPPORTED_FILE_FORMATS};
+use shared::config::{Config, Preset, CONFIG_VERSION};
 
 pub mod source;
-use source::{PageSource, PageCache, create_source, write_cache};
+use source::{PageSource, PageCache, FileBytes, SolidPage, SourceOptions, TranscodeOptions, cache_dir_name, cache_path, clear_downloads, create_directory_source, create_source, is_url, volume_name_cmp, reencode_as_png, remove_thumbnails, run_low_priority, thumb_path, write_cache, write_thumbnail};
+
+mod wake_lock;
+use wake_lock::WakeLock;
+
+mod search;
+use search::search_texts;
+
+mod titles;
+
+mod progress;
+
+mod reading_queue;
+
+mod recent_files;
+
+mod window_state;
+
+mod cache_eviction;
+
+/// 页面索引及其写入缓存的结果
+type Written = (usize, io::Result<PageCache>);
+
+/// 影响后台加载方式的配置项
+#[derive(Debug, Clone)]
+struct LoadOptions {
+    solid_channel_capacity: usize,
+    solid_parallel_write: bool,
+    decode_low_priority: bool,
+    max_cached_pages: usize,
+    prefetch_multiplier: usize,
+    persist_cache: bool,
+    transcode: TranscodeOptions,
+}
+
+impl From<&Config> for LoadOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            solid_channel_capacity: config.solid_channel_capacity.max(1),
+            solid_parallel_write: config.solid_parallel_write,
+            decode_low_priority: config.decode_low_priority,
+            max_cached_pages: config.max_cached_pages,
+            prefetch_multiplier: config.prefetch_multiplier,
+            persist_cache: config.persist_cache,
+            transcode: TranscodeOptions::from(config),
+        }
+    }
+}
 
 struct MangaBook {
     cache_dir: PathBuf,
     source: Box<dyn PageSource>,
     caches: Vec<Option<PageCache>>,
     unloaded: usize,
+    /// 载入批次，同一文件重复打开时用于区分新旧事件
+    epoch: u64,
+    options: LoadOptions,
+    /// 前端要求优先载入的页，换成别的范围之前不会被丢弃；空范围表示没有
+    priority: std::ops::Range<usize>,
+    /// 载入失败的页，不再自动重试，清理缓存后才会重新尝试
+    failed: HashSet<usize>,
+    /// 用户固定的页，不会因缓存上限或清理缓存被丢弃
+    pinned: HashSet<usize>,
 }
 
 impl MangaBook {
-    pub fn new(source: Box<dyn PageSource>, cache_dir: PathBuf) -> Self {
+    pub fn new(source: Box<dyn PageSource>, cache_dir: PathBuf, epoch: u64, options: LoadOptions) -> Self {
         let unloaded = source.page_count();
         let caches = (0..unloaded).map(|_| None).collect();
-        Self {
+        let mut manga = Self {
             cache_dir,
             source,
             caches,
             unloaded,
+            epoch,
+            options,
+            priority: 0..0,
+            failed: HashSet::new(),
+            pinned: HashSet::new(),
+        };
+        manga.adopt_caches();
+        manga
+    }
+
+    /// 上次异常退出时残留的缓存文件直接沿用，免去重新解码
+    fn adopt_caches(&mut self) {
+        for (index, cache) in self.caches.iter_mut().enumerate() {
+            let path = cache_path(index, self.cache_dir.as_path());
+            if path.is_file() {
+                match PageCache::adopt(path, &self.options.transcode) {
+                    Ok(page_cache) => {
+                        cache.replace(page_cache);
+                        self.unloaded -= 1;
+                    },
+                    Err(e) => eprintln!("残留的第 {} 页缓存无效：{}", index, e),
+                }
+            }
         }
+
+        let adopted = self.page_count() - self.unloaded;
+        if adopted > 0 {
+            eprintln!("沿用了 {} 页残留缓存", adopted);
+        }
+    }
+
+    fn emit_adopted(&self, app: &AppHandle) {
+        for index in 0..self.page_count() {
+            if let Some(event) = self.loaded_event(index) {
+                app.emit("load_page", event).unwrap();
+            }
+        }
+    }
+
+    /// 已载入页面的事件，带上写缓存时检测到的空白页标记
+    fn loaded_event(&self, index: usize) -> Option<LoadPage> {
+        let page_cache = self.caches.get(index)?.as_ref()?;
+        Some(LoadPage::new(*self.sha256(), self.epoch, index, self.page_count(), page_cache.get_data()).with_blank(page_cache.is_blank()))
     }
 
     pub fn load(&mut self, index: usize) -> anyhow::Result<Option<ImageData>> {
@@ -40,7 +141,9 @@ impl MangaBook {
             match self.caches.get_mut(index) {
                 None | Some(Some(_)) => None,
                 Some(cache @None) => {
-                    self.source.cache(index, cache, self.cache_dir.as_path())?;
+                    let (cache_dir, transcode) = (self.cache_dir.as_path(), &self.options.transcode);
+                    let source = &mut self.source;
+                    run_low_priority(self.options.decode_low_priority, || source.cache(index, cache, cache_dir, transcode))?;
                     self.unloaded -= 1;
                     Some(cache.as_ref().unwrap().get_data())
                 }
@@ -53,7 +156,13 @@ impl MangaBook {
     }
 
     pub fn into_caches(self) -> Vec<PageCache> {
-        self.caches.into_iter().flatten().collect()
+        let persist = self.options.persist_cache;
+        self.caches.into_iter().flatten().map(|mut cache| {
+            if persist {
+                cache.persist();
+            }
+            cache
+        }).collect()
     }
 
     pub fn all_loaded(&self) -> bool {
@@ -64,52 +173,195 @@ impl MangaBook {
         self.source.sha256()
     }
 
+    pub fn chapter_offsets(&self) -> Vec<usize> {
+        self.source.chapter_offsets()
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        self.cache_dir.as_path()
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.source.title()
+    }
+
+    pub fn right_to_left(&self) -> Option<bool> {
+        self.source.right_to_left()
+    }
+
+    pub fn page_texts(&self) -> Vec<Option<String>> {
+        (0..self.page_count()).map(|index| self.source.page_text(index)).collect()
+    }
+
+    pub fn page_names(&self) -> Vec<Option<String>> {
+        (0..self.page_count()).map(|index| self.source.page_name(index)).collect()
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.source.capabilities()
+    }
+
+    pub fn prefetch_multiplier(&self) -> usize {
+        prefetch_multiplier(self.options.prefetch_multiplier, self.source.prefetch_hint())
+    }
+
     pub fn is_unloaded(&self, index: usize) -> bool {
-        self.caches.get(index).is_some_and(|x| x.is_none())
+        self.caches.get(index).is_some_and(|x| x.is_none()) && !self.failed.contains(&index)
+    }
+
+    /// 下一个要载入的页：优先范围内的页在前，其次是当前页附近；缓存已被固定的页占满时不再载入
+    pub fn next_to_load(&self, index: usize, size: usize) -> Option<usize> {
+        if self.pinned_fill_cache() {
+            return None;
+        }
+        self.priority.clone().find(|&index| self.is_unloaded(index))
+            .or_else(|| (index < self.page_count()).then(|| self.has_unloaded_nearby(index, size)).flatten())
+    }
+
+    /// 设置优先载入的范围，超出页数的部分会被截掉
+    pub fn set_priority(&mut self, range: std::ops::Range<usize>) {
+        let end = range.end.min(self.page_count());
+        self.priority = range.start.min(end)..end;
+    }
+
+    pub fn set_pinned(&mut self, pinned: HashSet<usize>) {
+        self.pinned = pinned;
     }
 
+    /// 已载入的固定页达到缓存上限，再载入新页只能立即丢弃
+    pub fn pinned_fill_cache(&self) -> bool {
+        let max = self.options.max_cached_pages;
+        max > 0 && self.pinned.iter().filter(|&&index| self.caches.get(index).is_some_and(Option::is_some)).count() >= max
+    }
+
+    /// 预载范围截到页数以内，避免超大的 `size` 让查找空转到 `usize::MAX`
     pub fn has_unloaded_nearby(&self, index: usize, size: usize) -> Option<usize> {
-        (index..=index + size * 2 + size / 2).chain((index.saturating_sub(size + size / 2)..index).rev()).find(|&index| self.is_unloaded(index))
+        let window = prefetch_window(index, size);
+        let end = (*window.end()).min(self.page_count());
+        (index..end).chain((*window.start()..index.min(end)).rev()).find(|&index| self.is_unloaded(index))
+    }
+
+    /// 丢弃预载范围以外未固定的缓存，返回被丢弃的页；尚未完成的优先载入随之取消
+    pub fn trim(&mut self, index: usize, size: usize) -> Vec<usize> {
+        self.priority = 0..0;
+        self.failed.clear();
+        let window = prefetch_window(index, size);
+        let trimmed: Vec<usize> = self.caches.iter().enumerate()
+            .filter(|(index, cache)| cache.is_some() && !window.contains(index) && !self.pinned.contains(index))
+            .map(|(index, _)| index)
+            .collect();
+        for &index in trimmed.iter() {
+            self.caches[index] = None;
+        }
+        self.unloaded += trimmed.len();
+        trimmed
+    }
+
+    /// 缓存页数超过 `max_cached_pages` 时，逐个丢弃离预载范围最远的页，返回被丢弃的页
+    ///
+    /// 预载范围、优先载入范围内的页与固定的页不会被丢弃，因此这些页本身比上限多时缓存页数仍可能超出
+    pub fn evict_over_budget(&mut self, index: usize, size: usize) -> Vec<usize> {
+        let max = self.options.max_cached_pages;
+        let mut evicted = Vec::new();
+        while max > 0 && self.page_count() - self.unloaded > max {
+            let (priority, pinned) = (&self.priority, &self.pinned);
+            let loaded = self.caches.iter().enumerate()
+                .filter(|(index, x)| x.is_some() && !priority.contains(index) && !pinned.contains(index))
+                .map(|(index, _)| index);
+            let Some(victim) = eviction_victim(loaded, index, size) else {
+                break;
+            };
+            self.caches[victim] = None;
+            self.unloaded += 1;
+            evicted.push(victim);
+        }
+        evicted
     }
 
-    pub async fn launch(self, rx: watch::Receiver<(usize, usize)>, stop: watch::Receiver<bool>, app: AppHandle) -> Vec<PageCache> {
+    pub async fn launch(self, rx: watch::Receiver<(usize, usize)>, stop: watch::Receiver<bool>, trim: watch::Receiver<u64>, preload: watch::Receiver<std::ops::Range<usize>>, pinned: watch::Receiver<HashSet<usize>>, app: AppHandle) -> Vec<PageCache> {
+        self.emit_adopted(&app);
         if self.source.is_solid() {
-            eprintln!("Solid compression detected");
-            self.launch_solid(app, stop).await
+            if self.all_loaded() {
+                eprintln!("All pages loaded, drop MangaBook");
+                self.into_caches()
+            } else {
+                eprintln!("Solid compression detected");
+                self.launch_solid(app, stop).await
+            }
         } else {
-            self.launch_random(app, rx, stop).await
+            self.launch_random(app, rx, stop, trim, preload, pinned).await
         }
     }
 
-    async fn launch_random(mut self, app: AppHandle, mut rx: watch::Receiver<(usize, usize)>, mut stop: watch::Receiver<bool>) -> Vec<PageCache> {
+    /// 全部载入后任务仍然保留，清理缓存后按需重新载入
+    async fn launch_random(
+        mut self,
+        app: AppHandle,
+        mut rx: watch::Receiver<(usize, usize)>,
+        mut stop: watch::Receiver<bool>,
+        mut trim: watch::Receiver<u64>,
+        mut preload: watch::Receiver<std::ops::Range<usize>>,
+        mut pinned: watch::Receiver<HashSet<usize>>,
+    ) -> Vec<PageCache> {
+        // 前端报告的是一屏的页数，预载、清理都按放大后的范围计算
+        let multiplier = self.prefetch_multiplier();
         loop {
+            let mut trim_requested = false;
             tokio::select! {
                 biased;
                 
                 _ = stop.wait_for(|x| *x) => {
                     break;
                 },
-                x = rx.wait_for(|(index, size)| *index < self.page_count() && self.has_unloaded_nearby(*index, *size).is_some()) => {
-                    let (index, size) = *x.unwrap();                    
-                    if let Some(next_to_load) = self.has_unloaded_nearby(index, size) {
+                Ok(()) = trim.changed() => {
+                    trim_requested = true;
+                },
+                Ok(()) = preload.changed() => {
+                    let range = preload.borrow_and_update().clone();
+                    self.set_priority(range);
+                },
+                Ok(()) = pinned.changed() => {
+                    let pinned = pinned.borrow_and_update().clone();
+                    self.set_pinned(pinned);
+                    if self.pinned_fill_cache() {
+                        app.emit("toast", "W固定的页已占满缓存上限，暂停载入新页").unwrap();
+                    }
+                },
+                x = rx.wait_for(|(index, size)| self.next_to_load(*index, size.saturating_mul(multiplier)).is_some()) => {
+                    let (index, size) = *x.unwrap();
+                    let size = size.saturating_mul(multiplier);
+                    if let Some(next_to_load) = self.next_to_load(index, size) {
                         eprintln!("Now loading page {:03}", next_to_load);
                         match self.load(next_to_load) {
-                            Ok(Some(image_data)) => {
+                            Ok(Some(_)) => {
                                 eprintln!("Loaded page {:03}", next_to_load);
-                                app.emit("load_page", LoadPage::new(*self.sha256(), next_to_load, self.page_count(), image_data)).unwrap();
+                                if let Some(event) = self.loaded_event(next_to_load) {
+                                    app.emit("load_page", event).unwrap();
+                                }
+                                for evicted in self.evict_over_budget(index, size) {
+                                    eprintln!("Evicted page {:03}", evicted);
+                                    app.emit("load_page", LoadPage::new(*self.sha256(), self.epoch, evicted, self.page_count(), ImageData::Loading)).unwrap();
+                                }
                             },
                             Ok(None) => (),
                             Err(e) => {
                                 eprintln!("Fail to load page {}: {}", next_to_load, e);
+                                self.failed.insert(next_to_load);
+                                app.emit("load_page", LoadPage::new(*self.sha256(), self.epoch, next_to_load, self.page_count(), ImageData::Error(e.to_string()))).unwrap();
                             }
                         }
                     }
                 },
             };
-            
-            if self.all_loaded() {
-                eprintln!("All pages loaded, drop MangaBook");
-                break;
+
+            // select 中的 rx 仍被借用，清理放在 select 之后
+            if trim_requested {
+                let (index, size) = *rx.borrow();
+                let trimmed = self.trim(index, size.saturating_mul(multiplier));
+                for &index in trimmed.iter() {
+                    app.emit("load_page", LoadPage::new(*self.sha256(), self.epoch, index, self.page_count(), ImageData::Loading)).unwrap();
+                }
+                app.emit("toast", format!("I已清理 {} 页缓存", trimmed.len())).unwrap();
             }
         }
         
@@ -117,12 +369,24 @@ impl MangaBook {
     }
 
     pub async fn launch_solid(mut self, app: AppHandle, mut stop: watch::Receiver<bool>) -> Vec<PageCache> {
-        let (tx, mut rx) = channel(200);
-        let cache_dir = self.cache_dir.as_path();
-        let page_count = self.page_count();
-        let sha256 = *self.sha256();
+        let capacity = self.options.solid_channel_capacity;
+        let (tx, rx) = channel(capacity);
+        let cache_dir = self.cache_dir.clone();
+        let transcode = self.options.transcode;
+        let low_priority = self.options.decode_low_priority;
 
         if self.source.get_all_page_bytes(tx) {
+            // 已沿用残留缓存的页面不再写盘，否则新缓存 drop 时会删掉同名文件
+            let adopted: Arc<Vec<bool>> = Arc::new(self.caches.iter().map(Option::is_some).collect());
+            let mut raw_rx = Some(rx);
+            let mut written_rx = None;
+            if self.options.solid_parallel_write {
+                let cache_dir = cache_dir.clone();
+                written_rx = Some(spawn_parallel_writer(raw_rx.take().unwrap(), capacity, Arc::clone(&adopted), move |index, content| {
+                    run_low_priority(low_priority, || write_cache(index, content, cache_dir.as_path(), &transcode))
+                }));
+            }
+
             loop {
                 tokio::select! {
                     biased;
@@ -130,16 +394,18 @@ impl MangaBook {
                     _ = stop.wait_for(|x| *x) => {
                         break;
                     },
-                    x = rx.recv() => {
-                        if let Some((index, content)) = x {
-                            match write_cache(index, content, cache_dir) {
+                    x = Self::next_written(&mut raw_rx, &mut written_rx, cache_dir.as_path(), &transcode, adopted.as_slice(), low_priority) => {
+                        if let Some((index, result)) = x {
+                            match result {
                                 Ok(page_cache) => {
-                                    let image_data = page_cache.get_data();
-                                    app.emit("load_page", LoadPage::new(sha256, index, page_count, image_data)).unwrap();
                                     self.caches[index].replace(page_cache);
+                                    if let Some(event) = self.loaded_event(index) {
+                                        app.emit("load_page", event).unwrap();
+                                    }
                                 },
                                 Err(e) => {
                                     eprintln!("Fail to write page cache: {}", e);
+                                    app.emit("load_page", LoadPage::new(*self.sha256(), self.epoch, index, self.page_count(), ImageData::Error(e.to_string()))).unwrap();
                                 }
                             }
                         } else {
@@ -150,17 +416,133 @@ impl MangaBook {
                 };
             }
 
-            self.caches.into_iter().flatten().collect()
+            self.into_caches()
         } else {
             panic!("不应在可随机读取的源上调用本方法")
         }
     }
+
+    /// 取下一张写入缓存的页面，`raw_rx` 存在时就地写盘，否则等待写盘任务的结果
+    async fn next_written(
+        raw_rx: &mut Option<Receiver<SolidPage>>,
+        written_rx: &mut Option<Receiver<JoinHandle<Written>>>,
+        cache_dir: &Path,
+        transcode: &TranscodeOptions,
+        adopted: &[bool],
+        low_priority: bool,
+    ) -> Option<Written> {
+        match (raw_rx, written_rx) {
+            (Some(rx), _) => loop {
+                let (index, content) = rx.recv().await?;
+                if !adopted[index] {
+                    break Some((index, content.map_err(io::Error::other).and_then(|content| {
+                        run_low_priority(low_priority, || write_cache(index, content, cache_dir, transcode))
+                    })));
+                }
+            },
+            (None, Some(rx)) => match rx.recv().await?.await {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    eprintln!("写入缓存的任务出错：{}", e);
+                    None
+                },
+            },
+            (None, None) => None,
+        }
+    }
+}
+
+/// 写盘另起任务，与解压并行；按接收顺序排队等待写入结果，保证显示顺序不变
+///
+/// `adopted` 中标记的页已沿用残留缓存，跳过不写；写入结果的队列与解压的通道容量相同
+fn spawn_parallel_writer<T, W>(mut rx: Receiver<SolidPage>, capacity: usize, adopted: Arc<Vec<bool>>, write: W) -> Receiver<JoinHandle<(usize, io::Result<T>)>>
+where
+    T: Send + 'static,
+    W: Fn(usize, FileBytes) -> io::Result<T> + Clone + Send + 'static,
+{
+    let (handle_tx, handle_rx) = channel(capacity);
+    spawn(async move {
+        while let Some((index, content)) = rx.recv().await {
+            if adopted[index] {
+                continue;
+            }
+            let write = write.clone();
+            let handle = spawn_blocking(move || (index, content.map_err(io::Error::other).and_then(|content| write(index, content))));
+            if handle_tx.send(handle).await.is_err() {
+                break;
+            }
+        }
+    });
+    handle_rx
+}
+
+/// 预载范围：当前页之后两屏半、之前一屏半
+///
+/// 索引与页数都来自前端，一律饱和运算，异常的值只会让范围贴到边界而不会溢出
+fn prefetch_window(index: usize, size: usize) -> std::ops::RangeInclusive<usize> {
+    let before = size.saturating_add(size / 2);
+    let after = size.saturating_mul(2).saturating_add(size / 2);
+    index.saturating_sub(before)..=index.saturating_add(after)
+}
+
+/// 实际使用的预载倍数：配置为 0 时采用源的建议，至少为 1
+fn prefetch_multiplier(configured: usize, hint: usize) -> usize {
+    if configured > 0 { configured } else { hint.max(1) }
+}
+
+/// 在已载入的页中找出离预载范围最远的一页，距离相同时丢弃靠前（已读过）的页；范围内的页不参与
+fn eviction_victim(loaded: impl Iterator<Item = usize>, index: usize, size: usize) -> Option<usize> {
+    let window = prefetch_window(index, size);
+    let distance = |x: usize| x.saturating_sub(*window.end()).max(window.start().saturating_sub(x));
+    loaded.filter(|&x| distance(x) > 0).max_by(|&a, &b| distance(a).cmp(&distance(b)).then(b.cmp(&a)))
 }
 
 struct AppState {
     handle: Mutex<Option<JoinHandle<Vec<PageCache>>>>,
     tx: watch::Sender<(usize, usize)>,
     stop: watch::Sender<bool>,
+    /// 每次请求清理缓存时递增
+    trim: watch::Sender<u64>,
+    /// 前端要求优先载入的页的范围
+    preload: watch::Sender<std::ops::Range<usize>>,
+    /// 用户固定的页
+    pinned: watch::Sender<HashSet<usize>>,
+    /// 前端最近一次报告的阅读方向，只用于查询
+    right_to_left: AtomicBool,
+    wake_lock: Mutex<Option<WakeLock>>,
+    /// 每次请求打开漫画时递增，只有最新的请求可以载入
+    generation: AtomicU64,
+    /// 串行化“停止旧书、载入新书”的过程
+    create_lock: Mutex<()>,
+    /// 当前漫画的基本信息，供载入任务以外的命令查询
+    book: Mutex<Option<Arc<BookInfo>>>,
+    /// 在最近打开的书之间切换时要打开的书，打开后不调整顺序，以便接着往下切换
+    recent_pending: Mutex<Option<String>>,
+}
+
+struct BookInfo {
+    sha256: [u8; 32],
+    path: PathBuf,
+    /// 书中元数据里的标题
+    metadata_title: Option<String>,
+    cache_dir: PathBuf,
+    page_count: usize,
+    page_texts: Vec<Option<String>>,
+    page_names: Vec<Option<String>>,
+    /// 固实压缩包只能整本解压，清理后无法单页重新载入
+    solid: bool,
+    /// 不保留缓存时，关闭漫画一并删除缩略图
+    persist_cache: bool,
+}
+
+impl BookInfo {
+    fn check_index(&self, index: usize) -> Result<(), String> {
+        if index < self.page_count {
+            Ok(())
+        } else {
+            Err(format!("页码越界：{} / {}", index, self.page_count))
+        }
+    }
 }
 
 impl AppState {
@@ -168,29 +550,110 @@ impl AppState {
         let (tx, _) = watch::channel((0, 1));
         let handle = Mutex::new(None);
         let (stop, _) = watch::channel(false);
-        Self { handle, tx, stop }
+        let (trim, _) = watch::channel(0);
+        let (preload, _) = watch::channel(0..0);
+        let (pinned, _) = watch::channel(HashSet::new());
+        let right_to_left = AtomicBool::new(false);
+        let wake_lock = Mutex::new(None);
+        let generation = AtomicU64::new(0);
+        let create_lock = Mutex::new(());
+        let book = Mutex::new(None);
+        let recent_pending = Mutex::new(None);
+        Self { handle, tx, stop, trim, preload, pinned, right_to_left, wake_lock, generation, create_lock, book, recent_pending }
     }
 
+    pub fn next_generation(&self) -> u64 {
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn is_latest(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) == generation
+    }
+
+    pub async fn keep_awake(&self) {
+        let mut mutex_guard = self.wake_lock.lock().await;
+        if mutex_guard.is_none() {
+            *mutex_guard = WakeLock::acquire();
+        }
+    }
+
+    /// 没有加载任务在收听时也要记下，`current_view` 读的是这里
     pub fn set_current_and_size(&self, current_page: usize, size: usize) {
-        _ = self.tx.send((current_page, size));
+        self.tx.send_replace((current_page, size));
+    }
+
+    pub fn current_and_size(&self) -> (usize, usize) {
+        *self.tx.borrow()
+    }
+
+    pub fn set_right_to_left(&self, right_to_left: bool) {
+        self.right_to_left.store(right_to_left, Ordering::SeqCst);
+    }
+
+    pub fn view_state(&self, page_count: usize) -> ViewState {
+        let (current_page, size) = self.current_and_size();
+        let reading_direction = self.right_to_left.load(Ordering::SeqCst);
+        ViewState { current_page, size, reading_direction, page_count }
     }
 
     pub async fn launch<F, Fut>(&self, task: F)
     where
-        F: FnOnce(watch::Receiver<(usize, usize)>, watch::Receiver<bool>) -> Fut,
+        F: FnOnce(watch::Receiver<(usize, usize)>, watch::Receiver<bool>, watch::Receiver<u64>, watch::Receiver<std::ops::Range<usize>>, watch::Receiver<HashSet<usize>>) -> Fut,
         Fut: Future<Output = Vec<PageCache>> + Send + 'static,
     {
-        self.stop().await;
+        self.stop_loading().await;
         let mut mutex_guard = self.handle.lock().await;
         let rx = self.tx.subscribe();
         let stop = self.stop.subscribe();
+        let trim = self.trim.subscribe();
         self.tx.send((0, 1)).unwrap();
         self.stop.send(false).unwrap();
-        let new_handle = spawn(task(rx, stop));
+        self.preload.send_replace(0..0);
+        let preload = self.preload.subscribe();
+        self.pinned.send_replace(HashSet::new());
+        let pinned = self.pinned.subscribe();
+        let new_handle = spawn(task(rx, stop, trim, preload, pinned));
         mutex_guard.replace(new_handle);
     }
 
+    /// 停止旧书并换上新书，已有更新的打开请求时放弃并返回 `None`
+    ///
+    /// 返回的锁持有期间其他打开请求不能换书，应在启动载入任务后再释放
+    async fn replace_book(&self, generation: u64, book_info: Arc<BookInfo>) -> Option<MutexGuard<'_, ()>> {
+        let create_guard = self.create_lock.lock().await;
+        if !self.is_latest(generation) {
+            return None;
+        }
+
+        self.set_current_and_size(0, 1);
+        self.stop().await;
+        self.book.lock().await.replace(book_info);
+        Some(create_guard)
+    }
+
+    /// 把后端记录的当前页写入阅读进度，前端最后一次异步保存可能来不及完成
+    pub async fn flush_progress(&self, path: &Path) {
+        let Some(book) = self.book.lock().await.clone() else {
+            return;
+        };
+        let (current, _) = self.current_and_size();
+        match progress::set(path, sha256_hex(&book.sha256).as_str(), current) {
+            Ok(()) => eprintln!("已保存阅读进度：第 {} 页", current + 1),
+            Err(e) => eprintln!("保存阅读进度失败：{}", e),
+        }
+    }
+
     pub async fn stop(&self) {
+        let book = self.book.lock().await.take();
+        self.wake_lock.lock().await.take();
+        self.stop_loading().await;
+        if let Some(book) = book.filter(|x| !x.persist_cache) {
+            remove_thumbnails(book.cache_dir.as_path());
+        }
+    }
+
+    /// 只结束载入任务，不清除当前漫画的信息
+    async fn stop_loading(&self) {
         _ = self.stop.send(true);
         let mut mutex_guard = self.handle.lock().await;
         if let Some(handle) = mutex_guard.take() {
@@ -200,14 +663,41 @@ impl AppState {
     }
 }
 
-struct ConfigState {
+/// 旧版本配置文件补上缺少的项并写入版本号后写回，原有的项与注释保持不变；先写临时文件再改名
+fn write_upgraded_config(path: &Path, s: &str) -> anyhow::Result<()> {
+    let mut document: toml_edit::DocumentMut = s.parse()?;
+    let preset: toml_edit::DocumentMut = Config::preset().to_string().parse()?;
+    fill_missing_items(document.as_table_mut(), preset.as_table());
+    document["version"] = toml_edit::value(CONFIG_VERSION as i64);
+    let part_path = path.with_extension("part");
+    std::fs::write(part_path.as_path(), document.to_string())?;
+    std::fs::rename(part_path, path)?;
+    Ok(())
+}
+
+/// 把 `preset` 中有而 `table` 中没有的项补进 `table`，两边都是表的逐层合并
+fn fill_missing_items(table: &mut toml_edit::Table, preset: &toml_edit::Table) {
+    for (key, item) in preset.iter() {
+        match (table.get_mut(key), item) {
+            (Some(toml_edit::Item::Table(existing)), toml_edit::Item::Table(preset)) => fill_missing_items(existing, preset),
+            (Some(_), _) => {},
+            (None, item) => {
+                table.insert(key, item.clone());
+            },
+        }
+    }
+}
+
+struct ConfigState<R: Runtime = tauri::Wry> {
     file_path: PathBuf,
     config: Mutex<Config>,
-    app: AppHandle,
+    app: AppHandle<R>,
     message_id: AtomicU8,
+    /// 前端注册完所有事件监听后才发送配置，以免事件丢失
+    frontend_ready: AtomicBool,
 }
 
-impl ConfigState {
+impl<R: Runtime> ConfigState<R> {
     const MESSAGE: [&str; 6] = [
         "S读取配置文件成功",
         "W反序列化配置文件失败，将使用预设配置",
@@ -217,16 +707,18 @@ impl ConfigState {
         "W新建预设配置文件失败",
     ];
 
-    pub fn new(app: AppHandle) -> Self {
+    pub fn new(app: AppHandle<R>) -> Self {
         let file_path = app.path().resolve("config.toml", tauri::path::BaseDirectory::AppData).unwrap();
         let config = Default::default();
         let message_id = AtomicU8::new(u8::MAX);
+        let frontend_ready = AtomicBool::new(false);
 
         Self {
             file_path,
             config,
             app,
             message_id,
+            frontend_ready,
         }
     }
 
@@ -234,8 +726,17 @@ impl ConfigState {
         let config_file_path = self.file_path.as_path();
         if config_file_path.is_file() {
             match std::fs::read_to_string(config_file_path) {
-                Ok(s) => match Config::try_from(s.as_str()) {
-                    Ok(config) => {
+                Ok(s) => match Config::migrate(s.as_str()) {
+                    Ok((mut config, version)) => {
+                        if version < config.version {
+                            match write_upgraded_config(config_file_path, s.as_str()) {
+                                Ok(()) => eprintln!("配置文件已从版本 {} 升级到 {}", version, config.version),
+                                Err(e) => eprintln!("写回升级后的配置文件失败：{}", e),
+                            }
+                        }
+                        for message in config.validate() {
+                            eprintln!("{}", message);
+                        }
                         let m = 0;
                         eprintln!("{}", Self::MESSAGE[m as usize]);
                         std::io::stderr().flush().unwrap();
@@ -314,8 +815,7 @@ impl ConfigState {
                     }
                 }
                 
-                if self.load_config().await {
-                    self.send_config_and_message().await;
+                if self.reload().await {
                     if let Some(win) = self.app.get_webview_window("guide") {
                         let _ = win.close();
                     }
@@ -326,6 +826,27 @@ impl ConfigState {
         }
     }
 
+    /// 重新读取配置文件，有变化且前端已就绪时发送，返回是否发送了
+    ///
+    /// 前端就绪前的修改无需发送，就绪时会发送最新的配置
+    async fn reload(&self) -> bool {
+        if self.load_config().await && self.frontend_ready.load(Ordering::Acquire) {
+            self.send_config_and_message().await;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub async fn config(&self) -> Config {
+        self.config.lock().await.clone()
+    }
+
+    pub async fn set_frontend_ready(&self) {
+        self.frontend_ready.store(true, Ordering::Release);
+        self.send_config_and_message().await;
+    }
+
     pub async fn send_config_and_message(&self) {
         let message = loop {
             match self.message_id.load(Ordering::Relaxed) {
@@ -333,7 +854,7 @@ impl ConfigState {
                 x => break Self::MESSAGE[x as usize],
             }
         };
-        let config = self.config.lock().await.clone();
+        let config = self.config().await;
         self.app.emit("load_config", config).unwrap();
         self.app.emit("toast", message).unwrap();
     }
@@ -341,7 +862,7 @@ impl ConfigState {
     pub fn get_script(&self) -> String {
         dbg!("running here");
         block_on(async move {
-            self.config.lock().await.key_bind.to_replace_script(serde_json::to_string(self.file_path.as_path()).unwrap())
+            self.config.lock().await.key_bind.to_replace_script(self.file_path.to_string_lossy().as_ref())
         })
     }
 
@@ -353,7 +874,7 @@ impl ConfigState {
             let app = self.app.clone();
             std::thread::spawn(move || {
                 std::thread::sleep(std::time::Duration::from_millis(10));
-                tauri::WebviewWindowBuilder::new(
+                let window = tauri::WebviewWindowBuilder::new(
                     &app,
                     "guide",
                     tauri::WebviewUrl::App("public/guide.html".into()),
@@ -362,134 +883,950 @@ impl ConfigState {
                 .initialization_script(script)
                 .inner_size(600.0, 800.0)
                 .resizable(true)
-                .build()
-                .expect("open guide window");
+                .build();
+                if let Err(e) = window {
+                    eprintln!("打开操作指南窗口失败：{}", e);
+                }
+            });
+        }
+    }
+
+    /// 设置窗口与主窗口共用前端，由注入的全局变量区分
+    pub fn show_settings(&self) {
+        if let Some(window) = self.app.get_webview_window("settings") {
+            let _ = window.set_focus();
+        } else {
+            let app = self.app.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                let window = tauri::WebviewWindowBuilder::new(
+                    &app,
+                    "settings",
+                    tauri::WebviewUrl::App("index.html".into()),
+                )
+                .title("设置")
+                .initialization_script("window.__SETTINGS_WINDOW__ = true;")
+                .inner_size(600.0, 800.0)
+                .resizable(true)
+                .build();
+                if let Err(e) = window {
+                    eprintln!("打开设置窗口失败：{}", e);
+                }
             });
         }
     }
+
+    /// 检查后写入配置文件，由文件监视重新载入；有不合法的项或键位冲突时不写入，返回原因
+    pub fn save(&self, mut config: Config) -> Result<(), String> {
+        let mut problems = config.clone().validate();
+        problems.extend(config.key_bind.conflicts());
+        if !problems.is_empty() {
+            return Err(problems.join("；"));
+        }
+        config.version = CONFIG_VERSION;
+        std::fs::write(self.file_path.as_path(), config.to_string()).map_err(|e| format!("写入配置文件失败：{}", e))
+    }
 }
 
-fn create_manga_in_background(path: String, password: Option<String>, app: AppHandle, state: Arc<AppState>) {
+fn create_manga_in_background(path: String, password: Option<String>, generation: u64, app: AppHandle, state: Arc<AppState>) {
+    let config = block_on(async {
+        app.state::<Arc<ConfigState>>().config().await
+    });
+
     let try_create_manga = || -> anyhow::Result<MangaBook> {
         let path = Path::new(path.as_str());
-        let source: Box<dyn PageSource> = create_source(path, password)?;
-        let cache_dir = app.path().resolve(Path::new("cache").join(source.sha256().iter().map(|b| format!("{:02x}", b)).collect::<String>()), tauri::path::BaseDirectory::AppData)?;
+        let options = SourceOptions::from(&config);
+        let source: Box<dyn PageSource> = create_source(path, password, &options)?;
+        // 残留缓存按页码沿用，排序、转码等配置不同时页码对应的图片也不同，需分开存放
+        let cache_name = cache_dir_name(source.sha256(), &options);
+        let cache_dir = app.path().resolve(Path::new("cache").join(cache_name), tauri::path::BaseDirectory::AppData)?;
         std::fs::create_dir_all(cache_dir.as_path())?;
-        let manga = MangaBook::new(source, cache_dir);
+        if config.persist_cache {
+            cache_eviction::touch(cache_dir.as_path());
+        }
+        let manga = MangaBook::new(source, cache_dir, generation, LoadOptions::from(&config));
         Ok(manga)
     };
 
     let manga = match try_create_manga() {
         Ok(x) => x,
         Err(e) => {
-            app.emit::<CreateMangaResult>("load_manga", Err(e).into()).unwrap();
+            // 切换到最近的书时打开失败，清掉待打开的记录，以免之后正常打开同一本时漏记
+            block_on(async {
+                let mut pending = state.recent_pending.lock().await;
+                if pending.as_deref() == Some(path.as_str()) {
+                    pending.take();
+                }
+            });
+            if state.is_latest(generation) {
+                app.emit::<CreateMangaResult>("load_manga", Err(e).into()).unwrap();
+            }
             return;
         },
     };
 
-    let sha256 = manga.sha256();
+    let sha256 = *manga.sha256();
     let page_count = manga.page_count();
-    state.set_current_and_size(0, 1);
-
-    block_on(async {
-        state.stop().await;
+    let chapter_starts = manga.chapter_offsets();
+    let capabilities = manga.capabilities();
+    let right_to_left = manga.right_to_left();
+    let last_page = progress::get(progress_path(&app).as_path(), sha256_hex(&sha256).as_str());
+    let book_info = Arc::new(BookInfo {
+        sha256,
+        path: PathBuf::from(path.as_str()),
+        metadata_title: manga.title(),
+        cache_dir: manga.cache_dir().to_path_buf(),
+        page_count,
+        page_texts: manga.page_texts(),
+        page_names: manga.page_names(),
+        solid: capabilities.solid,
+        persist_cache: config.persist_cache,
     });
-    app.emit("load_manga", CreateMangaResult::Success(*sha256, page_count)).unwrap();
+    let keep_awake = config.keep_awake;
+    let suspicious = looks_undetected(Path::new(path.as_str()), page_count, config.small_book_warning_pages);
 
     block_on(async move {
-        state.launch(async move |rx, stop| manga.launch(rx, stop, app).await).await;
+        let Some(_create_guard) = state.replace_book(generation, Arc::clone(&book_info)).await else {
+            eprintln!("已有更新的打开请求，放弃载入第 {} 次请求的漫画", generation);
+            return;
+        };
+
+        apply_title(&app, &book_info);
+        if state.recent_pending.lock().await.take().as_deref() != Some(path.as_str()) {
+            if let Err(e) = recent_files::record(recent_path(&app).as_path(), path.as_str()) {
+                eprintln!("保存最近打开的书失败：{}", e);
+            }
+        }
+        app.emit("load_manga", CreateMangaResult::Success { sha256, epoch: generation, page_count, chapter_starts, capabilities, right_to_left, last_page }).unwrap();
+        if suspicious {
+            app.emit("toast", format!("W检测到 {} 页，可能未正确识别图片", page_count)).unwrap();
+        }
+        state.launch(async move |rx, stop, trim, preload, pinned| manga.launch(rx, stop, trim, preload, pinned, app).await).await;
+        if keep_awake {
+            state.keep_awake().await;
+        }
     });
 }
 
-#[tauri::command]
-fn create_manga(path: String, pwd: Option<String>, app: AppHandle, state: State<Arc<AppState>>) {
-    let arc = state.inner().clone();
-    std::thread::spawn(move || create_manga_in_background(path, pwd, app, arc));
+fn sha256_hex(sha256: &[u8; 32]) -> String {
+    sha256.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
-#[tauri::command]
-fn set_current(current: usize, size: usize, state: State<Arc<AppState>>) {
-    eprintln!(">>> page {:03} - {:03}", current, current + size - 1);
-    state.set_current_and_size(current, size);
+fn titles_path(app: &AppHandle) -> PathBuf {
+    app.path().resolve("titles.json", tauri::path::BaseDirectory::AppData).unwrap()
 }
 
-#[tauri::command]
-fn pick_file(app: AppHandle) -> Option<String> {
-    let window = app.get_webview_window("main").unwrap();
+fn queue_path(app: &AppHandle) -> PathBuf {
+    app.path().resolve("queue.toml", tauri::path::BaseDirectory::AppData).unwrap()
+}
 
-    rfd::FileDialog::new()
-        .set_title("选择漫画")
-        .add_filter("支持的格式", SUPPORTED_FILE_FORMATS)
-        .set_parent(&window)
-        .pick_file().map(|p| p.to_string_lossy().into_owned())
+fn recent_path(app: &AppHandle) -> PathBuf {
+    app.path().resolve("recent.toml", tauri::path::BaseDirectory::AppData).unwrap()
 }
 
+/// 最近打开的书中，当前这本更早（`older`）或更近的一本，跳过已不存在的文件
 #[tauri::command]
-fn show_guide(state: State<Arc<ConfigState>>) {
-    state.show_guide();
+async fn recent_book(older: bool, app: AppHandle, state: State<'_, Arc<AppState>>) -> Result<Option<String>, String> {
+    let recent = recent_files::load(recent_path(&app).as_path());
+    let current = state.book.lock().await.as_ref().map(|x| x.path.to_string_lossy().into_owned());
+    let next = recent_files::neighbor(recent.as_slice(), current.as_deref(), older, |x| is_url(x) || Path::new(x).exists()).map(String::from);
+    state.recent_pending.lock().await.clone_from(&next);
+    Ok(next)
 }
 
+/// 每打开一本书时保存当前的阅读队列
 #[tauri::command]
-fn focus_window(app: AppHandle) {
-    let window = app.get_webview_window("main").unwrap();
-    window.unminimize().unwrap();
-    window.set_focus().unwrap();
+async fn save_queue(queue: SavedQueue, app: AppHandle) -> Result<(), String> {
+    reading_queue::save(queue_path(&app).as_path(), &queue).map_err(|e| e.to_string())
 }
 
-#[tauri::command]
-fn read_config(state: State<Arc<ConfigState>>) {
-    let config_state = Arc::clone(state.inner());
-    spawn(async move {
-        eprintln!("从这里读取");
-        config_state.send_config_and_message().await;
-    });
+/// 启动参数只在第一次查询时生效，页面重新载入后不再重复打开
+static LAUNCH_TARGET_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// 从启动参数中取出要打开的书与页码
+///
+/// 第一个参数为路径，可在末尾加 `#page=N` 指定页码，也可以把页码作为第二个参数；
+/// 带 `#page=` 的整个参数本身就是存在的文件时按文件名处理
+fn parse_launch_args(args: &[String], exists: impl Fn(&str) -> bool) -> Option<LaunchTarget> {
+    let first = args.first()?;
+    let second = args.get(1).and_then(|x| x.parse::<usize>().ok());
+    let (path, page) = match first.rsplit_once("#page=") {
+        Some((path, page)) if !exists(first.as_str()) => match page.parse::<usize>() {
+            Ok(page) => (path, Some(page)),
+            Err(_) => (first.as_str(), None),
+        },
+        _ => (first.as_str(), None),
+    };
+    Some(LaunchTarget { path: path.to_string(), page: page.or(second) })
 }
 
+/// 以文件关联或命令行启动时要打开的书，没有时返回 `None`
 #[tauri::command]
-fn get_a_md5() -> [u8; 16] {
-    u128::to_le_bytes(u128::MAX)
+fn launch_target() -> Option<LaunchTarget> {
+    if LAUNCH_TARGET_TAKEN.swap(true, Ordering::SeqCst) {
+        return None;
+    }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let target = parse_launch_args(args.as_slice(), |x| Path::new(x).exists())?;
+    if !Path::new(target.path.as_str()).exists() {
+        eprintln!("启动参数中的 {} 不存在", target.path);
+        return None;
+    }
+    Some(target)
 }
 
+/// 启动时取回上次的阅读队列，已不存在的书跳过并提示
 #[tauri::command]
-fn sleep_5s() {
-    eprintln!("开始等待");
-    std::thread::sleep(std::time::Duration::from_secs(5));
-    eprintln!("等待完毕");
+async fn load_queue(app: AppHandle, config_state: State<'_, Arc<ConfigState>>) -> Result<Option<SavedQueue>, String> {
+    if !config_state.config().await.resume_queue {
+        return Ok(None);
+    }
+    let Some(queue) = reading_queue::load(queue_path(&app).as_path()) else {
+        return Ok(None);
+    };
+    let (queue, missing) = reading_queue::drop_missing(queue, |x| Path::new(x).exists());
+    for path in missing {
+        eprintln!("队列中的 {} 已不存在", path);
+        app.emit("toast", format!("W队列中的书已不存在，已跳过：{}", path)).unwrap();
+    }
+    Ok(queue)
 }
 
+fn progress_path(app: &AppHandle) -> PathBuf {
+    app.path().resolve("progress.toml", tauri::path::BaseDirectory::AppData).unwrap()
+}
+
+/// 记录这本书看到的源页，下次打开时从这里继续
 #[tauri::command]
-fn toggle_fullscreen(window: tauri::WebviewWindow) {
-    let is_fullscreen = window.is_fullscreen().unwrap_or_default();
-    match window.set_fullscreen(!is_fullscreen) {
-        Ok(()) => eprintln!("Toggled fullscreen."),
-        Err(e) => eprintln!("Error when toggling fullscreen: {}", e),
-    }
+async fn save_progress(sha256: [u8; 32], page: usize, app: AppHandle) -> Result<(), String> {
+    progress::set(progress_path(&app).as_path(), sha256_hex(&sha256).as_str(), page).map_err(|e| e.to_string())
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .setup(|app| {
-            use tauri::WindowEvent;
+/// 按优先级取得书名，设为主窗口标题
+fn apply_title(app: &AppHandle, book: &BookInfo) {
+    let title_override = titles::get_override(titles_path(app).as_path(), sha256_hex(&book.sha256).as_str());
+    let title = titles::resolve_title(title_override, book.metadata_title.clone(), book.path.as_path());
+    if let Some(window) = app.get_webview_window("main") {
+        if let Err(e) = window.set_title(title.as_str()) {
+            eprintln!("设置窗口标题失败：{}", e);
+        }
+    }
+}
 
-            let main_win = app.get_webview_window("main").unwrap(); // 主窗口 label=main
-            let app_handle = app.handle().clone();
+/// 体积超过该值却只识别出极少页面时，多半是图片未被识别（后缀不对、目录嵌套等）
+const SMALL_BOOK_MIN_BYTES: u64 = 10 << 20;
 
-            let config_state = Arc::new(ConfigState::new(app.handle().clone()));
-            app.manage(Arc::clone(&config_state));
+fn looks_undetected(path: &Path, page_count: usize, max_pages: usize) -> bool {
+    if max_pages == 0 || page_count > max_pages {
+        return false;
+    }
 
-            spawn(async move {
-                config_state.keep_watching().await;
-            });
+    let size = if path.is_dir() {
+        dir_size(path)
+    } else {
+        std::fs::metadata(path).map(|x| x.len()).unwrap_or_default()
+    };
+    size > SMALL_BOOK_MIN_BYTES
+}
 
-            main_win.on_window_event(move |evt| {
-                match evt {
+/// 递归遍历目录下的所有文件，单个条目出错（如权限不足）时跳过而不中断遍历
+fn walk_files(dir: &Path, f: &mut impl FnMut(&Path, &std::fs::Metadata)) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("读取目录 {} 失败：{}", dir.to_string_lossy(), e);
+            return;
+        }
+    };
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("读取目录条目失败：{}", e);
+                continue;
+            }
+        };
+        let entry_path = entry.path();
+        match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => walk_files(entry_path.as_path(), f),
+            Ok(metadata) => f(entry_path.as_path(), &metadata),
+            Err(e) => eprintln!("读取 {} 的元数据失败：{}", entry_path.to_string_lossy(), e),
+        }
+    }
+}
+
+#[tauri::command]
+fn create_manga(path: String, pwd: Option<String>, app: AppHandle, state: State<Arc<AppState>>) {
+    let arc = state.inner().clone();
+    let generation = arc.next_generation();
+    std::thread::spawn(move || create_manga_in_background(path, pwd, generation, app, arc));
+}
+
+#[tauri::command]
+fn set_current(current: usize, size: usize, rtl: bool, state: State<Arc<AppState>>) {
+    eprintln!(">>> page {:03} - {:03}", current, current.saturating_add(size).saturating_sub(1));
+    state.set_right_to_left(rtl);
+    state.set_current_and_size(current, size);
+}
+
+/// 后端记录的当前画面，与前端最近一次 `set_current` 一致，供外部工具查询
+#[tauri::command]
+async fn current_view(state: State<'_, Arc<AppState>>) -> Result<ViewState, String> {
+    let page_count = state.book.lock().await.as_ref().map_or(0, |x| x.page_count);
+    Ok(state.view_state(page_count))
+}
+
+#[tauri::command]
+fn pick_file(app: AppHandle) -> Option<String> {
+    let window = app.get_webview_window("main").unwrap();
+
+    rfd::FileDialog::new()
+        .set_title("选择漫画")
+        .add_filter("支持的格式", SUPPORTED_FILE_FORMATS)
+        .set_parent(&window)
+        .pick_file().map(|p| p.to_string_lossy().into_owned())
+}
+
+/// 一次选择多个文件，不支持的格式直接略过
+#[tauri::command]
+fn pick_files(app: AppHandle) -> Vec<String> {
+    let window = app.get_webview_window("main").unwrap();
+
+    rfd::FileDialog::new()
+        .set_title("选择漫画（可多选）")
+        .add_filter("支持的格式", SUPPORTED_FILE_FORMATS)
+        .set_parent(&window)
+        .pick_files()
+        .map(supported_paths)
+        .unwrap_or_default()
+}
+
+/// 保留受支持的文件，保持选择时的顺序
+fn supported_paths(paths: Vec<PathBuf>) -> Vec<String> {
+    paths.into_iter()
+        .filter(|p| is_supported_file(p))
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// 选择一个目录，把其中的图片作为一本漫画打开
+#[tauri::command]
+fn pick_folder(app: AppHandle) -> Option<String> {
+    let window = app.get_webview_window("main").unwrap();
+
+    rfd::FileDialog::new()
+        .set_title("选择漫画文件夹")
+        .set_parent(&window)
+        .pick_folder().map(|p| p.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+fn show_guide(state: State<Arc<ConfigState>>) {
+    state.show_guide();
+}
+
+#[tauri::command]
+fn show_settings(state: State<Arc<ConfigState>>) {
+    state.show_settings();
+}
+
+#[tauri::command]
+async fn get_config(state: State<'_, Arc<ConfigState>>) -> Result<Config, String> {
+    Ok(state.config().await)
+}
+
+#[tauri::command]
+fn save_config(config: Config, state: State<Arc<ConfigState>>) -> Result<(), String> {
+    state.save(config)
+}
+
+#[tauri::command]
+fn focus_window(app: AppHandle) {
+    let window = app.get_webview_window("main").unwrap();
+    window.unminimize().unwrap();
+    window.set_focus().unwrap();
+}
+
+/// 前端注册完所有事件监听后调用，此后后端才开始发送配置
+#[tauri::command]
+fn frontend_ready(state: State<Arc<ConfigState>>) {
+    let config_state = Arc::clone(state.inner());
+    spawn(async move {
+        eprintln!("前端已就绪");
+        config_state.set_frontend_ready().await;
+    });
+}
+
+#[tauri::command]
+fn cache_size(app: AppHandle) -> u64 {
+    let cache_dir = app.path().resolve("cache", tauri::path::BaseDirectory::AppData).unwrap();
+    dir_size(cache_dir.as_path())
+}
+
+/// 目录下所有文件（含子目录）的总字节数
+fn dir_size(dir: &Path) -> u64 {
+    let mut size = 0;
+    walk_files(dir, &mut |_, metadata| size += metadata.len());
+    size
+}
+
+/// 用户反馈问题时附带的诊断信息
+#[derive(Serialize, Debug)]
+struct SupportBundle {
+    app_version: String,
+    os: String,
+    arch: String,
+    config_path: String,
+    /// 配置文件原文，密码等敏感项已打码
+    config: String,
+    cache_size: u64,
+    cached_books: usize,
+}
+
+/// 将配置文件中键名含 password / pwd 的行打码
+fn redact_config(config: &str) -> String {
+    config.lines().map(|line| {
+        match line.split_once('=') {
+            Some((key, _)) if ["password", "pwd"].iter().any(|x| key.to_ascii_lowercase().contains(x)) => {
+                format!("{}= \"<redacted>\"", key)
+            },
+            _ => line.to_string(),
+        }
+    }).collect::<Vec<String>>().join("\n")
+}
+
+#[tauri::command]
+fn support_bundle(app: AppHandle, state: State<Arc<ConfigState>>) -> SupportBundle {
+    // 用 ~ 代替用户目录，避免泄露用户名
+    let hide_home = |path: &Path| -> String {
+        match app.path().home_dir() {
+            Ok(home) => match path.strip_prefix(home.as_path()) {
+                Ok(rest) => Path::new("~").join(rest).to_string_lossy().into_owned(),
+                Err(_) => path.to_string_lossy().into_owned(),
+            },
+            Err(_) => path.to_string_lossy().into_owned(),
+        }
+    };
+
+    let config = match std::fs::read_to_string(state.file_path.as_path()) {
+        Ok(s) => redact_config(s.as_str()),
+        Err(e) => format!("读取配置文件失败：{}", e),
+    };
+    let cache_dir = app.path().resolve("cache", tauri::path::BaseDirectory::AppData).unwrap();
+    let cached_books = std::fs::read_dir(cache_dir.as_path()).map(|x| x.flatten().count()).unwrap_or_default();
+
+    SupportBundle {
+        app_version: app.package_info().version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        config_path: hide_home(state.file_path.as_path()),
+        config,
+        cache_size: cache_size(app.clone()),
+        cached_books,
+    }
+}
+
+/// 只接受缓存目录下的文件，避免前端借此读写任意路径
+fn resolve_cached_page(app: &AppHandle, path: &str) -> anyhow::Result<PathBuf> {
+    let cache_dir = app.path().resolve("cache", tauri::path::BaseDirectory::AppData)?.canonicalize()?;
+    let path = Path::new(path).canonicalize()?;
+    anyhow::ensure!(path.starts_with(cache_dir.as_path()), "不是缓存中的页面");
+    Ok(path)
+}
+
+#[tauri::command]
+fn export_page(path: String, index: usize, app: AppHandle) {
+    let window = app.get_webview_window("main").unwrap();
+    let try_export = || -> anyhow::Result<bool> {
+        let path = resolve_cached_page(&app, path.as_str())?;
+        // 缓存文件没有后缀，按内容推断
+        let ext = image::ImageReader::open(path.as_path())?
+            .with_guessed_format()?
+            .format()
+            .and_then(|x| x.extensions_str().first().copied())
+            .unwrap_or("png");
+        let target = rfd::FileDialog::new()
+            .set_title("导出当前页")
+            .set_file_name(format!("{:03}.{}", index + 1, ext))
+            .add_filter(ext, &[ext])
+            .set_parent(&window)
+            .save_file();
+        match target {
+            Some(target) => {
+                std::fs::copy(path, target)?;
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    };
+
+    match try_export() {
+        Ok(true) => app.emit("toast", format!("S已导出第 {} 页", index + 1)).unwrap(),
+        Ok(false) => {},
+        Err(e) => app.emit("toast", format!("E导出失败：{}", e)).unwrap(),
+    }
+}
+
+/// 把同一画面的几页缩放到相同高度后从左到右拼成一张图
+fn compose_spread(images: &[image::DynamicImage]) -> image::RgbaImage {
+    let height = images.iter().map(|x| x.height()).max().unwrap_or_default();
+    let scaled: Vec<image::RgbaImage> = images.iter().map(|x| {
+        if x.height() == height {
+            x.to_rgba8()
+        } else {
+            let width = (x.width() as u64 * height as u64 / x.height().max(1) as u64).max(1) as u32;
+            x.resize_exact(width, height, image::imageops::FilterType::Lanczos3).into_rgba8()
+        }
+    }).collect();
+
+    let width = scaled.iter().map(|x| x.width()).sum();
+    let mut canvas = image::RgbaImage::new(width, height);
+    let mut x = 0;
+    for image in scaled.iter() {
+        image::imageops::overlay(&mut canvas, image, x, 0);
+        x += image.width() as i64;
+    }
+    canvas
+}
+
+fn crop_part(image: image::DynamicImage, part: PagePart) -> image::DynamicImage {
+    let (width, height) = (image.width(), image.height());
+    match part {
+        PagePart::Whole => image,
+        PagePart::Left => image.crop_imm(0, 0, width / 2, height),
+        PagePart::Right => image.crop_imm(width / 2, 0, width - width / 2, height),
+    }
+}
+
+/// 将当前画面的几页拼成一张 png 导出，`pages` 按阅读顺序排列，`reverse` 为真时从右到左摆放
+///
+/// 画面中只有一张完整的页时与 `export_page` 相同
+#[tauri::command]
+fn export_spread(pages: Vec<SpreadPage>, reverse: bool, app: AppHandle) {
+    if let [page] = pages.as_slice() {
+        if page.part == PagePart::Whole {
+            return export_page(page.path.clone(), page.index, app);
+        }
+    }
+    let (Some(first), Some(last)) = (pages.iter().map(|x| x.index).min(), pages.iter().map(|x| x.index).max()) else {
+        return;
+    };
+
+    let window = app.get_webview_window("main").unwrap();
+    let try_export = || -> anyhow::Result<bool> {
+        let mut images = pages.iter().map(|page| {
+            let path = resolve_cached_page(&app, page.path.as_str())?;
+            Ok(crop_part(image::open(path)?, page.part))
+        }).collect::<anyhow::Result<Vec<_>>>()?;
+        if reverse {
+            images.reverse();
+        }
+        let target = rfd::FileDialog::new()
+            .set_title("导出当前画面")
+            .set_file_name(format!("{:03}-{:03}.png", first + 1, last + 1))
+            .add_filter("png", &["png"])
+            .set_parent(&window)
+            .save_file();
+        match target {
+            Some(target) => {
+                compose_spread(images.as_slice()).save_with_format(target, image::ImageFormat::Png)?;
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    };
+
+    match try_export() {
+        Ok(true) => app.emit("toast", format!("S已导出第 {} - {} 页", first + 1, last + 1)).unwrap(),
+        Ok(false) => {},
+        Err(e) => app.emit("toast", format!("E导出失败：{}", e)).unwrap(),
+    }
+}
+
+#[tauri::command]
+fn copy_page(path: String, index: usize, app: AppHandle) {
+    let try_copy = || -> anyhow::Result<()> {
+        let path = resolve_cached_page(&app, path.as_str())?;
+        let image = image::open(path)?.into_rgba8();
+        let (width, height) = image.dimensions();
+        let image_data = arboard::ImageData {
+            width: width as usize,
+            height: height as usize,
+            bytes: image.into_raw().into(),
+        };
+        arboard::Clipboard::new()?.set_image(image_data)?;
+        Ok(())
+    };
+
+    match try_copy() {
+        Ok(()) => app.emit("toast", format!("S已复制第 {} 页", index + 1)).unwrap(),
+        Err(e) => app.emit("toast", format!("E复制失败：{}", e)).unwrap(),
+    }
+}
+
+/// 缩略图长边的像素数
+const THUMBNAIL_SIDE: u32 = 200;
+
+/// 返回从 `start` 起 `count` 页的缩略图路径，没有时由已载入的页面生成；尚未载入的页返回 `None`，由前端载入后再请求
+#[tauri::command]
+async fn thumbnails(start: usize, count: usize, state: State<'_, Arc<AppState>>) -> Result<Vec<Option<String>>, String> {
+    let book = state.book.lock().await.clone().ok_or("未打开漫画")?;
+    let end = start.saturating_add(count).min(book.page_count);
+    spawn_blocking(move || (start..end).map(|index| {
+        let thumb = thumb_path(index, book.cache_dir.as_path());
+        if !thumb.is_file() {
+            let page = cache_path(index, book.cache_dir.as_path());
+            if !page.is_file() {
+                return None;
+            }
+            if let Err(e) = write_thumbnail(page.as_path(), thumb.as_path(), THUMBNAIL_SIDE) {
+                eprintln!("生成第 {} 页缩略图失败：{}", index + 1, e);
+                return None;
+            }
+        }
+        Some(thumb.to_string_lossy().into_owned())
+    }).collect()).await.map_err(|e| e.to_string())
+}
+
+/// 关闭窗口时等待载入任务结束的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// `page_bytes` 单次返回的上限，整页会读入内存再经 IPC 复制一份给前端
+const MAX_PAGE_BYTES: u64 = 64 << 20;
+/// 等待页面载入的最长时间
+const PAGE_BYTES_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 返回当前漫画某页缓存的原始字节，前端收到的是 `ArrayBuffer`，供自定义渲染（如 OCR、滤镜）使用
+///
+/// 页面尚未载入时经 `preload_range` 的优先范围请求载入，不改动阅读位置，读完后换回原来的优先范围；
+/// 等待期间若又调用了 `preload_range`，以后来的范围为准
+#[tauri::command]
+async fn page_bytes(index: usize, state: State<'_, Arc<AppState>>) -> Result<tauri::ipc::Response, String> {
+    Ok(tauri::ipc::Response::new(read_page_bytes(&state, index).await?))
+}
+
+async fn read_page_bytes(state: &AppState, index: usize) -> Result<Vec<u8>, String> {
+    let book = state.book.lock().await.clone().ok_or("未打开漫画")?;
+    book.check_index(index)?;
+
+    let path = cache_path(index, book.cache_dir.as_path());
+    if path.is_file() {
+        return read_cached_page(path.as_path(), index);
+    }
+
+    // 优先范围内的页不会因缓存上限被丢弃，读完之前一直保留；固实压缩包整本解压，只需等待
+    let previous = state.preload.borrow().clone();
+    let requested = index..index + 1;
+    let requested_here = !book.solid && !previous.contains(&index);
+    if requested_here {
+        state.preload.send_replace(requested.clone());
+    }
+    let loaded = tokio::time::timeout(PAGE_BYTES_TIMEOUT, async {
+        while !path.is_file() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }).await;
+    let result = loaded.map_err(|_| format!("等待第 {} 页载入超时", index + 1))
+        .and_then(|()| read_cached_page(path.as_path(), index));
+    if requested_here {
+        state.preload.send_if_modified(|x| {
+            let unchanged = *x == requested;
+            if unchanged {
+                x.clone_from(&previous);
+            }
+            unchanged
+        });
+    }
+    result
+}
+
+fn read_cached_page(path: &Path, index: usize) -> Result<Vec<u8>, String> {
+    let len = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if len > MAX_PAGE_BYTES {
+        return Err(format!("第 {} 页过大：{} 字节", index + 1, len));
+    }
+    std::fs::read(path).map_err(|e| e.to_string())
+}
+
+/// 为当前漫画设置显示标题，按 sha256 保存，下次打开同一本书时沿用；传入空字符串恢复默认标题
+#[tauri::command]
+async fn set_title_override(title: String, app: AppHandle, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    let book = state.book.lock().await.clone().ok_or("未打开漫画")?;
+    titles::set_override(titles_path(&app).as_path(), sha256_hex(&book.sha256).as_str(), title.as_str()).map_err(|e| e.to_string())?;
+    apply_title(&app, &book);
+    Ok(())
+}
+
+fn is_supported_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| SUPPORTED_FILE_FORMATS.iter().any(|y| ext.eq_ignore_ascii_case(y)))
+}
+
+/// `path` 所在目录中所有受支持的文件（含 `path` 本身），按自然顺序排列，`cjk_numeral` 时识别中文数字
+fn book_files_beside(path: &Path, cjk_numeral: bool) -> Vec<PathBuf> {
+    let Some(parent) = path.parent() else {
+        return Vec::new();
+    };
+    let entries = match std::fs::read_dir(parent) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("读取目录 {} 失败：{}", parent.to_string_lossy(), e);
+            return Vec::new();
+        },
+    };
+
+    let mut files: Vec<PathBuf> = entries.flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|x| x.is_file()))
+        .map(|entry| entry.path())
+        .filter(|x| is_supported_file(x))
+        .collect();
+    files.sort_by(|a, b| volume_name_cmp(a.file_name().unwrap_or_default().to_string_lossy().as_ref(), b.file_name().unwrap_or_default().to_string_lossy().as_ref(), false, cjk_numeral));
+    files
+}
+
+/// 与 `path` 同目录的其他受支持文件，不含 `path` 本身
+fn sibling_files(path: &Path, cjk_numeral: bool) -> Vec<PathBuf> {
+    book_files_beside(path, cjk_numeral).into_iter().filter(|x| x != path).collect()
+}
+
+/// 按自然顺序排在 `path` 之后的第一个文件；`path` 是目录时没有下一卷
+fn next_sibling(path: &Path, cjk_numeral: bool) -> Option<PathBuf> {
+    let files = book_files_beside(path, cjk_numeral);
+    let position = files.iter().position(|x| x == path)?;
+    files.into_iter().nth(position + 1)
+}
+
+/// 当前漫画所在目录中的其他漫画，供“下一卷”等功能使用
+#[tauri::command]
+async fn siblings(state: State<'_, Arc<AppState>>, config_state: State<'_, Arc<ConfigState>>) -> Result<Vec<String>, String> {
+    let Some(book) = state.book.lock().await.clone() else {
+        return Ok(Vec::new());
+    };
+    let cjk_numeral_sort = config_state.config().await.cjk_numeral_sort;
+    Ok(sibling_files(book.path.as_path(), cjk_numeral_sort).into_iter().map(|x| x.to_string_lossy().into_owned()).collect())
+}
+
+/// 前端无法显示某页时调用，将该页缓存重新编码为 PNG，成功返回 `true`
+#[tauri::command]
+async fn reencode_page(index: usize, state: State<'_, Arc<AppState>>) -> Result<bool, String> {
+    let Some(book) = state.book.lock().await.clone() else {
+        return Ok(false);
+    };
+    book.check_index(index)?;
+
+    let path = cache_path(index, book.cache_dir.as_path());
+    let result = spawn_blocking(move || reencode_as_png(path.as_path())).await.map_err(|e| e.to_string())?;
+    match result {
+        Ok(()) => {
+            eprintln!("第 {} 页已重新编码为 PNG", index + 1);
+            Ok(true)
+        },
+        Err(e) => {
+            eprintln!("第 {} 页重新编码失败：{}", index + 1, e);
+            Ok(false)
+        },
+    }
+}
+
+#[tauri::command]
+fn format_info() -> Vec<FormatInfo> {
+    source::format_info()
+}
+
+/// 单次优先载入的最大页数，防止误把整本书都排进去
+const MAX_PRELOAD_PAGES: usize = 64;
+
+/// 不论当前页在哪，优先载入 `start..end` 的页，供缩略图、跳页使用；超过 `MAX_PRELOAD_PAGES` 的部分被截掉
+///
+/// 这些页在换成别的范围之前不会因缓存上限被丢弃，用完后传入空范围即可解除。固实压缩包本来就整本解压，直接忽略
+#[tauri::command]
+async fn preload_range(start: usize, end: usize, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    let book = state.book.lock().await.clone().ok_or("未打开漫画")?;
+    if !book.solid {
+        state.preload.send_replace(preload_bounds(start, end, book.page_count));
+    }
+    Ok(())
+}
+
+/// 固定一页，使其不因缓存上限或清理缓存被丢弃。固实压缩包本来就整本解压，直接忽略
+#[tauri::command]
+async fn pin_page(index: usize, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    let book = state.book.lock().await.clone().ok_or("未打开漫画")?;
+    book.check_index(index)?;
+    if !book.solid {
+        state.pinned.send_modify(|x| {
+            x.insert(index);
+        });
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn unpin_page(index: usize, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    state.pinned.send_if_modified(|x| x.remove(&index));
+    Ok(())
+}
+
+/// 截到书的页数与 `MAX_PRELOAD_PAGES` 以内
+fn preload_bounds(start: usize, end: usize, page_count: usize) -> std::ops::Range<usize> {
+    let end = end.min(page_count).min(start.saturating_add(MAX_PRELOAD_PAGES));
+    start.min(end)..end
+}
+
+/// 立即丢弃预载范围以外的缓存，之后翻页时再按需载入
+#[tauri::command]
+async fn trim_cache(state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    let book = state.book.lock().await.clone().ok_or("未打开漫画")?;
+    if book.solid {
+        return Err(String::from("固实压缩包不支持清理缓存"));
+    }
+    state.trim.send_modify(|x| *x += 1);
+    Ok(())
+}
+
+/// 重新扫描当前打开的文件夹，图片有增减时返回原来第 `index` 页在新列表中的位置，由前端重新载入
+///
+/// 原来的页已被删除时停在相同的索引上
+#[tauri::command]
+async fn rescan(index: usize, state: State<'_, Arc<AppState>>, config_state: State<'_, Arc<ConfigState>>) -> Result<Option<usize>, String> {
+    let book = state.book.lock().await.clone().ok_or("未打开漫画")?;
+    if !book.path.is_dir() {
+        return Err(String::from("只有文件夹可以重新扫描"));
+    }
+
+    let options = SourceOptions::from(&config_state.config().await);
+    rescan_folder(&book, index, &options).map_err(|e| e.to_string())
+}
+
+/// 重新读取文件夹，内容没变时返回 `None`，否则返回第 `index` 页在新列表中的位置
+fn rescan_folder(book: &BookInfo, index: usize, options: &SourceOptions) -> anyhow::Result<Option<usize>> {
+    let source = create_directory_source(book.path.as_path(), options)?;
+    if *source.sha256() == book.sha256 {
+        return Ok(None);
+    }
+    let page_count = source.page_count();
+    let new_index = book.page_names.get(index).cloned().flatten()
+        .and_then(|name| (0..page_count).position(|x| source.page_name(x).as_deref() == Some(name.as_str())))
+        .unwrap_or(index.min(page_count.saturating_sub(1)));
+    eprintln!("文件夹内容有变化：{} 页 -> {} 页", book.page_count, page_count);
+    Ok(Some(new_index))
+}
+
+/// 当前漫画的下一卷，没有时返回 `None`
+#[tauri::command]
+async fn next_volume(state: State<'_, Arc<AppState>>, config_state: State<'_, Arc<ConfigState>>) -> Result<Option<String>, String> {
+    let Some(book) = state.book.lock().await.clone() else {
+        return Ok(None);
+    };
+    let cjk_numeral_sort = config_state.config().await.cjk_numeral_sort;
+    Ok(next_sibling(book.path.as_path(), cjk_numeral_sort).map(|x| x.to_string_lossy().into_owned()))
+}
+
+/// 返回当前漫画某页可提取的文字，纯图片的页面返回 `None`
+#[tauri::command]
+async fn page_text(index: usize, state: State<'_, Arc<AppState>>) -> Result<Option<String>, String> {
+    let book = state.book.lock().await.clone().ok_or("未打开漫画")?;
+    book.check_index(index)?;
+    Ok(book.page_texts[index].clone())
+}
+
+/// 在当前漫画可提取的文字中搜索，进度通过 `search_progress` 事件发送
+#[tauri::command]
+async fn search(query: String, app: AppHandle, state: State<'_, Arc<AppState>>, config_state: State<'_, Arc<ConfigState>>) -> Result<Vec<SearchHit>, String> {
+    let Some(book) = state.book.lock().await.clone() else {
+        return Ok(Vec::new());
+    };
+    let case_sensitive = config_state.config().await.search_case_sensitive;
+    let hits = spawn_blocking(move || {
+        search_texts(book.page_texts.as_slice(), query.as_str(), case_sensitive, |done, total| {
+            _ = app.emit("search_progress", (done, total));
+        })
+    }).await.unwrap_or_default();
+    Ok(hits)
+}
+
+#[tauri::command]
+fn get_a_md5() -> [u8; 16] {
+    u128::to_le_bytes(u128::MAX)
+}
+
+#[tauri::command]
+fn sleep_5s() {
+    eprintln!("开始等待");
+    std::thread::sleep(std::time::Duration::from_secs(5));
+    eprintln!("等待完毕");
+}
+
+#[tauri::command]
+fn toggle_fullscreen(window: tauri::WebviewWindow) {
+    let is_fullscreen = window.is_fullscreen().unwrap_or_default();
+    match window.set_fullscreen(!is_fullscreen) {
+        Ok(()) => eprintln!("Toggled fullscreen."),
+        Err(e) => eprintln!("Error when toggling fullscreen: {}", e),
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .setup(|app| {
+            use tauri::WindowEvent;
+
+            let main_win = app.get_webview_window("main").unwrap(); // 主窗口 label=main
+            let app_handle = app.handle().clone();
+
+            let config_state = Arc::new(ConfigState::new(app.handle().clone()));
+            app.manage(Arc::clone(&config_state));
+
+            // 先读一次配置，启动时的窗口状态需要参考；监视开始时配置未变，不会重复发送
+            block_on(config_state.load_config());
+            let config = block_on(config_state.config());
+            let fullscreen_on_start = config.fullscreen_on_start;
+            if config.persist_cache {
+                let cache_dir = app.path().resolve("cache", tauri::path::BaseDirectory::AppData)?;
+                std::thread::spawn(move || cache_eviction::evict(cache_dir.as_path(), config.cache_max_age_days, config.cache_max_bytes));
+            }
+            spawn(async move {
+                config_state.keep_watching().await;
+            });
+
+            // 主窗口初始不可见，恢复上次的位置、大小与全屏状态后再显示，避免闪动
+            let window_state_path = app.path().resolve("window.toml", tauri::path::BaseDirectory::AppData)?;
+            let saved = window_state::load(window_state_path.as_path());
+            if let Some(geometry) = saved {
+                if let Err(e) = window_state::restore(&main_win, geometry) {
+                    eprintln!("恢复窗口状态失败：{}", e);
+                }
+            }
+            if window_state::start_fullscreen(saved.as_ref(), fullscreen_on_start) {
+                if let Err(e) = main_win.set_fullscreen(true) {
+                    eprintln!("进入全屏失败：{}", e);
+                }
+            }
+            main_win.show()?;
+
+            let window = main_win.clone();
+            main_win.on_window_event(move |evt| {
+                match evt {
                     WindowEvent::CloseRequested { .. } => {
+                        let previous = window_state::load(window_state_path.as_path());
+                        match window_state::capture(&window, previous) {
+                            Ok(geometry) => if let Err(e) = window_state::save(window_state_path.as_path(), &geometry) {
+                                eprintln!("保存窗口状态失败：{}", e);
+                            },
+                            Err(e) => eprintln!("读取窗口状态失败：{}", e),
+                        }
+
                         eprintln!(">>> window closing — 清缓存");
                         {
                             let state = app_handle.state::<Arc<AppState>>();
+                            let progress_path = progress_path(&app_handle);
                             block_on(async move {
-                                state.stop().await;
+                                state.flush_progress(progress_path.as_path()).await;
+                                // 载入任务迟迟不结束时不再等待，残留的缓存下次打开时沿用
+                                if tokio::time::timeout(SHUTDOWN_TIMEOUT, state.stop()).await.is_err() {
+                                    eprintln!("等待载入任务结束超时");
+                                }
                             });
                         }
                         if let Some(window) = app_handle.get_webview_window("guide") {
@@ -505,6 +1842,7 @@ pub fn run() {
                                 eprintln!("移除空目录 {}", entry_path.to_string_lossy());
                             }
                         }
+                        clear_downloads();
                     },
                     WindowEvent::Destroyed => {
                         eprintln!(">>> window destroyed");
@@ -540,7 +1878,589 @@ pub fn run() {
         // .manage(Mutex::new(Config::default()))
         .manage(Arc::new(AppState::new()))
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![get_a_md5, sleep_5s, create_manga, set_current, pick_file, focus_window, show_guide, read_config, toggle_fullscreen])
+        .invoke_handler(tauri::generate_handler![get_a_md5, sleep_5s, create_manga, set_current, current_view, pick_file, pick_files, pick_folder, focus_window, show_guide, show_settings, get_config, save_config, frontend_ready, toggle_fullscreen, cache_size, support_bundle, export_page, export_spread, copy_page, page_bytes, thumbnails, page_text, search, set_title_override, siblings, next_volume, recent_book, reencode_page, trim_cache, preload_range, pin_page, unpin_page, format_info, rescan, save_progress, save_queue, load_queue, launch_target])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::tests::temp_path;
+
+    fn book_info(sha256: [u8; 32]) -> Arc<BookInfo> {
+        book_in(sha256, PathBuf::new())
+    }
+
+    fn book_in(sha256: [u8; 32], cache_dir: PathBuf) -> Arc<BookInfo> {
+        Arc::new(BookInfo {
+            sha256,
+            path: PathBuf::new(),
+            metadata_title: None,
+            cache_dir,
+            page_count: 1,
+            page_texts: Vec::new(),
+            page_names: Vec::new(),
+            solid: false,
+            persist_cache: true,
+        })
+    }
+
+    /// 只记录被读取了哪些页的源
+    struct CountingSource {
+        pages: usize,
+        read: Arc<std::sync::Mutex<Vec<usize>>>,
+    }
+
+    impl PageSource for CountingSource {
+        fn get_page_bytes(&mut self, index: usize) -> anyhow::Result<FileBytes> {
+            self.read.lock().unwrap().push(index);
+            Ok(crate::source::tests::png_fixture(4, 6, 0))
+        }
+
+        fn page_count(&self) -> usize {
+            self.pages
+        }
+
+        fn sha256(&self) -> &[u8; 32] {
+            &[0; 32]
+        }
+    }
+
+    #[test]
+    fn leftover_cache_files_are_adopted() {
+        let cache_dir = temp_path("adopt");
+        std::fs::create_dir_all(cache_dir.as_path()).unwrap();
+        std::fs::write(cache_path(0, cache_dir.as_path()), crate::source::tests::png_fixture(4, 8, 0)).unwrap();
+        std::fs::write(cache_path(1, cache_dir.as_path()), crate::source::tests::png_fixture(4, 8, 0)).unwrap();
+        // 上次写到一半的空文件不能沿用
+        std::fs::write(cache_path(2, cache_dir.as_path()), b"").unwrap();
+
+        let read = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let source = CountingSource { pages: 4, read: Arc::clone(&read) };
+        let mut manga = MangaBook::new(Box::new(source), cache_dir.clone(), 1, LoadOptions::from(&Config::default()));
+        assert_eq!(manga.unloaded, 2);
+        assert!(matches!(manga.caches[0].as_ref().map(PageCache::get_data), Some(ImageData::Loaded(_, ratio)) if ratio == 0.5));
+        assert!(manga.caches[2].is_none());
+        for index in 0..4 {
+            manga.load(index).unwrap();
+        }
+        assert_eq!(*read.lock().unwrap(), [2, 3]);
+        assert_eq!(manga.unloaded, 0);
+
+        drop(manga);
+        std::fs::remove_dir_all(cache_dir.as_path()).unwrap();
+    }
+
+    #[test]
+    fn pinned_page_survives_eviction() {
+        let cache_dir = temp_path("pinned");
+        std::fs::create_dir_all(cache_dir.as_path()).unwrap();
+        let read = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let source = CountingSource { pages: 10, read };
+        let options = LoadOptions { max_cached_pages: 2, ..LoadOptions::from(&Config::default()) };
+        let mut manga = MangaBook::new(Box::new(source), cache_dir.clone(), 1, options);
+        manga.set_pinned(HashSet::from([0]));
+        for index in [0, 1, 9] {
+            manga.load(index).unwrap();
+        }
+        // 读到第 9 页时，离得最远的第 0 页本该最先丢弃
+        let evicted = manga.evict_over_budget(9, 1);
+        let cached: Vec<usize> = (0..10).filter(|&x| manga.caches[x].is_some()).collect();
+        let trimmed = manga.trim(9, 1);
+        assert!(manga.caches[0].is_some());
+
+        // 固定的页占满缓存上限后不再载入新页
+        manga.set_pinned(HashSet::from([0, 9]));
+        let full = manga.next_to_load(5, 1);
+        drop(manga);
+        std::fs::remove_dir_all(cache_dir.as_path()).unwrap();
+        assert_eq!(evicted, [1]);
+        assert_eq!(cached, [0, 9]);
+        assert!(trimmed.is_empty());
+        assert_eq!(full, None);
+    }
+
+    #[test]
+    fn page_farthest_from_window_is_evicted() {
+        let cache_dir = temp_path("evict");
+        std::fs::create_dir_all(cache_dir.as_path()).unwrap();
+        let source = CountingSource { pages: 20, read: Default::default() };
+        let options = LoadOptions { max_cached_pages: 3, persist_cache: false, ..LoadOptions::from(&Config::default()) };
+        let mut manga = MangaBook::new(Box::new(source), cache_dir.clone(), 1, options);
+        for index in [0, 1, 2] {
+            manga.load(index).unwrap();
+        }
+        let within_budget = manga.evict_over_budget(0, 1);
+        // 跳到第 12 页，预载范围为第 11 到 14 页
+        manga.load(12).unwrap();
+        let evicted = manga.evict_over_budget(12, 1);
+        let cached: Vec<usize> = (0..20).filter(|&x| manga.caches[x].is_some()).collect();
+        let files: Vec<bool> = [0, 1, 2, 12].iter().map(|&x| cache_path(x, cache_dir.as_path()).exists()).collect();
+        // 往回翻时离得最远的是后面的页
+        manga.load(0).unwrap();
+        let evicted_back = manga.evict_over_budget(0, 1);
+        drop(manga);
+        std::fs::remove_dir_all(cache_dir.as_path()).unwrap();
+        assert!(within_budget.is_empty());
+        assert_eq!(evicted, [0]);
+        assert_eq!(cached, [1, 2, 12]);
+        assert_eq!(files, [false, true, true, true]);
+        assert_eq!(evicted_back, [12]);
+    }
+
+    #[test]
+    fn trim_drops_pages_outside_prefetch_window() {
+        let cache_dir = temp_path("trim");
+        std::fs::create_dir_all(cache_dir.as_path()).unwrap();
+        let read = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let source = CountingSource { pages: 20, read: Arc::clone(&read) };
+        let mut manga = MangaBook::new(Box::new(source), cache_dir.clone(), 1, LoadOptions::from(&Config::default()));
+        for index in 0..20 {
+            manga.load(index).unwrap();
+        }
+        // 第 10 页、每屏 2 页时预载第 7 到 15 页
+        let trimmed = manga.trim(10, 2);
+        let cached: Vec<usize> = (0..20).filter(|&x| manga.caches[x].is_some()).collect();
+        // 被清理的页之后按需重新载入
+        let reload = manga.next_to_load(16, 2);
+        drop(manga);
+        std::fs::remove_dir_all(cache_dir.as_path()).unwrap();
+        assert_eq!(trimmed, (0..7).chain(16..20).collect::<Vec<usize>>());
+        assert_eq!(cached, (7..=15).collect::<Vec<usize>>());
+        assert_eq!(reload, Some(16));
+    }
+
+    #[test]
+    fn window_math_saturates_near_usize_max() {
+        assert_eq!(prefetch_window(usize::MAX, 4), usize::MAX - 6..=usize::MAX);
+        assert_eq!(prefetch_window(usize::MAX - 3, usize::MAX / 2), usize::MAX / 2 - usize::MAX / 4 - 2..=usize::MAX);
+        assert_eq!(prefetch_window(3, usize::MAX), 0..=usize::MAX);
+        assert_eq!(prefetch_window(10, 2), 7..=15);
+
+        let cache_dir = temp_path("window_math");
+        std::fs::create_dir_all(cache_dir.as_path()).unwrap();
+        let source = CountingSource { pages: 4, read: Default::default() };
+        let mut manga = MangaBook::new(Box::new(source), cache_dir.clone(), 1, LoadOptions::from(&Config::default()));
+        let first = manga.has_unloaded_nearby(2, usize::MAX);
+        for index in 0..4 {
+            manga.load(index).unwrap();
+        }
+        // 全部载入后超大的范围也要立即返回
+        let none = manga.has_unloaded_nearby(2, usize::MAX);
+        let past_end = manga.next_to_load(usize::MAX, usize::MAX);
+        drop(manga);
+        std::fs::remove_dir_all(cache_dir.as_path()).unwrap();
+        assert_eq!(first, Some(2));
+        assert_eq!(none, None);
+        assert_eq!(past_end, None);
+    }
+
+    #[test]
+    fn slow_writer_does_not_block_decoder() {
+        use std::sync::atomic::AtomicUsize;
+
+        let capacity = 2;
+        // 解压通道与写入结果队列各容纳 `capacity` 页，写盘任务手上还压着一页
+        let pages = capacity * 2 + 1;
+        let (tx, rx) = channel(capacity);
+        let finished = Arc::new(AtomicUsize::new(0));
+        let writer_finished = Arc::clone(&finished);
+        let mut written = spawn_parallel_writer(rx, capacity, Arc::new(vec![false; pages]), move |index, _| {
+            std::thread::sleep(Duration::from_millis(300));
+            writer_finished.fetch_add(1, Ordering::SeqCst);
+            Ok(index)
+        });
+        // 写盘较慢、显示端也还没取结果时，解压线程只受通道容量限制，不必等任何一页写完
+        let decoder = std::thread::spawn(move || {
+            for index in 0..pages {
+                tx.blocking_send((index, Ok(vec![0]))).unwrap();
+            }
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(decoder.is_finished());
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+
+        let order = block_on(async {
+            let mut order = Vec::new();
+            while let Some(handle) = written.recv().await {
+                let (index, result) = handle.await.unwrap();
+                assert_eq!(result.unwrap(), index);
+                order.push(index);
+            }
+            order
+        });
+        assert_eq!(order, (0..pages).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn large_book_with_few_pages_is_suspicious() {
+        let dir = temp_path("undetected");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        let big = dir.join("nested").join("pages.bin");
+        std::fs::File::create(big.as_path()).unwrap().set_len(SMALL_BOOK_MIN_BYTES + 1).unwrap();
+        let small = dir.join("small.cbz");
+        std::fs::write(small.as_path(), [0; 1024]).unwrap();
+
+        let results = [
+            looks_undetected(big.as_path(), 2, 2),
+            looks_undetected(dir.as_path(), 1, 2),
+            looks_undetected(big.as_path(), 3, 2),
+            looks_undetected(big.as_path(), 1, 0),
+            looks_undetected(small.as_path(), 1, 2),
+        ];
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+        assert_eq!(results, [true, true, false, false, false]);
+    }
+
+    #[test]
+    fn no_config_emitted_before_frontend_ready() {
+        use tauri::Listener;
+
+        let app = tauri::test::mock_app();
+        let emitted = Arc::new(AtomicU8::new(0));
+        let counter = Arc::clone(&emitted);
+        app.listen_any("load_config", move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let file_path = temp_path("ready_config.toml");
+        let config_state = ConfigState {
+            file_path: file_path.clone(),
+            config: Default::default(),
+            app: app.handle().clone(),
+            message_id: AtomicU8::new(u8::MAX),
+            frontend_ready: AtomicBool::new(false),
+        };
+
+        let mut config = Config::preset();
+        block_on(async {
+            // 启动时新建配置文件，随后又被修改，前端就绪前都不发送
+            assert!(!config_state.reload().await);
+            config.invert_scroll = !config.invert_scroll;
+            std::fs::write(file_path.as_path(), config.to_string()).unwrap();
+            assert!(!config_state.reload().await);
+            assert_eq!(emitted.load(Ordering::SeqCst), 0);
+
+            config_state.set_frontend_ready().await;
+            assert_eq!(emitted.load(Ordering::SeqCst), 1);
+            config.invert_scroll = !config.invert_scroll;
+            std::fs::write(file_path.as_path(), config.to_string()).unwrap();
+            assert!(config_state.reload().await);
+        });
+        std::fs::remove_file(file_path.as_path()).unwrap();
+        assert_eq!(emitted.load(Ordering::SeqCst), 2);
+        assert_eq!(block_on(config_state.config()), config);
+    }
+
+    #[test]
+    fn support_bundle_config_hides_passwords() {
+        let config = "theme = \"dark\"\nzip_password = \"hunter2\"\n\n[remembered]\nPwd = 'secret'\nnote = \"password hint\"\n";
+        let redacted = redact_config(config);
+        assert!(!redacted.contains("hunter2") && !redacted.contains("secret"));
+        assert!(redacted.contains("theme = \"dark\"") && redacted.contains("[remembered]"));
+        assert!(redacted.contains("note = \"password hint\""));
+    }
+
+    #[test]
+    fn page_bytes_decode_to_the_cached_page() {
+        let cache_dir = temp_path("page_bytes");
+        std::fs::create_dir_all(cache_dir.as_path()).unwrap();
+        let page_cache = write_cache(0, crate::source::tests::png_fixture(6, 9, 80), cache_dir.as_path(), &Default::default()).unwrap();
+        let state = AppState::new();
+        let generation = state.next_generation();
+        let result = block_on(async {
+            drop(state.replace_book(generation, book_in([3; 32], cache_dir.clone())).await);
+            (read_page_bytes(&state, 0).await, read_page_bytes(&state, 1).await)
+        });
+        drop(page_cache);
+        std::fs::remove_dir_all(cache_dir.as_path()).unwrap();
+
+        let image = image::load_from_memory(&result.0.unwrap()).unwrap();
+        assert_eq!((image.width(), image.height()), (6, 9));
+        assert!(result.1.is_err());
+    }
+
+    #[test]
+    fn only_latest_create_keeps_its_book() {
+        let state = AppState::new();
+        let first = state.next_generation();
+        let second = state.next_generation();
+        block_on(async {
+            let guard = state.replace_book(second, book_info([2; 32])).await;
+            assert!(guard.is_some());
+            state.launch(async |_, _, _, _, _| Vec::new()).await;
+            drop(guard);
+            // 先发出的请求晚一步完成时不能顶替新书
+            assert!(state.replace_book(first, book_info([1; 32])).await.is_none());
+            assert_eq!(state.book.lock().await.as_ref().map(|x| x.sha256), Some([2; 32]));
+        });
+    }
+
+    #[test]
+    fn cache_size_counts_nested_files() {
+        let dir = temp_path("cache_size");
+        std::fs::create_dir_all(dir.join("abc_0000")).unwrap();
+        std::fs::write(dir.join("thumb_1.jpg"), [0u8; 100]).unwrap();
+        std::fs::write(dir.join("abc_0000").join("0.png"), [0u8; 2048]).unwrap();
+        std::fs::write(dir.join("abc_0000").join("1.png"), [0u8; 5]).unwrap();
+        let size = dir_size(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(size, 2153);
+        assert_eq!(dir_size(&dir), 0);
+    }
+
+    /// 三本漫画与一个无关文件
+    fn volume_folder(name: &str) -> PathBuf {
+        let dir = temp_path(name);
+        std::fs::create_dir_all(dir.join("extras.zip")).unwrap();
+        for file in ["vol 10.cbz", "vol 2.zip", "vol 1.7z", "notes.txt"] {
+            std::fs::write(dir.join(file), b"").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn siblings_exclude_current_and_sort_naturally() {
+        let dir = volume_folder("siblings");
+        let siblings = sibling_files(dir.join("vol 2.zip").as_path(), false);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(siblings, [dir.join("vol 1.7z"), dir.join("vol 10.cbz")]);
+    }
+
+    #[test]
+    fn rescan_follows_current_page_after_changes() {
+        let dir = temp_path("rescan");
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        for name in ["1.png", "2.png", "3.png"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+        let options = SourceOptions::default();
+        let scan = || {
+            let source = create_directory_source(dir.as_path(), &options).unwrap();
+            let page_names = (0..source.page_count()).map(|x| source.page_name(x)).collect();
+            BookInfo {
+                sha256: *source.sha256(),
+                path: dir.clone(),
+                metadata_title: None,
+                cache_dir: PathBuf::new(),
+                page_count: source.page_count(),
+                page_texts: Vec::new(),
+                page_names,
+                solid: false,
+                persist_cache: false,
+            }
+        };
+        let book = scan();
+        let unchanged = rescan_folder(&book, 1, &options).unwrap();
+        // 前面加了一页，当前的 2.png 后移
+        std::fs::write(dir.join("0.png"), b"0").unwrap();
+        let added = rescan_folder(&book, 1, &options).unwrap();
+        // 当前页被删掉时停在原来的索引上
+        let book = scan();
+        std::fs::remove_file(dir.join("2.png")).unwrap();
+        let removed = rescan_folder(&book, 2, &options).unwrap();
+        let shifted_back = rescan_folder(&book, 3, &options).unwrap();
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+        assert_eq!(unchanged, None);
+        assert_eq!(added, Some(2));
+        assert_eq!(removed, Some(2));
+        assert_eq!(shifted_back, Some(2));
+    }
+
+    #[test]
+    fn spread_is_composited_side_by_side() {
+        let left = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(4, 6, image::Rgba([255, 0, 0, 255])));
+        // 矮一半的页放大到同样高度
+        let right = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(3, 3, image::Rgba([0, 0, 255, 255])));
+        let spread = compose_spread(&[left, right]);
+        assert_eq!(spread.dimensions(), (10, 6));
+        assert_eq!(*spread.get_pixel(0, 0), image::Rgba([255, 0, 0, 255]));
+        assert_eq!(*spread.get_pixel(3, 5), image::Rgba([255, 0, 0, 255]));
+        assert_eq!(*spread.get_pixel(4, 0), image::Rgba([0, 0, 255, 255]));
+        assert_eq!(*spread.get_pixel(9, 5), image::Rgba([0, 0, 255, 255]));
+
+        // 拆开的跨页左右两半拼回原图
+        let wide = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(5, 2, |x, _| image::Rgba([x as u8, 0, 0, 255])));
+        let halves = [crop_part(wide.clone(), PagePart::Left), crop_part(wide.clone(), PagePart::Right)];
+        assert_eq!(compose_spread(&halves), wide.to_rgba8());
+    }
+
+    #[test]
+    fn picked_files_keep_supported_ones_in_order() {
+        let picked = ["/a/vol 2.CBZ", "/a/readme.txt", "/a/vol 1.zip", "/a/cover.jpg", "/a/extra.pdf"].map(PathBuf::from);
+        assert_eq!(supported_paths(picked.to_vec()), ["/a/vol 2.CBZ", "/a/vol 1.zip", "/a/extra.pdf"]);
+        assert!(supported_paths(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn next_volume_follows_natural_order() {
+        let dir = volume_folder("next_volume");
+        let next = |name: &str| next_sibling(dir.join(name).as_path(), false);
+        let results = [next("vol 1.7z"), next("vol 2.zip"), next("vol 10.cbz")];
+        std::fs::remove_dir_all(&dir).unwrap();
+        // 最后一卷之后没有下一卷
+        assert_eq!(results, [Some(dir.join("vol 2.zip")), Some(dir.join("vol 10.cbz")), None]);
+    }
+
+    #[test]
+    fn view_state_reflects_last_set_current() {
+        let state = AppState::new();
+        assert_eq!(state.view_state(0), ViewState { size: 1, ..Default::default() });
+        // 还没有加载任务在收听
+        state.set_right_to_left(true);
+        state.set_current_and_size(4, 2);
+        state.set_current_and_size(10, 3);
+        state.set_right_to_left(false);
+        assert_eq!(state.view_state(40), ViewState { current_page: 10, size: 3, reading_direction: false, page_count: 40 });
+    }
+
+    #[test]
+    fn launch_args_parse_page_fragment() {
+        let args = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        let target = |path: &str, page| Some(LaunchTarget { path: path.to_string(), page });
+        assert_eq!(parse_launch_args(&args(&["file.cbz#page=42"]), |_| false), target("file.cbz", Some(42)));
+        assert_eq!(parse_launch_args(&args(&["file.cbz", "7"]), |_| true), target("file.cbz", Some(7)));
+        assert_eq!(parse_launch_args(&args(&["file.cbz"]), |_| true), target("file.cbz", None));
+        // 文件名本身带 `#page=` 时不拆开
+        assert_eq!(parse_launch_args(&args(&["odd#page=3.cbz"]), |_| true), target("odd#page=3.cbz", None));
+        assert_eq!(parse_launch_args(&args(&["file.cbz#page=x"]), |_| false), target("file.cbz#page=x", None));
+        assert_eq!(parse_launch_args(&[], |_| true), None);
+    }
+
+    #[test]
+    fn closing_persists_last_set_current_page() {
+        let path = temp_path("close_progress.toml");
+        let state = AppState::new();
+        let generation = state.next_generation();
+        block_on(async {
+            drop(state.replace_book(generation, book_info([5; 32])).await);
+            state.set_current_and_size(12, 2);
+            // 前端最后一次保存还没发出就关闭了
+            state.set_current_and_size(42, 2);
+            state.flush_progress(path.as_path()).await;
+            state.stop().await;
+        });
+        let saved = progress::get(path.as_path(), sha256_hex(&[5; 32]).as_str());
+        std::fs::remove_file(path.as_path()).unwrap();
+        assert_eq!(saved, Some(42));
+    }
+
+    /// 建议预载三倍的源
+    struct HintingSource(CountingSource);
+
+    impl PageSource for HintingSource {
+        fn get_page_bytes(&mut self, index: usize) -> anyhow::Result<FileBytes> {
+            self.0.get_page_bytes(index)
+        }
+
+        fn page_count(&self) -> usize {
+            self.0.page_count()
+        }
+
+        fn sha256(&self) -> &[u8; 32] {
+            self.0.sha256()
+        }
+
+        fn prefetch_hint(&self) -> usize {
+            3
+        }
+    }
+
+    #[test]
+    fn source_hint_widens_prefetch_window() {
+        let cache_dir = temp_path("prefetch_hint");
+        std::fs::create_dir_all(cache_dir.as_path()).unwrap();
+        let source = HintingSource(CountingSource { pages: 40, read: Default::default() });
+        let mut manga = MangaBook::new(Box::new(source), cache_dir.clone(), 1, LoadOptions::from(&Config::default()));
+        for index in 0..8 {
+            manga.load(index).unwrap();
+        }
+        let multiplier = manga.prefetch_multiplier();
+        let plain = manga.next_to_load(0, 2);
+        let hinted = manga.next_to_load(0, 2 * multiplier);
+        drop(manga);
+        std::fs::remove_dir_all(cache_dir.as_path()).unwrap();
+
+        assert_eq!(multiplier, 3);
+        assert_eq!(plain, None);
+        assert_eq!(hinted, Some(8));
+        // 配置不为 0 时以配置为准
+        assert_eq!(prefetch_multiplier(1, 3), 1);
+        assert_eq!(prefetch_multiplier(0, 0), 1);
+    }
+
+    #[test]
+    fn preloaded_range_loads_before_far_pages() {
+        let cache_dir = temp_path("preload_range");
+        std::fs::create_dir_all(cache_dir.as_path()).unwrap();
+        let read = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let source = CountingSource { pages: 100, read: Arc::clone(&read) };
+        let mut manga = MangaBook::new(Box::new(source), cache_dir.clone(), 1, LoadOptions::from(&Config::default()));
+        manga.set_priority(preload_bounds(50, 53, 100));
+        for _ in 0..5 {
+            let index = manga.next_to_load(0, 1).unwrap();
+            manga.load(index).unwrap();
+        }
+        drop(manga);
+        std::fs::remove_dir_all(cache_dir.as_path()).unwrap();
+
+        assert_eq!(*read.lock().unwrap(), [50, 51, 52, 0, 1]);
+        assert_eq!(preload_bounds(90, 200, 100), 90..100);
+        assert_eq!(preload_bounds(0, usize::MAX, usize::MAX), 0..MAX_PRELOAD_PAGES);
+        assert_eq!(preload_bounds(120, 130, 100), 100..100);
+    }
+
+    #[test]
+    fn page_bytes_requests_load_without_moving_view() {
+        let cache_dir = temp_path("page_bytes_load");
+        std::fs::create_dir_all(cache_dir.as_path()).unwrap();
+        let state = AppState::new();
+        let generation = state.next_generation();
+        let (result, during) = block_on(async {
+            drop(state.replace_book(generation, book_in([4; 32], cache_dir.clone())).await);
+            state.set_current_and_size(7, 2);
+            let load = async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                // 载入任务看到的是优先范围，阅读位置不变
+                let during = (state.preload.borrow().clone(), state.current_and_size());
+                let page_cache = write_cache(0, crate::source::tests::png_fixture(5, 7, 0), cache_dir.as_path(), &Default::default()).unwrap();
+                (during, page_cache)
+            };
+            let (result, (during, page_cache)) = tokio::join!(read_page_bytes(&state, 0), load);
+            drop(page_cache);
+            (result, during)
+        });
+        let after = (state.preload.borrow().clone(), state.current_and_size());
+        std::fs::remove_dir_all(cache_dir.as_path()).unwrap();
+
+        assert_eq!(during, (0..1, (7, 2)));
+        assert_eq!(after, (0..0, (7, 2)));
+        let image = image::load_from_memory(&result.unwrap()).unwrap();
+        assert_eq!((image.width(), image.height()), (5, 7));
+    }
+
+    #[test]
+    fn v0_config_file_is_rewritten_as_current_version() {
+        let app = tauri::test::mock_app();
+        let file_path = temp_path("v0_config.toml");
+        std::fs::write(file_path.as_path(), "# 我的设置\ninvert_scroll = true # 自然滚动\n\n[key_bind]\npage_next = [\"KeyX\"]\n").unwrap();
+        let config_state = ConfigState {
+            file_path: file_path.clone(),
+            config: Default::default(),
+            app: app.handle().clone(),
+            message_id: AtomicU8::new(u8::MAX),
+            frontend_ready: AtomicBool::new(false),
+        };
+        let (config, _) = config_state.read_config_from_file();
+        let written = std::fs::read_to_string(file_path.as_path()).unwrap();
+        // 再读一次已是当前版本，不再升级
+        let (_, version) = Config::migrate(written.as_str()).unwrap();
+        std::fs::remove_file(file_path.as_path()).unwrap();
+
+        assert_eq!(version, CONFIG_VERSION);
+        assert!(config.invert_scroll);
+        assert!(written.starts_with("# 我的设置\ninvert_scroll = true # 自然滚动\n"));
+        assert_eq!(Config::migrate(written.as_str()).unwrap().0, config);
+        assert!(!file_path.with_extension("part").exists());
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 前端每次翻页都会保存，关闭时后端也会写一次，读改写需串行进行
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 读取各本漫画上次看到的页，键为 sha256 的十六进制；文件不存在时为空表，无法解析时返回错误
+fn read_progress(path: &Path) -> io::Result<HashMap<String, usize>> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => toml::from_str(s.as_str()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn get(path: &Path, key: &str) -> Option<usize> {
+    read_progress(path).unwrap_or_else(|e| {
+        eprintln!("读取阅读进度失败：{}", e);
+        HashMap::new()
+    }).remove(key)
+}
+
+/// 停在第一页时移除记录，不必保存
+///
+/// 进度文件无法解析时不覆盖，以免丢掉其他书的记录；先写临时文件再改名，中途退出也不会留下残缺的文件
+pub fn set(path: &Path, key: &str, page: usize) -> io::Result<()> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut progress = read_progress(path)?;
+    let changed = if page == 0 {
+        progress.remove(key).is_some()
+    } else {
+        progress.insert(key.to_string(), page) != Some(page)
+    };
+    if !changed {
+        return Ok(());
+    }
+    let part_path = path.with_extension("part");
+    std::fs::write(part_path.as_path(), toml::to_string(&progress).map_err(io::Error::other)?)?;
+    std::fs::rename(part_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::tests::temp_path;
+
+    #[test]
+    fn round_trip_and_keep_unparsable_file() {
+        let path = temp_path("progress.toml");
+        set(path.as_path(), "a", 41).unwrap();
+        set(path.as_path(), "b", 7).unwrap();
+        let saved = get(path.as_path(), "a");
+        set(path.as_path(), "a", 0).unwrap();
+        let cleared = (get(path.as_path(), "a"), get(path.as_path(), "b"));
+
+        std::fs::write(path.as_path(), "not = [toml").unwrap();
+        let overwritten = set(path.as_path(), "a", 3);
+        let content = std::fs::read_to_string(path.as_path()).unwrap();
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        assert_eq!(saved, Some(41));
+        assert_eq!(cleared, (None, Some(7)));
+        assert!(overwritten.is_err());
+        assert_eq!(content, "not = [toml");
+    }
+}
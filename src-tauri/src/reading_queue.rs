@@ -0,0 +1,62 @@
+use std::io;
+use std::path::Path;
+
+use shared::SavedQueue;
+
+pub fn load(path: &Path) -> Option<SavedQueue> {
+    let s = std::fs::read_to_string(path).ok()?;
+    toml::from_str(s.as_str()).inspect_err(|e| eprintln!("解析阅读队列失败：{}", e)).ok()
+}
+
+/// 队列中没有排队的书时删除文件，下次启动不再恢复
+pub fn save(path: &Path, queue: &SavedQueue) -> io::Result<()> {
+    if queue.remaining.is_empty() {
+        return match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    std::fs::write(path, toml::to_string(queue).map_err(io::Error::other)?)
+}
+
+/// 去掉已不存在的书，返回剩下的队列与被去掉的路径；正在读的书不存在时由下一本顶上
+pub fn drop_missing(queue: SavedQueue, exists: impl Fn(&str) -> bool) -> (Option<SavedQueue>, Vec<String>) {
+    let (kept, missing): (Vec<String>, Vec<String>) = std::iter::once(queue.current)
+        .chain(queue.remaining)
+        .partition(|x| exists(x.as_str()));
+    let mut kept = kept.into_iter();
+    let queue = kept.next().map(|current| SavedQueue { current, remaining: kept.collect() });
+    (queue, missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::tests::temp_path;
+
+    #[test]
+    fn queue_with_missing_entry_restores_the_rest() {
+        let path = temp_path("queue.toml");
+        let queue = SavedQueue {
+            current: "a.cbz".to_string(),
+            remaining: vec!["gone.cbz".to_string(), "c.cbz".to_string()],
+        };
+        save(path.as_path(), &queue).unwrap();
+        let loaded = load(path.as_path());
+        // 没有排队的书时删除文件
+        save(path.as_path(), &SavedQueue { current: "c.cbz".to_string(), remaining: vec![] }).unwrap();
+        let removed = !path.exists();
+        assert_eq!(loaded, Some(queue.clone()));
+        assert!(removed);
+
+        let (restored, missing) = drop_missing(queue.clone(), |x| x != "gone.cbz");
+        assert_eq!(restored, Some(SavedQueue { current: "a.cbz".to_string(), remaining: vec!["c.cbz".to_string()] }));
+        assert_eq!(missing, vec!["gone.cbz".to_string()]);
+
+        // 正在读的书不存在时由下一本顶上
+        let (restored, missing) = drop_missing(queue, |x| x != "a.cbz");
+        assert_eq!(restored, Some(SavedQueue { current: "gone.cbz".to_string(), remaining: vec!["c.cbz".to_string()] }));
+        assert_eq!(missing, vec!["a.cbz".to_string()]);
+        assert_eq!(drop_missing(SavedQueue { current: "x".to_string(), remaining: vec![] }, |_| false).0, None);
+    }
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+use std::io;
+use std::path::Path;
+
+/// 最多记住的书数
+const MAX_RECENT: usize = 30;
+
+/// 最近打开的书的路径，最近的在前
+#[derive(Serialize, Deserialize, Default)]
+struct RecentFiles {
+    books: Vec<String>,
+}
+
+pub fn load(path: &Path) -> Vec<String> {
+    let Ok(s) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    toml::from_str::<RecentFiles>(s.as_str())
+        .map(|x| x.books)
+        .unwrap_or_else(|e| {
+            eprintln!("解析最近打开的书失败：{}", e);
+            Vec::new()
+        })
+}
+
+/// 把 `book` 移到最前面，超出上限的旧记录丢弃
+pub fn record(path: &Path, book: &str) -> io::Result<()> {
+    let mut books = load(path);
+    books.retain(|x| x != book);
+    books.insert(0, book.to_string());
+    books.truncate(MAX_RECENT);
+    std::fs::write(path, toml::to_string(&RecentFiles { books }).map_err(io::Error::other)?)
+}
+
+/// 从 `current` 出发，向更早（`older`）或更近的方向找下一本仍存在的书，到头后从另一端接着找
+///
+/// `current` 不在列表中时从最近或最早的一本开始；当前的书本身不会被选中
+pub fn neighbor<'a>(recent: &'a [String], current: Option<&str>, older: bool, exists: impl Fn(&str) -> bool) -> Option<&'a str> {
+    let n = recent.len();
+    let start = current.and_then(|current| recent.iter().position(|x| x == current));
+    (1..=n)
+        .map(|step| match (start, older) {
+            (Some(i), true) => (i + step) % n,
+            (Some(i), false) => (i + n - step) % n,
+            (None, true) => step - 1,
+            (None, false) => n - step,
+        })
+        .map(|i| recent[i].as_str())
+        .find(|&x| Some(x) != current && exists(x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_existing_recent_entry_is_picked() {
+        let recent: Vec<String> = ["a", "gone", "c", "d"].iter().map(|x| x.to_string()).collect();
+        let exists = |x: &str| x != "gone";
+        // 跳过已不存在的书
+        assert_eq!(neighbor(&recent, Some("a"), true, exists), Some("c"));
+        assert_eq!(neighbor(&recent, Some("c"), false, exists), Some("a"));
+        // 到头后从另一端接着找
+        assert_eq!(neighbor(&recent, Some("d"), true, exists), Some("a"));
+        assert_eq!(neighbor(&recent, Some("a"), false, exists), Some("d"));
+        // 当前的书不在列表中
+        assert_eq!(neighbor(&recent, None, true, exists), Some("a"));
+        assert_eq!(neighbor(&recent, Some("x"), false, exists), Some("d"));
+        // 只剩当前这本
+        assert_eq!(neighbor(&recent, Some("a"), true, |x| x == "a"), None);
+        assert_eq!(neighbor(&[], None, true, exists), None);
+    }
+}
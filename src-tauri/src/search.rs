@@ -0,0 +1,85 @@
+use shared::SearchHit;
+
+/// 摘要中匹配处前后各保留的字符数
+const SNIPPET_CONTEXT: usize = 20;
+/// 每搜索这么多页发送一次进度
+const PROGRESS_STEP: usize = 200;
+
+/// 在各页文字中查找，每页只返回第一处匹配
+///
+/// `progress` 以（已搜索页数，总页数）调用
+pub fn search_texts(texts: &[Option<String>], query: &str, case_sensitive: bool, mut progress: impl FnMut(usize, usize)) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+    if query.is_empty() {
+        return hits;
+    }
+
+    let query = if case_sensitive { query.to_string() } else { query.to_lowercase() };
+    for (page, text) in texts.iter().enumerate() {
+        if let Some(text) = text {
+            if let Some((start, end)) = find_first(text, query.as_str(), case_sensitive) {
+                hits.push(SearchHit { page, snippet: snippet(text, start, end) });
+            }
+        }
+        if (page + 1) % PROGRESS_STEP == 0 {
+            progress(page + 1, texts.len());
+        }
+    }
+    progress(texts.len(), texts.len());
+
+    hits
+}
+
+/// 返回第一处匹配在原文中的字节范围，不区分大小写时 `query` 需已转为小写
+fn find_first(text: &str, query: &str, case_sensitive: bool) -> Option<(usize, usize)> {
+    if case_sensitive {
+        return text.find(query).map(|start| (start, start + query.len()));
+    }
+
+    // 转小写可能改变字节长度，记下小写文本每个字节对应的原文字符位置
+    let mut lowered = String::with_capacity(text.len());
+    let mut origin = Vec::with_capacity(text.len());
+    for (index, c) in text.char_indices() {
+        for lc in c.to_lowercase() {
+            lowered.push(lc);
+            origin.resize(lowered.len(), (index, index + c.len_utf8()));
+        }
+    }
+    let start = lowered.find(query)?;
+    let end = start + query.len();
+    Some((origin[start].0, origin[end - 1].1))
+}
+
+fn snippet(text: &str, start: usize, end: usize) -> String {
+    let before: String = {
+        let mut v: Vec<char> = text[..start].chars().rev().take(SNIPPET_CONTEXT).collect();
+        v.reverse();
+        v.into_iter().collect()
+    };
+    let after: String = text[end..].chars().take(SNIPPET_CONTEXT).collect();
+    format!("{}{}{}", before, &text[start..end], after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{SourceOptions, create_source};
+    use crate::source::tests::{pdf_fixture, temp_path};
+
+    #[test]
+    fn finds_phrase_in_multi_page_pdf() {
+        let path = temp_path("search.pdf");
+        std::fs::write(path.as_path(), pdf_fixture(&["Prologue", "", "Not The End yet", "the end"])).unwrap();
+        let source = create_source(path.as_path(), None, &SourceOptions::default());
+        std::fs::remove_file(path.as_path()).unwrap();
+        let source = source.unwrap();
+        let texts: Vec<Option<String>> = (0..source.page_count()).map(|x| source.page_text(x)).collect();
+        let mut progress = Vec::new();
+        let hits = search_texts(texts.as_slice(), "THE END", false, |done, total| progress.push((done, total)));
+        let pages: Vec<usize> = hits.iter().map(|x| x.page).collect();
+        assert_eq!(pages, [2, 3]);
+        assert!(hits[0].snippet.contains("The End"));
+        assert_eq!(progress.last(), Some(&(4, 4)));
+        assert!(search_texts(texts.as_slice(), "THE END", true, |_, _| {}).is_empty());
+    }
+}
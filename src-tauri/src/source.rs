@@ -1,12 +1,20 @@
 use sha2::{Digest, Sha256};
+use thread_priority::{ThreadPriority, set_current_thread_priority};
 use tauri::async_runtime::Sender;
 
+use image::{DynamicImage, ImageFormat, codecs::jpeg::JpegEncoder, codecs::webp::WebPEncoder, imageops::FilterType};
+
 use std::io::{self, Read, Seek, SeekFrom, Cursor};
+use std::fs::{File, OpenOptions};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::path::{Path, PathBuf};
 use std::ffi::OsStr;
 use std::collections::HashSet;
+use std::borrow::Cow;
+use std::cmp::Ordering;
 
 use shared::*;
+use shared::config::{Config, TranscodeFormat};
 
 mod zipped_source;
 use zipped_source::ZippedSource;
@@ -15,13 +23,13 @@ mod epub_source;
 use epub_source::EpubSource;
 
 mod directory_source;
-use directory_source::DirectorySource;
+pub use directory_source::{DirectorySource, sub_dirs};
 
 mod sevenz_source;
 use sevenz_source::SevenzSource;
 
 mod pdf_source;
-use pdf_source::PdfSource;
+use pdf_source::{PdfSource, PdfRenderOptions};
 
 mod mobi_source;
 use mobi_source::MobiSource;
@@ -29,21 +37,381 @@ use mobi_source::MobiSource;
 mod tar_source;
 use tar_source::{TarSource};
 
+mod rar_source;
+use rar_source::RarSource;
+
+mod concat_source;
+use concat_source::ConcatSource;
+
+mod split_reader;
+use split_reader::{SplitReader, is_spanned_zip, split_joined_name, split_parts};
+
+mod url_source;
+pub use url_source::{clear_downloads, is_url};
+use url_source::create_url_source;
+
 pub type FileBytes = Vec<u8>;
 
+/// 固实源逐页发送的内容，读取失败的页发送错误，由前端显示为载入失败
+pub type SolidPage = (usize, anyhow::Result<FileBytes>);
+
+/// 可随机读取的数据，页面源需要在线程间传递，故要求 `Send + Sync`
+pub trait ReadSeek: Read + Seek + Send + Sync {}
+
+impl<T: Read + Seek + Send + Sync> ReadSeek for T {}
+
+/// 影响页面源构建方式的配置项
+#[derive(Debug, Clone, Default)]
+pub struct SourceOptions {
+    /// 将压缩包内的 pdf 展开，其页面接在图片之后
+    pub expand_embedded_pdf: bool,
+    /// 单页解压后的大小上限，0 表示不限制
+    pub max_page_bytes: u64,
+    /// mobi 中短边小于该值的图片视为缩略图
+    pub mobi_min_image_side: u32,
+    /// 固实压缩包的解压线程以较低优先级运行
+    pub decode_low_priority: bool,
+    /// 排序页面时忽略文件名大小写
+    pub case_insensitive_sort: bool,
+    /// 内嵌 pdf 排序时识别中文数字
+    pub cjk_numeral_sort: bool,
+    /// 文件夹中的子文件夹各作为一章
+    pub subdirs_as_chapters: bool,
+    pub pdf_render: PdfRenderOptions,
+    pub transcode: TranscodeOptions,
+}
+
+impl From<&Config> for SourceOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            expand_embedded_pdf: config.expand_embedded_pdf,
+            max_page_bytes: config.max_page_bytes,
+            mobi_min_image_side: config.mobi_min_image_side,
+            decode_low_priority: config.decode_low_priority,
+            case_insensitive_sort: config.case_insensitive_sort,
+            cjk_numeral_sort: config.cjk_numeral_sort,
+            subdirs_as_chapters: config.treat_subdirs_as_chapters,
+            pdf_render: PdfRenderOptions::from(config),
+            transcode: TranscodeOptions::from(config),
+        }
+    }
+}
+
+/// 图片需要重新编码时的输出设置
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TranscodeOptions {
+    pub format: TranscodeFormat,
+    pub jpeg_quality: u8,
+    /// 长图切块的高度，0 表示不切
+    pub tile_height: u32,
+    /// 写缓存前缩小到的最大边长，`None` 表示不缩小
+    pub max_dimension: Option<u32>,
+    /// 写缓存时检测是否为空白页，供跳过空白页使用
+    pub detect_blank: bool,
+}
+
+impl From<&Config> for TranscodeOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            format: config.transcode_format,
+            jpeg_quality: config.transcode_jpeg_quality.clamp(1, 100),
+            tile_height: config.tile_height,
+            max_dimension: config.max_page_dimension,
+            detect_blank: config.skip_blank_pages,
+        }
+    }
+}
+
+/// WebView 可以直接显示的图片格式，其余格式写缓存前需要转码
+const WEBVIEW_FORMATS: &[ImageFormat] = &[
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+    ImageFormat::Ico,
+];
+
+pub fn encode_image(image: &DynamicImage, transcode: &TranscodeOptions) -> io::Result<FileBytes> {
+    let mut buffer = Cursor::new(Vec::new());
+    match transcode.format {
+        TranscodeFormat::Png => image.write_to(&mut buffer, ImageFormat::Png),
+        TranscodeFormat::Jpeg => {
+            // JPEG 不支持透明通道
+            let encoder = JpegEncoder::new_with_quality(&mut buffer, transcode.jpeg_quality);
+            image.to_rgb8().write_with_encoder(encoder)
+        },
+    }.map_err(io::Error::other)?;
+    Ok(buffer.into_inner())
+}
+
+/// 写缓存前的规范化：WebView 无法显示的格式按设置转码，其余原样保留
+fn normalize<'a>(content: &'a [u8], transcode: &TranscodeOptions) -> io::Result<Cow<'a, [u8]>> {
+    let format = image::guess_format(content).map_err(io::Error::other)?;
+    if WEBVIEW_FORMATS.contains(&format) {
+        Ok(Cow::Borrowed(content))
+    } else {
+        let image = image::load_from_memory_with_format(content, format).map_err(io::Error::other)?;
+        Ok(Cow::Owned(encode_image(&image, transcode)?))
+    }
+}
+
+/// 超出 `transcode.max_dimension` 的页面用 Lanczos3 等比缩小：JPEG 按转码质量仍存为 JPEG，其余存为无损 WebP
+///
+/// GIF 缩小后会丢掉动画，保持原样
+fn downscale<'a>(content: Cow<'a, [u8]>, transcode: &TranscodeOptions) -> io::Result<Cow<'a, [u8]>> {
+    let Some(max) = transcode.max_dimension else {
+        return Ok(content);
+    };
+    let format = image::guess_format(content.as_ref()).map_err(io::Error::other)?;
+    if format == ImageFormat::Gif {
+        return Ok(content);
+    }
+    let (width, height) = image::ImageReader::with_format(Cursor::new(content.as_ref()), format)
+        .into_dimensions()
+        .map_err(io::Error::other)?;
+    let Some((new_width, new_height)) = fit_within(width, height, max, transcode.tile_height == 0) else {
+        return Ok(content);
+    };
+
+    let image = image::load_from_memory_with_format(content.as_ref(), format).map_err(io::Error::other)?
+        .resize_exact(new_width, new_height, FilterType::Lanczos3);
+    let mut buffer = Cursor::new(Vec::new());
+    if format == ImageFormat::Jpeg {
+        let encoder = JpegEncoder::new_with_quality(&mut buffer, transcode.jpeg_quality);
+        image.to_rgb8().write_with_encoder(encoder)
+    } else if image.color().has_alpha() {
+        image.to_rgba8().write_with_encoder(WebPEncoder::new_lossless(&mut buffer))
+    } else {
+        image.to_rgb8().write_with_encoder(WebPEncoder::new_lossless(&mut buffer))
+    }.map_err(io::Error::other)?;
+    Ok(Cow::Owned(buffer.into_inner()))
+}
+
+/// 等比缩小到宽度（`limit_height` 时高度也）不超过 `max` 后的尺寸，不需要缩小时返回 `None`
+pub fn fit_within(width: u32, height: u32, max: u32, limit_height: bool) -> Option<(u32, u32)> {
+    let longest = if limit_height { width.max(height) } else { width };
+    if max == 0 || longest <= max {
+        return None;
+    }
+    let scale = max as f64 / longest as f64;
+    let scaled = |x: u32| ((x as f64 * scale).round() as u32).max(1);
+    Some((scaled(width).min(max), scaled(height)))
+}
+
 lazy_static::lazy_static! {
     pub static ref SUPPORTED_IMG_FORMATS_MAP: HashSet<&'static str> = shared::SUPPORTED_IMG_FORMATS.iter().copied().collect();
 }
 
+/// macOS 打包时附带的元数据：任意层级的 `__MACOSX` 目录，以及 `._` 开头的 AppleDouble 资源分支文件
+///
+/// 真有图片以 `._` 开头命名时也会被当作资源分支跳过，这类命名极少见，误伤的代价小于把资源分支当作坏页显示
+pub fn is_mac_artifact(path: &Path) -> bool {
+    path.iter().any(|x| x == OsStr::new("__MACOSX"))
+    || path.file_name().is_some_and(|x| x.as_encoded_bytes().starts_with(b"._"))
+}
+
 pub fn check_valid_ext(file_name: impl AsRef<Path>) -> bool {
     let path = file_name.as_ref();
-    path.iter().next().unwrap_or_default() != OsStr::new("__MACOSX")
+    !is_mac_artifact(path)
     && {
         let ext = path.extension().unwrap_or_default().to_ascii_lowercase();
         SUPPORTED_IMG_FORMATS_MAP.contains(ext.to_str().unwrap_or_default())
     }
 }
 
+/// 读取一个压缩包条目，声明的或实际解压出的大小超过 `max` 时返回错误，`max` 为 0 表示不限制
+///
+/// 声明的大小不可信，只用作预分配的参考，实际读取时另行计数
+pub fn read_capped(mut reader: impl Read, declared: u64, max: u64) -> io::Result<FileBytes> {
+    let too_large = |size: u64| io::Error::new(io::ErrorKind::InvalidData, format!("解压后大小 {} 超过上限 {}", size, max));
+    if max == 0 {
+        let mut buffer = Vec::with_capacity(declared.min(64 << 20) as usize);
+        reader.read_to_end(&mut buffer)?;
+        return Ok(buffer);
+    }
+    if declared > max {
+        return Err(too_large(declared));
+    }
+
+    let mut buffer = Vec::with_capacity(declared as usize);
+    reader.take(max + 1).read_to_end(&mut buffer)?;
+    if buffer.len() as u64 > max {
+        return Err(too_large(buffer.len() as u64));
+    }
+    Ok(buffer)
+}
+
+/// 自然排序：连续的数字按数值比较，使 `2` 排在 `10` 之前
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |iter: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = iter.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let (x_trimmed, y_trimmed) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                // 先比位数再逐位比较，避免超长数字溢出；数值相同时前导零少的在前
+                let ordering = x_trimmed.len().cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            },
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            },
+        }
+    }
+}
+
+/// 页面文件名的排序规则
+///
+/// 忽略大小写时先比较小写形式，`Page1.JPG` 与 `page1.jpg` 这类仅大小写不同的文件再按原名比较（大写在前），
+/// 保证顺序稳定；读取时仍使用原名
+pub fn page_name_cmp(a: &str, b: &str, case_insensitive: bool) -> Ordering {
+    if case_insensitive {
+        natural_cmp(a.to_lowercase().as_str(), b.to_lowercase().as_str()).then_with(|| natural_cmp(a, b))
+    } else {
+        natural_cmp(a, b)
+    }
+}
+
+/// 卷名的排序规则，开启 `cjk_numeral` 时先将中文数字换成阿拉伯数字再自然排序，换算后相同的再按原名比较
+pub fn volume_name_cmp(a: &str, b: &str, case_insensitive: bool, cjk_numeral: bool) -> Ordering {
+    if cjk_numeral {
+        page_name_cmp(expand_cjk_numerals(a).as_str(), expand_cjk_numerals(b).as_str(), case_insensitive)
+            .then_with(|| page_name_cmp(a, b, case_insensitive))
+    } else {
+        page_name_cmp(a, b, case_insensitive)
+    }
+}
+
+fn cjk_digit(c: char) -> Option<u64> {
+    Some(match c {
+        '〇' | '零' => 0,
+        '一' | '壹' => 1,
+        '二' | '两' | '贰' => 2,
+        '三' | '叁' => 3,
+        '四' | '肆' => 4,
+        '五' | '伍' => 5,
+        '六' | '陆' => 6,
+        '七' | '柒' => 7,
+        '八' | '捌' => 8,
+        '九' | '玖' => 9,
+        _ => return None,
+    })
+}
+
+fn cjk_unit(c: char) -> Option<u64> {
+    Some(match c {
+        '十' | '拾' => 10,
+        '百' | '佰' => 100,
+        '千' | '仟' => 1000,
+        '万' | '萬' => 10000,
+        _ => return None,
+    })
+}
+
+/// 将一段连续的中文数字换算为数值
+///
+/// 带单位的按读法计算（`十二`、`一百零五`、`两万`），不带单位的按位读（`二〇二四`）；无法识别时返回 `None`
+pub fn cjk_numeral_value(s: &str) -> Option<u64> {
+    if s.is_empty() {
+        return None;
+    }
+    if !s.chars().any(|c| cjk_unit(c).is_some()) {
+        return s.chars().try_fold(0_u64, |acc, c| acc.checked_mul(10)?.checked_add(cjk_digit(c)?));
+    }
+
+    let (mut total, mut section, mut digit) = (0_u64, 0_u64, 0_u64);
+    for c in s.chars() {
+        if let Some(x) = cjk_digit(c) {
+            digit = x;
+        } else {
+            match cjk_unit(c)? {
+                10000 => {
+                    // 开头单独的 `万` 按一万算，与单独的 `十` 一致
+                    let count = match section + digit {
+                        0 if total == 0 => 1,
+                        x => x,
+                    };
+                    total = total.checked_add(count)?.checked_mul(10000)?;
+                    section = 0;
+                },
+                unit => section += if digit == 0 { 1 } else { digit } * unit,
+            }
+            digit = 0;
+        }
+    }
+    total.checked_add(section + digit)
+}
+
+/// 将名字中每段连续的中文数字替换为阿拉伯数字
+pub fn expand_cjk_numerals(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut run = String::new();
+    let flush = |run: &mut String, result: &mut String| {
+        match cjk_numeral_value(run.as_str()) {
+            Some(value) => result.push_str(value.to_string().as_str()),
+            None => result.push_str(run.as_str()),
+        }
+        run.clear();
+    };
+    for c in s.chars() {
+        if cjk_digit(c).is_some() || cjk_unit(c).is_some() {
+            run.push(c);
+        } else {
+            flush(&mut run, &mut result);
+            result.push(c);
+        }
+    }
+    flush(&mut run, &mut result);
+    result
+}
+
+/// 降低当前线程的系统优先级，让界面线程优先得到调度
+pub fn lower_thread_priority() {
+    if let Err(e) = set_current_thread_priority(ThreadPriority::Min) {
+        eprintln!("降低线程优先级失败：{:?}", e);
+    }
+}
+
+/// `enabled` 时在另开的低优先级线程中执行 `f` 并等待结果，否则直接在当前线程执行
+///
+/// 不直接降低当前线程，因为调用处多在异步运行时的工作线程上，降低后会波及其他任务
+pub fn run_low_priority<T: Send>(enabled: bool, f: impl FnOnce() -> T + Send) -> T {
+    run_with_hook(enabled, lower_thread_priority, f)
+}
+
+/// `enabled` 时另开线程，先执行 `hook` 再执行 `f`
+fn run_with_hook<T: Send>(enabled: bool, hook: impl FnOnce() + Send, f: impl FnOnce() -> T + Send) -> T {
+    if !enabled {
+        return f();
+    }
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            hook();
+            f()
+        }).join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+    })
+}
+
 pub fn get_aspect_ratio(content: impl AsRef<[u8]>) -> f64 {
     let format = image::guess_format(content.as_ref()).expect("不支持的图片格式");
     let reader = image::ImageReader::with_format(Cursor::new(content.as_ref()), format);
@@ -51,6 +419,38 @@ pub fn get_aspect_ratio(content: impl AsRef<[u8]>) -> f64 {
     width as f64 / height as f64
 }
 
+/// 检测空白页时缩小到的边长，太小会把细线条和小字平均掉
+const BLANK_SAMPLE_SIDE: u32 = 256;
+/// 缩小后亮度与平均值相差超过该值的像素视为有内容
+const BLANK_TOLERANCE: f64 = 16.;
+/// 有内容的像素不超过该比例时视为空白页，容许扫描件上的噪点与污渍
+const BLANK_MAX_INK_RATIO: f64 = 0.002;
+
+/// 页面是否近乎纯色：缩小后按亮度比较，透明部分按白底计算
+fn is_blank_page(content: &[u8]) -> bool {
+    match image::load_from_memory(content) {
+        Ok(image) => is_uniform(&image),
+        Err(e) => {
+            eprintln!("检测空白页失败：{}", e);
+            false
+        },
+    }
+}
+
+fn is_uniform(image: &DynamicImage) -> bool {
+    let luma: Vec<f64> = image.thumbnail(BLANK_SAMPLE_SIDE, BLANK_SAMPLE_SIDE).to_rgba8().pixels().map(|p| {
+        let [r, g, b, a] = p.0.map(f64::from);
+        let alpha = a / 255.;
+        (0.299 * r + 0.587 * g + 0.114 * b) * alpha + 255. * (1. - alpha)
+    }).collect();
+    if luma.is_empty() {
+        return false;
+    }
+    let mean = luma.iter().sum::<f64>() / luma.len() as f64;
+    let ink = luma.iter().filter(|&&x| (x - mean).abs() > BLANK_TOLERANCE).count();
+    ink as f64 <= luma.len() as f64 * BLANK_MAX_INK_RATIO
+}
+
 pub fn cal_sha256(mut stream: impl Seek + Read) -> io::Result<[u8; 32]> {
     stream.seek(SeekFrom::Start(0))?;
     let mut hasher = Sha256::new();
@@ -66,39 +466,198 @@ pub fn cal_sha256(mut stream: impl Seek + Read) -> io::Result<[u8; 32]> {
     }
 }
 
-pub fn write_cache(index: usize, content: FileBytes, cache_dir: &Path) -> io::Result<PageCache> {
-    let path = cache_dir.join(format!("page_{:03}", index));
-    PageCache::new(content, path)
+/// 缓存目录名：书的哈希加上影响缓存内容与页码的配置的摘要，改了这些配置后不会沿用按旧配置写的缓存
+pub fn cache_dir_name(sha256: &[u8; 32], options: &SourceOptions) -> String {
+    let TranscodeOptions { format, jpeg_quality, tile_height, max_dimension, detect_blank: _ } = options.transcode;
+    let mut settings = vec![
+        format as u8,
+        jpeg_quality,
+        options.expand_embedded_pdf as u8,
+        options.case_insensitive_sort as u8,
+        options.cjk_numeral_sort as u8,
+        options.subdirs_as_chapters as u8,
+        options.pdf_render.antialias as u8,
+    ];
+    for x in [tile_height, max_dimension.unwrap_or(0), options.pdf_render.page_height, options.mobi_min_image_side] {
+        settings.extend(x.to_le_bytes());
+    }
+    let digest = Sha256::digest(settings.as_slice());
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    format!("{}_{}", hex(sha256), hex(&digest[..4]))
+}
+
+pub fn cache_path(index: usize, cache_dir: &Path) -> PathBuf {
+    cache_dir.join(format!("page_{:03}", index))
+}
+
+/// 缩略图与页面缓存放在同一目录，保留缓存时再次打开沿用，否则关闭漫画时由 [`remove_thumbnails`] 删除
+pub fn thumb_path(index: usize, cache_dir: &Path) -> PathBuf {
+    cache_dir.join(format!("thumb_{:03}", index))
+}
+
+/// 删除缓存目录中的缩略图及写了一半的临时文件
+pub fn remove_thumbnails(cache_dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(cache_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with("thumb_") || path.extension().is_some_and(|x| x == "part") {
+            if let Err(e) = std::fs::remove_file(path) {
+                eprintln!("Error removing thumbnail: {}", e);
+            }
+        }
+    }
+}
+
+/// 将页面缩小到长边不超过 `side` 像素，存为 JPEG
+pub fn write_thumbnail(page: &Path, thumb: &Path, side: u32) -> anyhow::Result<()> {
+    let image = image::ImageReader::open(page)?.with_guessed_format()?.decode()?.thumbnail(side, side);
+    let mut buffer = Cursor::new(Vec::new());
+    image.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, 80))?;
+    let part_path = thumb.with_extension("part");
+    std::fs::write(part_path.as_path(), buffer.into_inner())?;
+    std::fs::rename(part_path, thumb)?;
+    Ok(())
+}
+
+fn tile_path(path: &Path, tile_index: usize) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!("_tile_{}", tile_index));
+    path.with_file_name(file_name)
+}
+
+/// 按块高将图片高度切成若干段，返回各段的（起点，高度）；不超过块高时返回空表
+fn tile_ranges(height: u32, tile_height: u32) -> Vec<(u32, u32)> {
+    if tile_height == 0 || height <= tile_height {
+        return Vec::new();
+    }
+    (0..height).step_by(tile_height as usize).map(|y| (y, tile_height.min(height - y))).collect()
+}
+
+/// 将长图切块写在缓存文件旁边，图片不够长时不写
+fn write_tiles(path: &Path, content: &[u8], transcode: &TranscodeOptions) -> io::Result<Vec<PathBuf>> {
+    let format = image::guess_format(content).map_err(io::Error::other)?;
+    let (width, height) = image::ImageReader::with_format(Cursor::new(content), format)
+        .into_dimensions()
+        .map_err(io::Error::other)?;
+    let ranges = tile_ranges(height, transcode.tile_height);
+    if ranges.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let image = image::load_from_memory_with_format(content, format).map_err(io::Error::other)?;
+    let mut tiles = Vec::with_capacity(ranges.len());
+    for (tile_index, (y, h)) in ranges.into_iter().enumerate() {
+        let tile_path = tile_path(path, tile_index);
+        std::fs::write(tile_path.as_path(), encode_image(&image.crop_imm(0, y, width, h), transcode)?)?;
+        tiles.push(tile_path);
+    }
+    eprintln!("长图 {} 切成了 {} 块", path.to_string_lossy(), tiles.len());
+    Ok(tiles)
+}
+
+pub fn write_cache(index: usize, content: FileBytes, cache_dir: &Path, transcode: &TranscodeOptions) -> io::Result<PageCache> {
+    PageCache::new(content, cache_path(index, cache_dir), transcode)
 }
 
 #[derive(Debug)]
 pub struct PageCache {
     path: PathBuf,
     aspect_ratio: f64,
+    /// 长图的切块，原图仍保留，供导出等操作使用
+    tiles: Vec<PathBuf>,
+    /// 为真时 drop 不删除文件，留待下次打开沿用
+    persist: bool,
+    /// 写缓存时检测到的空白页，未开启检测时总为假
+    blank: bool,
 }
 
 impl PageCache {
-    pub fn new(content: impl AsRef<[u8]>, path: PathBuf) -> io::Result<Self> {
+    pub fn new(content: impl AsRef<[u8]>, path: PathBuf, transcode: &TranscodeOptions) -> io::Result<Self> {
+        let content = normalize(content.as_ref(), transcode)?;
+        // 缩小时取整会让宽高比略有偏差，按原图计算，版面不受影响
         let aspect_ratio = get_aspect_ratio(content.as_ref());
-        std::fs::write(path.as_path(), content)?;
+        let content = downscale(content, transcode)?;
+        let tiles = write_tiles(path.as_path(), content.as_ref(), transcode)?;
+        let blank = transcode.detect_blank && is_blank_page(content.as_ref());
+        // 先写临时文件再改名，其他地方看到缓存文件时内容总是完整的
+        let part_path = path.with_extension("part");
+        std::fs::write(part_path.as_path(), content)?;
+        std::fs::rename(part_path, path.as_path())?;
+
+        Ok(Self { path, aspect_ratio, tiles, persist: false, blank })
+    }
+
+    /// 沿用已存在的缓存文件，只读取文件头获取尺寸，文件为空或无法识别时返回错误
+    ///
+    /// 长图的切块若有缺失则从原图重新切
+    pub fn adopt(path: PathBuf, transcode: &TranscodeOptions) -> io::Result<Self> {
+        let (width, height) = image::ImageReader::open(path.as_path())?
+            .with_guessed_format()?
+            .into_dimensions()
+            .map_err(io::Error::other)?;
+        if width == 0 || height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "图片尺寸为 0"));
+        }
+        let aspect_ratio = width as f64 / height as f64;
+        let tile_count = tile_ranges(height, transcode.tile_height).len();
+        let mut tiles: Vec<PathBuf> = (0..tile_count).map(|x| tile_path(path.as_path(), x)).collect();
+        let missing_tiles = !tiles.iter().all(|x| x.is_file());
+        let mut blank = false;
+        if missing_tiles || transcode.detect_blank {
+            let content = std::fs::read(path.as_path())?;
+            if missing_tiles {
+                tiles = write_tiles(path.as_path(), content.as_slice(), transcode)?;
+            }
+            blank = transcode.detect_blank && is_blank_page(content.as_slice());
+        }
 
-        Ok(Self { path, aspect_ratio })
+        Ok(Self { path, aspect_ratio, tiles, persist: false, blank })
+    }
+
+    pub fn persist(&mut self) {
+        self.persist = true;
     }
 
     pub fn get_path(&self) -> &Path {
         self.path.as_path()
     }
 
+    pub fn is_blank(&self) -> bool {
+        self.blank
+    }
+
     pub fn get_data(&self) -> ImageData {
-        ImageData::new(self.path.as_path(), self.aspect_ratio)
+        if self.tiles.is_empty() {
+            ImageData::new(self.path.as_path(), self.aspect_ratio)
+        } else {
+            ImageData::tiled(self.path.as_path(), self.aspect_ratio, self.tiles.as_slice())
+        }
     }
 }
 
+/// 将缓存页解码后重新存为 PNG，用于 WebView 无法显示的图片
+pub fn reencode_as_png(path: &Path) -> anyhow::Result<()> {
+    let image = image::ImageReader::open(path)?.with_guessed_format()?.decode()?;
+    let mut buffer = Cursor::new(Vec::new());
+    image.write_to(&mut buffer, image::ImageFormat::Png)?;
+    let part_path = path.with_extension("part");
+    std::fs::write(part_path.as_path(), buffer.into_inner())?;
+    std::fs::rename(part_path, path)?;
+    Ok(())
+}
+
 impl Drop for PageCache {
     fn drop(&mut self) {
+        if self.persist {
+            return;
+        }
         eprintln!("dropping {}", self.path.to_string_lossy());
-        if let Err(e) = std::fs::remove_file(self.path.as_path()) {
-            eprintln!("Error removing page cache: {}", e);
+        for path in std::iter::once(&self.path).chain(self.tiles.iter()) {
+            if let Err(e) = std::fs::remove_file(path.as_path()) {
+                eprintln!("Error removing page cache: {}", e);
+            }
         }
     }
 }
@@ -110,12 +669,36 @@ pub trait PageSource: Send + Sync {
     fn sha256(&self) -> &[u8; 32];
 
     fn is_solid(&self) -> bool { false }
-    fn get_all_page_bytes(&mut self, _tx: Sender<(usize, FileBytes)>) -> bool { false }
+    fn get_all_page_bytes(&mut self, _tx: Sender<SolidPage>) -> bool { false }
+
+    /// 各章节起始页的索引，升序排列；没有目录信息的源返回空表
+    fn chapter_offsets(&self) -> Vec<usize> { Vec::new() }
+
+    /// 这本书建议的阅读方向，`Some(true)` 为从右到左；没有线索时返回 `None`，沿用配置
+    fn right_to_left(&self) -> Option<bool> { None }
+
+    /// 书中元数据记载的标题
+    fn title(&self) -> Option<String> { None }
+
+    /// 该页可提取的文字，供搜索、无障碍使用；纯图片的源返回 `None`
+    ///
+    /// pdf 只能取出简单字体写出的文字，复合字体缺少字符码到文字的映射
+    fn page_text(&self, _index: usize) -> Option<String> { None }
+
+    /// 该页在源中的文件名，用于内容变化后按名字找回原来的页
+    fn page_name(&self, _index: usize) -> Option<String> { None }
+
+    /// 预载范围的倍数，按页读取代价低的源可以多预载几屏；配置中的 `prefetch_multiplier` 不为 0 时以配置为准
+    fn prefetch_hint(&self) -> usize { 1 }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::new(self.is_solid(), !self.chapter_offsets().is_empty())
+    }
 
-    fn cache(&mut self, index: usize, cache: &mut Option<PageCache>, cache_dir: &Path) -> anyhow::Result<()> {
+    fn cache(&mut self, index: usize, cache: &mut Option<PageCache>, cache_dir: &Path, transcode: &TranscodeOptions) -> anyhow::Result<()> {
         if index < self.page_count() && cache.is_none() {
             let content = self.get_page_bytes(index)?;
-            let page_cache = write_cache(index, content, cache_dir)?;
+            let page_cache = write_cache(index, content, cache_dir, transcode)?;
             cache.replace(page_cache);
             Ok(())
         } else {
@@ -140,20 +723,200 @@ impl PageSource for NoSource {
     }
 }
 
-pub fn create_source(path: &Path, password: Option<String>) -> anyhow::Result<Box<dyn PageSource>> {
-    if path.is_dir() {
-        Ok(Box::new(DirectorySource::new(path)?))
+/// 压缩包套压缩包时最多展开的层数
+const NESTED_MAX_DEPTH: usize = 3;
+/// 内嵌压缩包展开后的总大小最多为外层压缩包的几倍：里面的图片本身已经压缩过，正常的书展开后不会大多少
+const NESTED_MAX_RATIO: u64 = 2;
+
+/// 内嵌压缩包写到临时文件的总大小上限，按外层压缩包的大小计算，另留一页的余量
+fn nested_budget(zipped_source: &ZippedSource, options: &SourceOptions) -> u64 {
+    zipped_source.archive_len().saturating_mul(NESTED_MAX_RATIO).saturating_add(options.max_page_bytes)
+}
+
+/// 在临时目录建一个匿名文件：打开后立即删除路径，句柄关闭时由系统回收空间
+pub fn spill_file() -> io::Result<File> {
+    static SPILL_COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!("local-manga-reader-{}-{}.spill", std::process::id(), SPILL_COUNT.fetch_add(1, AtomicOrdering::Relaxed));
+    let path = std::env::temp_dir().join(name);
+    let file = OpenOptions::new().read(true).write(true).create_new(true).open(path.as_path())?;
+    if let Err(e) = std::fs::remove_file(path.as_path()) {
+        eprintln!("删除临时文件 {} 失败：{}", path.to_string_lossy(), e);
+    }
+    Ok(file)
+}
+
+fn create_zipped_source(path: &Path, password: Option<String>, options: &SourceOptions) -> anyhow::Result<Box<dyn PageSource>> {
+    let zipped_source = ZippedSource::new(path, password.clone(), options.case_insensitive_sort)?.with_max_page_bytes(options.max_page_bytes);
+    let mut budget = nested_budget(&zipped_source, options);
+    expand_zipped_source(zipped_source, password, options, 0, &mut budget)
+}
+
+/// 压缩包里没有图片、只有各章的压缩包时，按名称排序依次展开，每个内嵌压缩包作为一章；否则按设置展开内嵌的 pdf
+///
+/// `depth` 为当前所在的嵌套层数，`budget` 为剩余可写出的内嵌压缩包大小，各层共用
+fn expand_zipped_source(mut zipped_source: ZippedSource, password: Option<String>, options: &SourceOptions, depth: usize, budget: &mut u64) -> anyhow::Result<Box<dyn PageSource>> {
+    if zipped_source.page_count() == 0 && depth < NESTED_MAX_DEPTH {
+        let archives = zipped_source.nested_archives(options.case_insensitive_sort, options.cjk_numeral_sort, budget)?;
+        let mut sources: Vec<Box<dyn PageSource>> = Vec::new();
+        for (name, file) in archives {
+            match open_nested_archive(name.as_str(), file, password.clone(), options, depth + 1, budget) {
+                Ok(source) if source.page_count() > 0 => sources.push(source),
+                Ok(_) => eprintln!("内嵌压缩包 {} 中没有图片，已跳过", name),
+                Err(e) => eprintln!("打开内嵌压缩包 {} 失败：{}", name, e),
+            }
+        }
+        if !sources.is_empty() {
+            return Ok(Box::new(ConcatSource::new(*zipped_source.sha256(), sources)));
+        }
+    }
+
+    if options.expand_embedded_pdf {
+        let pdfs = zipped_source.embedded_pdfs(options.case_insensitive_sort, options.cjk_numeral_sort)?;
+        if !pdfs.is_empty() {
+            let sha256 = *zipped_source.sha256();
+            let mut sources: Vec<Box<dyn PageSource>> = vec![Box::new(zipped_source)];
+            for pdf in pdfs {
+                match PdfSource::from_bytes(pdf) {
+                    Ok(pdf_source) => sources.push(Box::new(pdf_source.with_transcode(options.transcode).with_render(options.pdf_render))),
+                    Err(e) => eprintln!("展开内嵌 pdf 失败：{}", e),
+                }
+            }
+            return Ok(Box::new(ConcatSource::new(sha256, sources)));
+        }
+    }
+
+    Ok(Box::new(zipped_source))
+}
+
+/// 按字节切分的分卷（`name.zip.001`……）拼接后读取，按合并后的文件名判断格式，目前只支持 zip
+fn create_split_source(path: &Path, parts: &[PathBuf], password: Option<String>, options: &SourceOptions) -> anyhow::Result<Box<dyn PageSource>> {
+    let joined = split_joined_name(path).unwrap_or_default();
+    let ext = Path::new(joined).extension().unwrap_or_default().to_ascii_lowercase();
+    if ext != EXT_ZIP && ext != EXT_CBZ {
+        anyhow::bail!("不支持的分卷格式：{}", joined);
+    }
+    let reader = SplitReader::open(parts)?;
+    let zipped_source = ZippedSource::from_reader(reader, password.clone(), options.case_insensitive_sort)?.with_max_page_bytes(options.max_page_bytes);
+    let mut budget = nested_budget(&zipped_source, options);
+    expand_zipped_source(zipped_source, password, options, 0, &mut budget)
+}
+
+/// 打开解压到临时文件的内嵌压缩包，zip 可以继续向下展开
+fn open_nested_archive(name: &str, file: File, password: Option<String>, options: &SourceOptions, depth: usize, budget: &mut u64) -> anyhow::Result<Box<dyn PageSource>> {
+    let ext = Path::new(name).extension().unwrap_or_default().to_ascii_lowercase();
+    if ext == EXT_7Z {
+        Ok(Box::new(SevenzSource::from_reader(file, password, options.case_insensitive_sort)?.with_max_page_bytes(options.max_page_bytes).with_low_priority(options.decode_low_priority)))
+    } else {
+        let zipped_source = ZippedSource::from_reader(file, password.clone(), options.case_insensitive_sort)?.with_max_page_bytes(options.max_page_bytes);
+        expand_zipped_source(zipped_source, password, options, depth, budget)
+    }
+}
+
+/// 开启 `subdirs_as_chapters` 且有含图片的子文件夹时，文件夹本身的图片在前，各子文件夹依次作为一章
+pub fn create_directory_source(path: &Path, options: &SourceOptions) -> anyhow::Result<Box<dyn PageSource>> {
+    let root = DirectorySource::new(path, options.case_insensitive_sort)?;
+    if !options.subdirs_as_chapters {
+        return Ok(Box::new(root));
+    }
+
+    let mut chapters = Vec::new();
+    for dir in sub_dirs(path, options.case_insensitive_sort, options.cjk_numeral_sort)? {
+        let chapter = DirectorySource::new(dir.as_path(), options.case_insensitive_sort)?.as_chapter()?;
+        if chapter.page_count() > 0 {
+            chapters.push((dir, chapter));
+        }
+    }
+    if chapters.is_empty() {
+        return Ok(Box::new(root));
+    }
+
+    // 各章的文件夹名与页面列表共同决定整本书的哈希
+    let mut names = root.sha256().to_vec();
+    let mut sources: Vec<Box<dyn PageSource>> = Vec::new();
+    if root.page_count() > 0 {
+        sources.push(Box::new(root));
+    }
+    for (dir, chapter) in chapters {
+        names.extend(dir.file_name().unwrap_or_default().as_encoded_bytes());
+        names.extend(chapter.sha256());
+        sources.push(Box::new(chapter));
+    }
+    let sha256 = cal_sha256(Cursor::new(names))?;
+    Ok(Box::new(ConcatSource::new(sha256, sources)))
+}
+
+/// 所有支持的格式及其说明，与 `create_source` 的分支一一对应
+///
+/// 目前没有可选的格式特性，所有格式都会编入
+pub fn format_info() -> Vec<FormatInfo> {
+    SUPPORTED_FILE_FORMATS.iter().map(|&ext| {
+        let (label, is_archive, supports_password) = match ext {
+            EXT_ZIP => ("ZIP 压缩包", true, true),
+            EXT_CBZ => ("漫画 ZIP 压缩包", true, true),
+            EXT_7Z => ("7z 压缩包", true, true),
+            EXT_TAR => ("tar 归档", true, false),
+            EXT_XZ => ("tar.xz 归档", true, false),
+            EXT_GZ => ("tar.gz 归档", true, false),
+            EXT_BZ2 => ("tar.bz2 归档", true, false),
+            EXT_CBT => ("漫画 tar 归档", true, false),
+            EXT_TGZ => ("tar.gz 归档（简写）", true, false),
+            EXT_TXZ => ("tar.xz 归档（简写）", true, false),
+            EXT_TBZ2 => ("tar.bz2 归档（简写）", true, false),
+            EXT_RAR => ("RAR 压缩包", true, true),
+            EXT_SPLIT => ("分卷 ZIP 压缩包（首卷）", true, true),
+            EXT_CBR => ("漫画 RAR 压缩包", true, true),
+            EXT_EPUB => ("EPUB 电子书", false, false),
+            EXT_MOBI => ("MOBI 电子书", false, false),
+            EXT_PDF => ("PDF 文档", false, false),
+            _ => (ext, false, false),
+        };
+        FormatInfo {
+            ext: ext.to_string(),
+            label: label.to_string(),
+            is_archive,
+            supports_password,
+            enabled: true,
+        }
+    }).collect()
+}
+
+/// 由完整文件名判断 tar 归档的压缩方式，返回 `TarSource::new` 所需的后缀
+///
+/// `a.tar.gz` 的 `Path::extension()` 只有 `gz`，因此按文件名末尾判断，简写的 `tgz` 等归到对应的压缩方式
+fn tar_kind(file_name: &str) -> Option<&'static str> {
+    let file_name = file_name.to_ascii_lowercase();
+    let (_, ext) = file_name.rsplit_once('.')?;
+    match ext {
+        EXT_TAR | EXT_CBT => Some(EXT_TAR),
+        EXT_GZ | EXT_TGZ => Some(EXT_GZ),
+        EXT_XZ | EXT_TXZ => Some(EXT_XZ),
+        EXT_BZ2 | EXT_TBZ2 => Some(EXT_BZ2),
+        _ => None,
+    }
+}
+
+pub fn create_source(path: &Path, password: Option<String>, options: &SourceOptions) -> anyhow::Result<Box<dyn PageSource>> {
+    if let Some(url) = path.to_str().filter(|x| is_url(x)) {
+        create_url_source(url, password, options)
+    } else if path.is_dir() {
+        create_directory_source(path, options)
+    } else if is_spanned_zip(path) {
+        anyhow::bail!("暂不支持 .z01 形式的分卷 zip，可先用 `zip -s 0` 合并为单个文件")
+    } else if let Some(parts) = split_parts(path) {
+        create_split_source(path, parts.as_slice(), password, options)
+    } else if let Some(kind) = path.file_name().and_then(OsStr::to_str).and_then(tar_kind) {
+        Ok(Box::new(TarSource::new(path, kind, options.max_page_bytes, options.case_insensitive_sort)?))
     } else {
         match path.extension() {
             Some(ext) => match ext.to_str() {
                 Some(ext) => match ext.to_ascii_lowercase().as_str() {
-                    EXT_ZIP => Ok(Box::new(ZippedSource::new(path, password)?)),
-                    EXT_EPUB => Ok(Box::new(EpubSource::new(path)?)),
-                    EXT_7Z => Ok(Box::new(SevenzSource::new(path, password)?)),
-                    EXT_PDF => Ok(Box::new(PdfSource::new(path)?)),
-                    EXT_CBZ => Ok(Box::new(ZippedSource::new(path, password)?)),
-                    EXT_MOBI => Ok(Box::new(MobiSource::new(path)?)),
-                    x @ (EXT_TAR | EXT_XZ | EXT_GZ | EXT_BZ2) => Ok(Box::new(TarSource::new(path, x)?)),
+                    EXT_ZIP => create_zipped_source(path, password, options),
+                    EXT_EPUB => Ok(Box::new(EpubSource::new(path, options)?)),
+                    EXT_7Z => Ok(Box::new(SevenzSource::new(path, password, options.case_insensitive_sort)?.with_max_page_bytes(options.max_page_bytes).with_low_priority(options.decode_low_priority))),
+                    EXT_PDF => Ok(Box::new(PdfSource::new(path)?.with_transcode(options.transcode).with_render(options.pdf_render))),
+                    EXT_CBZ => create_zipped_source(path, password, options),
+                    EXT_MOBI => Ok(Box::new(MobiSource::new(path, options.mobi_min_image_side)?)),
+                    EXT_RAR | EXT_CBR => Ok(Box::new(RarSource::new(path, password, options.case_insensitive_sort)?.with_max_page_bytes(options.max_page_bytes).with_low_priority(options.decode_low_priority))),
                     _ => Err(anyhow::anyhow!("不支持的文件格式")),
                 },
                 None => Err(anyhow::anyhow!("非法的后缀名")),
@@ -162,3 +925,416 @@ pub fn create_source(path: &Path, password: Option<String>) -> anyhow::Result<Bo
         }
     }    
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// 测试用的临时路径，带上进程号以免并行运行的测试互相干扰
+    pub fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("lmr_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn read_capped_rejects_declared_and_streamed_overflow() {
+        // 声明的大小超过上限时不读取也不预分配
+        let e = read_capped(io::repeat(0), u64::MAX, 16).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        // 声明的大小可能是假的，实际读到的超过上限同样拒绝
+        let e = read_capped(io::repeat(0).take(17), 4, 16).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(read_capped(io::repeat(1).take(16), 4, 16).unwrap(), [1; 16]);
+        assert_eq!(read_capped(io::repeat(1).take(32), 4, 0).unwrap().len(), 32);
+    }
+
+    /// 把（文件名，内容）依次写成 zip
+    pub fn zip_fixture(entries: &[(&str, &[u8])]) -> FileBytes {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// 纯色的 PNG，`shade` 为灰度
+    pub fn png_fixture(width: u32, height: u32, shade: u8) -> FileBytes {
+        let mut buffer = Cursor::new(Vec::new());
+        image::GrayImage::from_pixel(width, height, image::Luma([shade])).write_to(&mut buffer, ImageFormat::Png).unwrap();
+        buffer.into_inner()
+    }
+
+    /// 把（文件名，内容）依次写成 tar
+    pub fn tar_fixture(entries: &[(&str, &[u8])]) -> FileBytes {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, name, *content).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    /// 每页用 Helvetica 写一行 `texts` 中对应文字的 pdf，文字为空时该页是白色空页
+    pub fn pdf_fixture(texts: &[&str]) -> FileBytes {
+        let kids: Vec<String> = (0..texts.len()).map(|x| format!("{} 0 R", x * 2 + 4)).collect();
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), texts.len()),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        ];
+        for (index, text) in texts.iter().enumerate() {
+            let stream = if text.is_empty() { String::new() } else { format!("BT /F1 6 Tf 4 40 Td ({}) Tj ET", text) };
+            objects.push(format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 60 80] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>", index * 2 + 5));
+            objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", stream.len(), stream));
+        }
+
+        let mut content = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(content.len());
+            content.extend(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).into_bytes());
+        }
+        let xref = content.len();
+        content.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+        for offset in offsets {
+            content.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+        }
+        content.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).into_bytes());
+        content
+    }
+
+    #[test]
+    fn embedded_pdf_pages_follow_images() {
+        let images = [png_fixture(8, 12, 0), png_fixture(8, 12, 200)];
+        let archive = zip_fixture(&[("bonus.pdf", &pdf_fixture(&[""])), ("02.png", &images[1]), ("01.png", &images[0])]);
+        let path = temp_path("embedded_pdf.cbz");
+        std::fs::write(path.as_path(), archive).unwrap();
+
+        let ignored = create_source(path.as_path(), None, &SourceOptions::default()).map(|x| x.page_count());
+        let options = SourceOptions { expand_embedded_pdf: true, ..Default::default() };
+        let source = create_source(path.as_path(), None, &options);
+        std::fs::remove_file(path.as_path()).unwrap();
+        assert_eq!(ignored.unwrap(), 2);
+        let mut source = source.unwrap();
+        assert_eq!(source.page_count(), 3);
+        for (index, image) in images.iter().enumerate() {
+            assert_eq!(&source.get_page_bytes(index).unwrap(), image);
+        }
+        let pdf_page = image::load_from_memory(&source.get_page_bytes(2).unwrap()).unwrap();
+        assert!(pdf_page.width() > 0 && pdf_page.height() > pdf_page.width());
+    }
+
+    #[test]
+    fn tall_strip_is_split_into_tiles() {
+        assert_eq!(tile_ranges(20000, 8000), [(0, 8000), (8000, 8000), (16000, 4000)]);
+        assert!(tile_ranges(8000, 8000).is_empty());
+        assert!(tile_ranges(20000, 0).is_empty());
+
+        let dir = temp_path("tiles");
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        let transcode = TranscodeOptions { tile_height: 8000, ..Default::default() };
+        let page_cache = PageCache::new(png_fixture(4, 20000, 0), cache_path(0, dir.as_path()), &transcode).unwrap();
+        let ImageData::Tiled(_, aspect_ratio, tiles) = page_cache.get_data() else {
+            panic!("长图没有切块");
+        };
+        let heights: Vec<u32> = tiles.iter().map(|x| image::ImageReader::open(x).unwrap().with_guessed_format().unwrap().into_dimensions().unwrap().1).collect();
+        drop(page_cache);
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+        assert_eq!(aspect_ratio, 4. / 20000.);
+        assert_eq!(heights, [8000, 8000, 4000]);
+    }
+
+    #[test]
+    fn oversized_page_is_downscaled_to_bound() {
+        let dir = temp_path("downscale");
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        let transcode = TranscodeOptions { max_dimension: Some(2048), ..Default::default() };
+        let page_cache = PageCache::new(png_fixture(4000, 1000, 0), cache_path(0, dir.as_path()), &transcode).unwrap();
+        let cached = std::fs::read(cache_path(0, dir.as_path())).unwrap();
+        let data = page_cache.get_data();
+        let unbounded = PageCache::new(png_fixture(4000, 30, 0), cache_path(1, dir.as_path()), &Default::default()).unwrap();
+        let original = image::load_from_memory(&std::fs::read(cache_path(1, dir.as_path())).unwrap()).unwrap().width();
+        drop((page_cache, unbounded));
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+
+        // PNG 缩小后改存无损 WebP，宽高比按原图
+        assert_eq!(image::guess_format(&cached).unwrap(), ImageFormat::WebP);
+        let image = image::load_from_memory(&cached).unwrap();
+        assert_eq!((image.width(), image.height()), (2048, 512));
+        assert!(matches!(data, ImageData::Loaded(_, ratio) if ratio == 4.));
+        // 不设上限时保留原尺寸
+        assert_eq!(original, 4000);
+        assert_eq!(fit_within(1000, 4000, 2048, true), Some((512, 2048)));
+        assert_eq!(fit_within(1000, 4000, 2048, false), None);
+    }
+
+    #[test]
+    fn jpeg_quality_sets_downscaled_size() {
+        let noise = image::RgbImage::from_fn(400, 600, |x, y| {
+            let v = (x.wrapping_mul(7919) ^ y.wrapping_mul(104729)).wrapping_mul(2654435761) >> 24;
+            image::Rgb([v as u8, (v >> 1) as u8, (v >> 2) as u8])
+        });
+        let jpeg = encode_image(&DynamicImage::ImageRgb8(noise), &TranscodeOptions { format: TranscodeFormat::Jpeg, jpeg_quality: 100, ..Default::default() }).unwrap();
+        let downscaled = |jpeg_quality| {
+            let transcode = TranscodeOptions { jpeg_quality, max_dimension: Some(300), ..Default::default() };
+            downscale(Cow::Borrowed(jpeg.as_slice()), &transcode).unwrap().into_owned()
+        };
+        let (low, high) = (downscaled(30), downscaled(95));
+        assert_eq!(image::guess_format(&low).unwrap(), ImageFormat::Jpeg);
+        assert_eq!(image::load_from_memory(&low).unwrap().height(), 300);
+        assert!(low.len() < high.len());
+        // 预设无损，线稿不会被意外重新压缩
+        assert_eq!(TranscodeOptions::from(&Config::default()).format, TranscodeFormat::Png);
+    }
+
+    #[test]
+    fn two_level_nested_cbz_expands_to_chapters() {
+        let pages: Vec<FileBytes> = (0..4).map(|x| png_fixture(8, 12, x * 60)).collect();
+        let chapter_1 = zip_fixture(&[("01.png", &pages[0]), ("02.png", &pages[1])]);
+        let chapter_2 = zip_fixture(&[("01.png", &pages[2]), ("02.png", &pages[3])]);
+        let outer = zip_fixture(&[("第二話.cbz", &chapter_2), ("第一話.cbz", &chapter_1)]);
+        let path = temp_path("nested.cbz");
+        std::fs::write(path.as_path(), outer).unwrap();
+
+        let options = SourceOptions { cjk_numeral_sort: true, ..Default::default() };
+        let source = create_source(path.as_path(), None, &options);
+        std::fs::remove_file(path.as_path()).unwrap();
+        let mut source = source.unwrap();
+        assert_eq!(source.page_count(), 4);
+        assert_eq!(source.chapter_offsets(), [0, 2]);
+        for (index, page) in pages.iter().enumerate() {
+            assert_eq!(&source.get_page_bytes(index).unwrap(), page);
+        }
+    }
+
+    #[test]
+    fn nested_archives_bounded_by_outer_size() {
+        // 一页全零的图片压缩后很小，展开后远超外层压缩包的大小
+        let inner = zip_fixture(&[("01.png", &vec![0; 4 << 20])]);
+        let outer = zip_fixture(&[("a.cbz", &inner)]);
+        let mut zipped_source = ZippedSource::from_reader(Cursor::new(outer), None, false).unwrap();
+        let mut budget = zipped_source.archive_len();
+        assert!(zipped_source.nested_archives(false, false, &mut budget).is_err());
+    }
+
+    #[test]
+    fn priority_hook_runs_only_when_enabled() {
+        use std::sync::atomic::AtomicBool;
+
+        let caller = std::thread::current().id();
+        for enabled in [true, false] {
+            let hooked = AtomicBool::new(false);
+            let hook_thread = std::sync::Mutex::new(None);
+            let (work_thread, result) = run_with_hook(enabled, || {
+                hooked.store(true, AtomicOrdering::SeqCst);
+                *hook_thread.lock().unwrap() = Some(std::thread::current().id());
+            }, || (std::thread::current().id(), 42));
+            assert_eq!(result, 42);
+            assert_eq!(hooked.load(AtomicOrdering::SeqCst), enabled);
+            // 开启时在另一个线程上降低优先级并解码，不影响调用线程
+            if enabled {
+                assert_ne!(work_thread, caller);
+                assert_eq!(*hook_thread.lock().unwrap(), Some(work_thread));
+            } else {
+                assert_eq!(work_thread, caller);
+            }
+        }
+        assert_eq!(run_low_priority(true, || 7), 7);
+    }
+
+    #[test]
+    fn mac_artifacts_are_skipped_at_any_depth() {
+        assert!(is_mac_artifact(Path::new("__MACOSX/img.jpg")));
+        assert!(is_mac_artifact(Path::new("folder/__MACOSX/img.jpg")));
+        assert!(is_mac_artifact(Path::new("._cover.jpg")));
+        assert!(is_mac_artifact(Path::new("vol1/chapter/._001.jpg")));
+        // 真的以 `._` 开头命名的图片同样被跳过，这是有意的取舍
+        assert!(!check_valid_ext("vol1/._notmac.jpg"));
+        assert!(!is_mac_artifact(Path::new("folder/cover.jpg")));
+        assert!(!is_mac_artifact(Path::new("MACOSX/a._b.jpg")));
+        assert!(check_valid_ext("folder/cover.JPG"));
+    }
+
+    #[test]
+    fn natural_order_compares_numbers_by_value() {
+        let mut names = vec!["10", "01", "2", "1"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        // 数值相同时前导零少的在前
+        assert_eq!(names, ["1", "01", "2", "10"]);
+        let mut names = vec!["ch2_p01.jpg", "ch1_p10.jpg", "ch10_p1.jpg", "ch1_p09.jpg"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["ch1_p09.jpg", "ch1_p10.jpg", "ch2_p01.jpg", "ch10_p1.jpg"]);
+        assert_eq!(natural_cmp("page_2.jpg", "page_10.jpg"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+        // 超出整数范围的数字按位数比较
+        assert_eq!(natural_cmp("99999999999999999999999", "100000000000000000000000"), Ordering::Less);
+    }
+
+    #[test]
+    fn case_insensitive_sort_breaks_ties_by_original_name() {
+        let mut names = vec!["page2.jpg", "page1.jpg", "Page10.JPG", "Page1.JPG", "cover.jpg"];
+        names.sort_by(|a, b| page_name_cmp(a, b, true));
+        assert_eq!(names, ["cover.jpg", "Page1.JPG", "page1.jpg", "page2.jpg", "Page10.JPG"]);
+        // 区分大小写时大写字母整体排在小写之前
+        names.sort_by(|a, b| page_name_cmp(a, b, false));
+        assert_eq!(names, ["Page1.JPG", "Page10.JPG", "cover.jpg", "page1.jpg", "page2.jpg"]);
+    }
+
+    #[test]
+    fn cjk_numerals_sort_by_value() {
+        let mut names = vec!["第十巻", "第二巻", "第一巻"];
+        names.sort_by(|a, b| volume_name_cmp(a, b, false, true));
+        assert_eq!(names, ["第一巻", "第二巻", "第十巻"]);
+    }
+
+    #[test]
+    fn cjk_numeral_values() {
+        assert_eq!(cjk_numeral_value("十二"), Some(12));
+        assert_eq!(cjk_numeral_value("一百零五"), Some(105));
+        assert_eq!(cjk_numeral_value("两万"), Some(20000));
+        assert_eq!(cjk_numeral_value("二〇二四"), Some(2024));
+        assert_eq!(cjk_numeral_value("十"), Some(10));
+        assert_eq!(cjk_numeral_value("万"), Some(10000));
+        assert_eq!(cjk_numeral_value(""), None);
+    }
+
+    #[test]
+    fn solid_white_page_is_blank() {
+        let white = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(1200, 1700, image::Rgb([255, 255, 255])));
+        assert!(is_uniform(&white));
+
+        // 一小段细线文字也算有内容，不能被缩小平均掉
+        let mut text = image::RgbImage::from_pixel(1200, 1700, image::Rgb([255, 255, 255]));
+        for y in (200..400).step_by(20) {
+            for x in 100..400 {
+                text.put_pixel(x, y, image::Rgb([0, 0, 0]));
+            }
+        }
+        assert!(!is_uniform(&DynamicImage::ImageRgb8(text)));
+    }
+
+    #[test]
+    fn thumbnails_removed_without_touching_pages() {
+        let dir = temp_path("thumbs");
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        // 页面缓存写到一半留下的临时文件也一并删除
+        for path in [thumb_path(0, dir.as_path()), thumb_path(1, dir.as_path()).with_extension("part"), cache_path(0, dir.as_path()), cache_path(2, dir.as_path()).with_extension("part")] {
+            std::fs::write(path, b"x").unwrap();
+        }
+
+        remove_thumbnails(dir.as_path());
+        let mut left: Vec<String> = std::fs::read_dir(dir.as_path()).unwrap()
+            .map(|x| x.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+        assert_eq!(left, ["page_000"]);
+    }
+
+    #[test]
+    fn thumbnail_fits_longest_side() {
+        let dir = temp_path("thumb_side");
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        let page = cache_path(0, dir.as_path());
+        std::fs::write(page.as_path(), png_fixture(300, 800, 90)).unwrap();
+        let thumb = thumb_path(0, dir.as_path());
+        write_thumbnail(page.as_path(), thumb.as_path(), 200).unwrap();
+        let image = image::load_from_memory(&std::fs::read(thumb.as_path()).unwrap());
+        let part_left = thumb.with_extension("part").exists();
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+
+        let image = image.unwrap();
+        assert_eq!((image.width(), image.height()), (75, 200));
+        assert!(!part_left);
+    }
+
+    #[test]
+    fn cache_dir_name_follows_output_settings() {
+        let sha256 = [0xab; 32];
+        let options = SourceOptions::default();
+        let name = cache_dir_name(&sha256, &options);
+        assert!(name.starts_with(&"ab".repeat(32)));
+        assert_eq!(name, cache_dir_name(&sha256, &options.clone()));
+
+        let mut jpeg = options.clone();
+        jpeg.transcode.jpeg_quality = 50;
+        let mut smaller = options.clone();
+        smaller.transcode.max_dimension = Some(2000);
+        let mut pdf = options.clone();
+        pdf.pdf_render.antialias = true;
+        for other in [jpeg, smaller, pdf] {
+            assert_ne!(name, cache_dir_name(&sha256, &other));
+        }
+    }
+
+    #[test]
+    fn bare_unit_is_not_zero() {
+        assert_eq!(expand_cjk_numerals("万事屋"), "10000事屋");
+        assert_eq!(volume_name_cmp("万事屋", "0事屋", false, true), Ordering::Greater);
+    }
+
+    #[test]
+    fn picked_folder_of_chapters_opens_as_one_book() {
+        let root = temp_path("chapters");
+        let pages: Vec<FileBytes> = (0..5).map(|x| png_fixture(4, 6, x * 50)).collect();
+        for (dir, name, page) in [("第2话", "1.png", 3), ("第2话", "2.png", 4), ("第1话", "2.png", 2), ("第1话", "cover.png", 1), (".", "0.png", 0)] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(name), &pages[page]).unwrap();
+        }
+        let options = SourceOptions { subdirs_as_chapters: true, ..Default::default() };
+        let source = create_source(root.as_path(), None, &options);
+        let flat = create_source(root.as_path(), None, &SourceOptions::default()).map(|x| x.page_count());
+        let mut source = source.unwrap();
+        let read: Vec<FileBytes> = (0..source.page_count()).map(|x| source.get_page_bytes(x).unwrap()).collect();
+        std::fs::remove_dir_all(root.as_path()).unwrap();
+        // 根目录的图片在前，各章按名称排列，封面提到章首
+        assert_eq!(read, pages);
+        assert_eq!(flat.unwrap(), 1);
+    }
+
+    #[test]
+    fn tar_kind_reads_compound_extensions() {
+        assert_eq!(tar_kind("Vol.1.TAR.GZ"), Some(EXT_GZ));
+        assert_eq!(tar_kind("a.tgz"), Some(EXT_GZ));
+        assert_eq!(tar_kind("a.tar.xz"), Some(EXT_XZ));
+        assert_eq!(tar_kind("a.txz"), Some(EXT_XZ));
+        assert_eq!(tar_kind("a.tbz2"), Some(EXT_BZ2));
+        assert_eq!(tar_kind("a.cbt"), Some(EXT_TAR));
+        assert_eq!(tar_kind("a.tar"), Some(EXT_TAR));
+        assert_eq!(tar_kind("a.zip"), None);
+        assert_eq!(tar_kind("tar"), None);
+    }
+
+    #[test]
+    fn format_info_lists_every_format_with_flags() {
+        let formats = format_info();
+        assert_eq!(formats.len(), SUPPORTED_FILE_FORMATS.len());
+        // 每种格式都有专门的说明，而不是退回后缀名
+        assert!(formats.iter().all(|x| x.enabled && x.label != x.ext), "{:?}", formats);
+        let find = |ext: &str| formats.iter().find(|x| x.ext == ext).unwrap();
+        let flags = |ext: &str| (find(ext).is_archive, find(ext).supports_password);
+        assert_eq!(flags(EXT_CBZ), (true, true));
+        assert_eq!(flags(EXT_7Z), (true, true));
+        assert_eq!(flags(EXT_CBR), (true, true));
+        assert_eq!(flags(EXT_GZ), (true, false));
+        assert_eq!(flags(EXT_PDF), (false, false));
+        assert_eq!(flags(EXT_EPUB), (false, false));
+    }
+
+    #[test]
+    fn reencode_rewrites_cache_file_as_png() {
+        let path = temp_path("reencode_0");
+        let mut bmp = Cursor::new(Vec::new());
+        image::GrayImage::from_pixel(6, 9, image::Luma([90])).write_to(&mut bmp, ImageFormat::Bmp).unwrap();
+        std::fs::write(path.as_path(), bmp.into_inner()).unwrap();
+        reencode_as_png(path.as_path()).unwrap();
+        let bytes = std::fs::read(path.as_path()).unwrap();
+        std::fs::remove_file(path.as_path()).unwrap();
+        assert_eq!(image::guess_format(&bytes).unwrap(), ImageFormat::Png);
+        assert_eq!(image::load_from_memory(&bytes).unwrap().to_luma8(), image::GrayImage::from_pixel(6, 9, image::Luma([90])));
+        assert!(!path.with_extension("part").exists());
+    }
+}
@@ -0,0 +1,100 @@
+use super::{PageSource, FileBytes};
+use shared::Capabilities;
+
+/// 将多个页面源首尾相接，视为一本书，每个子源的开头即一个章节
+pub struct ConcatSource {
+    sha256: [u8; 32],
+    sources: Vec<Box<dyn PageSource>>,
+    offsets: Vec<usize>,
+    page_count: usize,
+}
+
+impl PageSource for ConcatSource {
+    fn get_page_bytes(&mut self, index: usize) -> anyhow::Result<FileBytes> {
+        let (source_index, offset) = self.locate(index);
+        self.sources[source_index].get_page_bytes(index - offset)
+    }
+
+    fn page_count(&self) -> usize {
+        self.page_count
+    }
+
+    fn sha256(&self) -> &[u8; 32] {
+        &self.sha256
+    }
+
+    fn chapter_offsets(&self) -> Vec<usize> {
+        let mut v: Vec<usize> = self.sources.iter().zip(self.offsets.iter())
+            .filter(|(source, _)| source.page_count() > 0)
+            .flat_map(|(source, &offset)| {
+                std::iter::once(offset).chain(source.chapter_offsets().into_iter().map(move |x| x + offset))
+            })
+            .collect();
+        v.sort_unstable();
+        v.dedup();
+        v
+    }
+
+    fn right_to_left(&self) -> Option<bool> {
+        self.sources.first().and_then(|x| x.right_to_left())
+    }
+
+    fn title(&self) -> Option<String> {
+        self.sources.first().and_then(|x| x.title())
+    }
+
+    fn page_text(&self, index: usize) -> Option<String> {
+        let (source_index, offset) = self.locate(index);
+        self.sources[source_index].page_text(index - offset)
+    }
+
+    fn page_name(&self, index: usize) -> Option<String> {
+        let (source_index, offset) = self.locate(index);
+        self.sources[source_index].page_name(index - offset)
+    }
+
+    /// 取最保守的子源
+    fn prefetch_hint(&self) -> usize {
+        self.sources.iter().map(|x| x.prefetch_hint()).min().unwrap_or(1)
+    }
+
+    /// 任一子源的限制都会限制整体，监视与密码只看最外层的源
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = self.sources.first().map(|x| x.capabilities()).unwrap_or_default();
+        for source in self.sources.iter().skip(1) {
+            let x = source.capabilities();
+            capabilities.random_access &= x.random_access;
+            capabilities.solid |= x.solid;
+            capabilities.has_toc |= x.has_toc;
+        }
+        capabilities.has_chapters = self.chapter_offsets().len() > 1;
+        capabilities
+    }
+}
+
+impl ConcatSource {
+    /// `sha256` 由调用方给出，通常取最外层文件的哈希
+    pub fn new(sha256: [u8; 32], sources: Vec<Box<dyn PageSource>>) -> Self {
+        let mut page_count = 0;
+        let offsets = sources.iter().map(|source| {
+            let offset = page_count;
+            page_count += source.page_count();
+            offset
+        }).collect();
+
+        Self {
+            sha256,
+            sources,
+            offsets,
+            page_count,
+        }
+    }
+
+    /// 返回全局索引所在子源的下标及该子源的起始索引
+    ///
+    /// 取起始索引不大于 `index` 的最后一个子源，页数为 0 的子源与下一个子源起始索引相同，因此会被自然跳过
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let source_index = self.offsets.partition_point(|&offset| offset <= index) - 1;
+        (source_index, self.offsets[source_index])
+    }
+}
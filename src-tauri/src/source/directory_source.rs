@@ -1,13 +1,18 @@
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
 use std::io::{self, Cursor};
+use std::time::UNIX_EPOCH;
 
-use super::{PageSource, FileBytes, check_valid_ext, cal_sha256};
+use super::{PageSource, FileBytes, check_valid_ext, cal_sha256, page_name_cmp, volume_name_cmp};
+use shared::Capabilities;
 
 pub struct DirectorySource{
     sha256: [u8; 32],
     source_dir: PathBuf,
     img_names: Vec<OsString>,
+    right_to_left: Option<bool>,
+    /// 作为章节时的文件夹名，加在页面文件名前，避免各章同名的页混淆
+    chapter: Option<String>,
 }
 
 impl PageSource for DirectorySource {
@@ -22,10 +27,34 @@ impl PageSource for DirectorySource {
     fn sha256(&self) -> &[u8; 32] {
         &self.sha256
     }
+
+    /// 直接读文件，代价最低
+    fn prefetch_hint(&self) -> usize {
+        2
+    }
+
+    fn right_to_left(&self) -> Option<bool> {
+        self.right_to_left
+    }
+
+    fn page_name(&self, index: usize) -> Option<String> {
+        let name = self.img_names.get(index)?.to_string_lossy();
+        Some(match self.chapter.as_deref() {
+            Some(chapter) => format!("{}/{}", chapter, name),
+            None => name.into_owned(),
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            watchable: true,
+            ..Capabilities::new(false, false)
+        }
+    }
 }
 
 impl DirectorySource {
-    pub fn new(dir_path: impl AsRef<Path>) -> io::Result<Self> {
+    pub fn new(dir_path: impl AsRef<Path>, case_insensitive_sort: bool) -> io::Result<Self> {
         let source_dir = dir_path.as_ref().to_path_buf();
 
         let mut img_names: Vec<OsString> = std::fs::read_dir(dir_path.as_ref())?
@@ -39,14 +68,149 @@ impl DirectorySource {
             )
             .filter(|file_name| check_valid_ext(file_name))
             .collect();
-        img_names.sort_unstable();
-        let total_names: OsString = img_names.iter().map(|s| s.as_os_str()).collect();
-        let sha256 = cal_sha256(Cursor::new(total_names.into_encoded_bytes()))?;
+        img_names.sort_unstable_by(|a, b| page_name_cmp(a.to_string_lossy().as_ref(), b.to_string_lossy().as_ref(), case_insensitive_sort));
+        let sha256 = listing_sha256(source_dir.as_path(), img_names.as_slice())?;
+
+        let right_to_left = read_direction_marker(source_dir.as_path());
 
         Ok(Self {
             sha256,
             source_dir,
             img_names,
+            right_to_left,
+            chapter: None,
         })
     }
+
+    /// 作为一本书中的一章：名为 cover 的图片提到最前，页面名前加上文件夹名
+    pub fn as_chapter(mut self) -> io::Result<Self> {
+        if let Some(cover) = self.img_names.iter().position(|x| is_cover(x)) {
+            let cover = self.img_names.remove(cover);
+            self.img_names.insert(0, cover);
+            self.sha256 = listing_sha256(self.source_dir.as_path(), self.img_names.as_slice())?;
+        }
+        self.chapter = self.source_dir.file_name().map(|x| x.to_string_lossy().into_owned());
+        Ok(self)
+    }
+}
+
+/// 由文件夹路径与各图片的文件名、大小、修改时间计算哈希
+///
+/// 只看文件名的话，不同文件夹里同名的图片会共用缓存与阅读进度，图片改动后也会沿用旧的缓存
+fn listing_sha256(dir: &Path, img_names: &[OsString]) -> io::Result<[u8; 32]> {
+    let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let mut listing = dir.as_os_str().as_encoded_bytes().to_vec();
+    for name in img_names {
+        let metadata = std::fs::metadata(dir.join(name)).ok();
+        let size = metadata.as_ref().map_or(0, |x| x.len());
+        let modified = metadata.and_then(|x| x.modified().ok())
+            .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |x| x.as_nanos());
+        listing.extend((name.len() as u64).to_le_bytes());
+        listing.extend(name.as_encoded_bytes());
+        listing.extend(size.to_le_bytes());
+        listing.extend(modified.to_le_bytes());
+    }
+    cal_sha256(Cursor::new(listing))
+}
+
+fn is_cover(file_name: &OsStr) -> bool {
+    Path::new(file_name).file_stem().is_some_and(|x| x.eq_ignore_ascii_case("cover"))
+}
+
+/// 文件夹下的子文件夹，按卷名的规则排序；是否含有图片由调用方判断
+pub fn sub_dirs(dir: &Path, case_insensitive: bool, cjk_numeral: bool) -> io::Result<Vec<PathBuf>> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|x| x.is_dir()))
+        .map(|entry| entry.path())
+        .collect();
+    dirs.sort_unstable_by(|a, b| {
+        let name = |x: &Path| x.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+        volume_name_cmp(name(a).as_str(), name(b).as_str(), case_insensitive, cjk_numeral)
+    });
+    Ok(dirs)
+}
+
+/// 目录中的 `.rtl` / `.ltr` 空文件，或内容为 rtl / ltr 的 `direction.txt`，指明这本书的阅读方向
+fn read_direction_marker(dir: &Path) -> Option<bool> {
+    if dir.join(".rtl").is_file() {
+        return Some(true);
+    }
+    if dir.join(".ltr").is_file() {
+        return Some(false);
+    }
+    let content = std::fs::read_to_string(dir.join("direction.txt")).ok()?;
+    match content.trim().to_ascii_lowercase().as_str() {
+        "rtl" => Some(true),
+        "ltr" => Some(false),
+        x => {
+            eprintln!("无法识别 direction.txt 的内容：{}", x);
+            None
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{SourceOptions, create_source};
+
+    #[test]
+    fn same_names_in_other_folder_hash_differently() {
+        let root = crate::source::tests::temp_path("dirs");
+        let (a, b) = (root.join("a"), root.join("b"));
+        for dir in [a.as_path(), b.as_path()] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("001.jpg"), b"page").unwrap();
+        }
+        let sha_a = *DirectorySource::new(a.as_path(), false).unwrap().sha256();
+        let sha_b = *DirectorySource::new(b.as_path(), false).unwrap().sha256();
+        std::fs::write(a.join("001.jpg"), b"edited page").unwrap();
+        let edited = *DirectorySource::new(a.as_path(), false).unwrap().sha256();
+        std::fs::remove_dir_all(root.as_path()).unwrap();
+
+        assert_ne!(sha_a, sha_b);
+        assert_ne!(sha_a, edited);
+    }
+
+    #[test]
+    fn direction_marker_suggests_reading_direction() {
+        let root = crate::source::tests::temp_path("direction");
+        let (rtl, ltr, plain) = (root.join("rtl"), root.join("ltr"), root.join("plain"));
+        for dir in [rtl.as_path(), ltr.as_path(), plain.as_path()] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("001.jpg"), b"page").unwrap();
+        }
+        std::fs::write(rtl.join(".rtl"), b"").unwrap();
+        std::fs::write(ltr.join("direction.txt"), b" LTR\n").unwrap();
+        let suggested = |dir: &Path| create_source(dir, None, &SourceOptions::default()).unwrap().right_to_left();
+        let results = (suggested(rtl.as_path()), suggested(ltr.as_path()), suggested(plain.as_path()));
+        std::fs::remove_dir_all(root.as_path()).unwrap();
+
+        // 没有标记时不给出建议，由配置决定
+        assert_eq!(results, (Some(true), Some(false), None));
+    }
+
+    #[test]
+    fn subfolders_become_chapters_in_natural_order() {
+        let root = crate::source::tests::temp_path("subdir_chapters");
+        for (dir, names) in [("ch10", ["01.jpg", "02.jpg", "cover.jpg"]), ("ch2", ["10.jpg", "2.jpg", "1.jpg"])] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            for name in names {
+                std::fs::write(root.join(dir).join(name), name).unwrap();
+            }
+        }
+        let options = SourceOptions { subdirs_as_chapters: true, ..Default::default() };
+        let mut source = create_source(root.as_path(), None, &options).unwrap();
+        let pages: Vec<String> = (0..source.page_count())
+            .map(|x| String::from_utf8(source.get_page_bytes(x).unwrap()).unwrap())
+            .collect();
+        let offsets = source.chapter_offsets();
+        std::fs::remove_dir_all(root.as_path()).unwrap();
+
+        // ch2 排在 ch10 前，封面提到章首
+        assert_eq!(pages, ["1.jpg", "2.jpg", "10.jpg", "cover.jpg", "01.jpg", "02.jpg"]);
+        assert_eq!(offsets, [0, 3]);
+    }
 }
@@ -6,46 +6,114 @@ use std::path::{Path, PathBuf};
 use std::io::{Read, Seek};
 use std::collections::HashSet;
 
-use super::{PageSource, ZippedSource};
-
-pub struct EpubSource(ZippedSource);
+use super::{PageSource, SourceOptions, ZippedSource};
+use shared::Capabilities;
+
+pub struct EpubSource {
+    inner: ZippedSource,
+    chapter_offsets: Vec<usize>,
+    page_texts: Vec<Option<String>>,
+    title: Option<String>,
+}
 
 impl PageSource for EpubSource {
     fn get_page_bytes(&mut self, index: usize) -> anyhow::Result<super::FileBytes> {
-        self.0.get_page_bytes(index)
+        self.inner.get_page_bytes(index)
     }
 
     fn page_count(&self) -> usize {
-        self.0.page_count()
+        self.inner.page_count()
     }
 
     fn sha256(&self) -> &[u8; 32] {
-        self.0.sha256()
+        self.inner.sha256()
+    }
+
+    fn chapter_offsets(&self) -> Vec<usize> {
+        self.chapter_offsets.clone()
+    }
+
+    fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+
+    fn page_text(&self, index: usize) -> Option<String> {
+        self.page_texts.get(index).cloned().flatten()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            has_toc: true,
+            ..Capabilities::new(false, !self.chapter_offsets.is_empty())
+        }
     }
 }
 
 impl EpubSource {
-    pub fn new(file_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+    pub fn new(file_path: impl AsRef<Path>, options: &SourceOptions) -> anyhow::Result<Self> {
         let path = file_path.as_ref();
-        let img_paths = {
+        let (img_paths, mut chapter_offsets, mut page_texts, title) = {
             let doc = EpubDoc::new(path)?;
-            get_imgs(doc)
+            let title = doc.get_title();
+            let (img_paths, chapter_offsets, page_texts) = get_imgs(doc);
+            (img_paths, chapter_offsets, page_texts, title)
         };
-        let mut inner = ZippedSource::new(path, None)?;
+        let mut inner = ZippedSource::new(path, None, options.case_insensitive_sort)?.with_max_page_bytes(options.max_page_bytes);
         let img_paths: Vec<&Path> = img_paths.iter().map(|p| p.as_path()).collect();
-        inner.rebuild_indice_table(img_paths.as_slice());
+        let found = inner.rebuild_indice_table(img_paths.as_slice());
+        if found.contains(&false) {
+            chapter_offsets = remap_offsets(chapter_offsets.as_slice(), found.as_slice());
+            let mut found_iter = found.iter();
+            page_texts.retain(|_| *found_iter.next().unwrap_or(&false));
+        }
 
-        Ok(Self(inner))
+        Ok(Self {
+            inner,
+            chapter_offsets,
+            page_texts,
+            title,
+        })
     }
 }
 
-fn get_imgs<R: Read + Seek>(mut doc: EpubDoc<R>) -> Vec<PathBuf> {
+/// 跳过找不到的页后，章节起点换算为新的索引；整章都找不到时该章节并入下一章
+fn remap_offsets(offsets: &[usize], found: &[bool]) -> Vec<usize> {
+    let page_count = found.iter().filter(|&&x| x).count();
+    let mut remapped: Vec<usize> = offsets.iter()
+        .map(|&offset| found.iter().take(offset).filter(|&&x| x).count())
+        .filter(|&offset| offset < page_count)
+        .collect();
+    remapped.dedup();
+    remapped
+}
+
+/// 返回全书图片路径、目录中各章节起始页的索引，以及各页的文字
+///
+/// 一个 XHTML 中的文字归到其中的第一张图，没有图片的 XHTML 的文字归到其后的第一张图
+fn get_imgs<R: Read + Seek>(mut doc: EpubDoc<R>) -> (Vec<PathBuf>, Vec<usize>, Vec<Option<String>>) {
     let mut v = Vec::with_capacity(300);
+    let mut texts = Vec::with_capacity(300);
+    let mut pending_text = String::new();
+    // spine 中第 i 项之前已有的图片数
+    let mut spine_offsets = Vec::with_capacity(doc.get_num_chapters());
 
     loop {
+        spine_offsets.push(v.len());
         if let Some(cur_path) = doc.get_current_path() {
             if let Some((html, _mime)) = doc.get_current_str() {
-                v.extend(extract_img_paths(html.as_str(), cur_path.as_path()));
+                let img_paths = extract_img_paths(html.as_str(), cur_path.as_path());
+                let text = extract_text(html.as_str());
+                if !text.is_empty() {
+                    if !pending_text.is_empty() {
+                        pending_text.push('\n');
+                    }
+                    pending_text.push_str(text.as_str());
+                }
+                if !img_paths.is_empty() {
+                    texts.push((!pending_text.is_empty()).then(|| std::mem::take(&mut pending_text)));
+                    texts.resize(texts.len() + img_paths.len() - 1, None);
+                    v.extend(img_paths);
+                }
             }
         }
 
@@ -54,7 +122,31 @@ fn get_imgs<R: Read + Seek>(mut doc: EpubDoc<R>) -> Vec<PathBuf> {
         }
     }
 
-    v
+    let mut chapter_offsets: Vec<usize> = doc.toc.iter()
+        .filter_map(|nav_point| {
+            // 去掉 `#锚点` 部分再查 spine
+            let content = nav_point.content.to_string_lossy();
+            let content = PathBuf::from(content.split('#').next().unwrap_or_default());
+            doc.resource_uri_to_chapter(&content)
+        })
+        .filter_map(|spine_index| spine_offsets.get(spine_index).copied())
+        .filter(|&offset| offset < v.len())
+        .collect();
+    chapter_offsets.sort_unstable();
+    chapter_offsets.dedup();
+
+    (v, chapter_offsets, texts)
+}
+
+/// 提取正文的纯文字，连续空白合并为一个空格
+fn extract_text(html: &str) -> String {
+    let dom = Html::parse_document(html);
+    static BODY_SEL: std::sync::OnceLock<Selector> = std::sync::OnceLock::new();
+    dom.select(BODY_SEL.get_or_init(|| Selector::parse("body").unwrap()))
+        .flat_map(|body| body.text())
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<&str>>()
+        .join(" ")
 }
 
 /// 返回本页所有图片的 **zip 内绝对路径**，顺序 = DOM 出现顺序
@@ -87,3 +179,63 @@ fn normalize_src(src_raw: &str, base_path: &Path) -> PathBuf {
     let base_dir = base_path.parent().unwrap_or_else(|| Path::new(""));
     base_dir.join(decoded.as_ref()).clean()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::create_source;
+    use crate::source::tests::{png_fixture, temp_path, zip_fixture};
+
+    const CONTAINER: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#;
+
+    const OPF: &str = r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>测试</dc:title><dc:identifier id="id">test</dc:identifier></metadata>
+  <manifest>
+    <item id="p1" href="text/p1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="p2" href="text/p2.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="p1"/><itemref idref="p2"/></spine>
+</package>"#;
+
+    fn xhtml(images: &[&str]) -> String {
+        let images: String = images.iter().map(|x| format!(r#"<img src="../images/{}"/>"#, x)).collect();
+        format!(r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>{}</body></html>"#, images)
+    }
+
+    #[test]
+    fn pages_follow_spine_and_dom_order() {
+        let pages: Vec<Vec<u8>> = (0..3).map(|x| png_fixture(4, 6, x * 100)).collect();
+        // zip 中按名称倒序存放，spine 与 DOM 的顺序是 z、a、m，另有一张不存在的图
+        let (p1, p2) = (xhtml(&["z.png", "a.png"]), xhtml(&["missing.png", "m.png"]));
+        let archive = zip_fixture(&[
+            ("mimetype", b"application/epub+zip"),
+            ("META-INF/container.xml", CONTAINER.as_bytes()),
+            ("OEBPS/content.opf", OPF.as_bytes()),
+            ("OEBPS/images/m.png", &pages[2]),
+            ("OEBPS/images/a.png", &pages[1]),
+            ("OEBPS/images/z.png", &pages[0]),
+            ("OEBPS/text/p2.xhtml", p2.as_bytes()),
+            ("OEBPS/text/p1.xhtml", p1.as_bytes()),
+        ]);
+        let path = temp_path("book.epub");
+        std::fs::write(path.as_path(), archive).unwrap();
+        let source = create_source(path.as_path(), None, &SourceOptions::default());
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        let mut source = source.unwrap();
+        assert_eq!(source.title().as_deref(), Some("测试"));
+        let read: Vec<Vec<u8>> = (0..source.page_count()).map(|x| source.get_page_bytes(x).unwrap()).collect();
+        assert_eq!(read, pages);
+    }
+
+    #[test]
+    fn offsets_skip_missing_pages() {
+        // 第二章整章缺失时并入第三章
+        assert_eq!(remap_offsets(&[0, 2, 3], &[true, true, false, true]), [0, 2]);
+        assert_eq!(remap_offsets(&[0, 3], &[true, true, true, false]), [0]);
+    }
+}
@@ -1,5 +1,6 @@
 use mobi::Mobi;
 
+use std::io::Cursor;
 use std::path::Path;
 use std::fs::File;
 
@@ -29,7 +30,8 @@ impl PageSource for MobiSource {
 }
 
 impl MobiSource {
-    pub fn new(file_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+    /// 短边小于 `min_side` 像素的图片视为缩略图，不作为单独的页
+    pub fn new(file_path: impl AsRef<Path>, min_side: u32) -> anyhow::Result<Self> {
         let mut file = File::open(file_path.as_ref())?;
         let sha256 = cal_sha256(&mut file)?;
         let mobi = Mobi::from_read(file)?;
@@ -38,7 +40,7 @@ impl MobiSource {
             .range(mobi.metadata.mobi.first_image_index as usize ..)
             .iter()
             .map(|record| record.content)
-            .filter(|x| x.len() > 1 << 15 && image::guess_format(x).is_ok())
+            .filter(|x| is_page_image(x, min_side))
             .map(|x| x.to_vec())
             .collect();
 
@@ -48,3 +50,81 @@ impl MobiSource {
         })
     }
 }
+
+fn is_page_image(content: &[u8], min_side: u32) -> bool {
+    let Ok(format) = image::guess_format(content) else {
+        return false;
+    };
+    match image::ImageReader::with_format(Cursor::new(content), format).into_dimensions() {
+        Ok((width, height)) => width.min(height) >= min_side,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{SourceOptions, create_source};
+    use crate::source::tests::{png_fixture, temp_path};
+
+    /// 只含 PalmDOC 与 MOBI 头的最小 mobi，第 1 条记录起依次为 `records`
+    fn mobi_fixture(records: &[&[u8]]) -> FileBytes {
+        const NAME: &[u8] = b"Test";
+        let mut record_0 = Vec::new();
+        // PalmDOC 头：不压缩、无文字记录、不加密
+        record_0.extend(1u16.to_be_bytes());
+        record_0.extend([0; 14]);
+        // MOBI 头，长度 232
+        let mut mobi = vec![0u8; 232];
+        mobi[..4].copy_from_slice(b"MOBI");
+        mobi[4..8].copy_from_slice(&232u32.to_be_bytes());
+        mobi[8..12].copy_from_slice(&2u32.to_be_bytes());
+        mobi[12..16].copy_from_slice(&65001u32.to_be_bytes());
+        mobi[64..68].copy_from_slice(&(records.len() as u32 + 1).to_be_bytes());
+        mobi[68..72].copy_from_slice(&(16 + 232u32).to_be_bytes());
+        mobi[72..76].copy_from_slice(&(NAME.len() as u32).to_be_bytes());
+        mobi[92..96].copy_from_slice(&1u32.to_be_bytes());
+        record_0.extend(mobi);
+        record_0.extend(NAME);
+
+        let count = records.len() + 1;
+        let mut offset = 78 + 8 * count + 2;
+        let mut content = Vec::new();
+        let mut name = [0u8; 32];
+        name[..NAME.len()].copy_from_slice(NAME);
+        content.extend(name);
+        content.extend([0; 28]);
+        content.extend(b"BOOKMOBI");
+        content.extend([0; 8]);
+        content.extend((count as u16).to_be_bytes());
+        for (id, len) in std::iter::once(record_0.len()).chain(records.iter().map(|x| x.len())).enumerate() {
+            content.extend((offset as u32).to_be_bytes());
+            content.extend((id as u32).to_be_bytes());
+            offset += len;
+        }
+        content.extend([0; 2]);
+        content.extend(record_0);
+        for record in records {
+            content.extend(*record);
+        }
+        content
+    }
+
+    #[test]
+    fn thumbnails_and_other_records_are_skipped() {
+        let pages = [png_fixture(300, 400, 0), png_fixture(320, 480, 255)];
+        let thumbnail = png_fixture(40, 60, 128);
+        let path = temp_path("book.mobi");
+        std::fs::write(path.as_path(), mobi_fixture(&[&pages[0], &thumbnail, b"FLIS\0\0\0\x08", &pages[1]])).unwrap();
+        let options = SourceOptions { mobi_min_image_side: 100, ..Default::default() };
+        let source = create_source(path.as_path(), None, &options);
+        let unfiltered = MobiSource::new(path.as_path(), 0).map(|x| x.page_count());
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        let mut source = source.unwrap();
+        assert_eq!(source.page_count(), 2);
+        assert_eq!(source.get_page_bytes(0).unwrap(), pages[0]);
+        assert_eq!(source.get_page_bytes(1).unwrap(), pages[1]);
+        assert_eq!(unfiltered.unwrap(), 3);
+    }
+}
@@ -1,21 +1,71 @@
-use hayro::{Pdf, RenderSettings, render};
+use hayro::{Pdf, Pixmap, RenderSettings, render};
+use hayro_interpret::hayro_syntax::content::ops::TypedInstruction;
 use hayro_interpret::hayro_syntax::object::{
+    Dict,
+    Name,
     Stream,
     Object,
-    dict::keys::{SUBTYPE, IMAGE, WIDTH, HEIGHT},
+    dict::keys::{SUBTYPE, IMAGE, WIDTH, HEIGHT, FILTER, DCT_DECODE, COLORSPACE, INDEXED, BITS_PER_COMPONENT, TYPE0},
 };
+use hayro_interpret::hayro_syntax::page::Page;
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage, imageops::FilterType};
 use sha2::Digest;
+use shared::config::{Config, TranscodeFormat};
 
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
 use std::sync::Arc;
 
-use super::{PageSource, FileBytes};
+use super::{PageSource, FileBytes, TranscodeOptions, encode_image};
 
-const DEFAULT_PAGE_HEIGHT: u32 = 1280;
+/// 超采样的倍数
+const SUPERSAMPLE: u32 = 2;
+
+/// pdf 的渲染质量设置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PdfRenderOptions {
+    /// 页面中没有图片时渲染的高度（像素）；有图片时按最高的图片渲染
+    pub page_height: u32,
+    /// hayro 自身总会做抗锯齿且不可调，开启后再以 `SUPERSAMPLE` 倍分辨率渲染后缩小，文字边缘更平滑，耗时约为四倍
+    pub antialias: bool,
+}
+
+impl Default for PdfRenderOptions {
+    fn default() -> Self {
+        Self {
+            page_height: 1280,
+            antialias: false,
+        }
+    }
+}
+
+impl From<&Config> for PdfRenderOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            page_height: config.pdf_page_height,
+            antialias: config.pdf_antialias,
+        }
+    }
+}
+
+impl PdfRenderOptions {
+    /// 把高为 `original_height` 的页面渲染到 `target_height` 像素高所用的设置，超采样时相应放大
+    pub fn settings(&self, target_height: u32, original_height: f32) -> RenderSettings {
+        let supersample = if self.antialias { SUPERSAMPLE } else { 1 };
+        let scale = target_height.saturating_mul(supersample) as f32 / original_height;
+        RenderSettings {
+            x_scale: scale,
+            y_scale: scale,
+            ..Default::default()
+        }
+    }
+}
 
 pub struct PdfSource {
     sha256: [u8; 32],
     pdf: Pdf,
+    transcode: TranscodeOptions,
+    render: PdfRenderOptions,
 }
     
 impl PageSource for PdfSource {
@@ -23,6 +73,8 @@ impl PageSource for PdfSource {
         let page = &self.pdf.pages()[index];
         let mut max_height = 0;
         let mut max_width = 0;
+        // 面积最大的内嵌图片，渲染失败时直接解码它
+        let mut largest: Option<(u64, Stream, u32, u32)> = None;
         for (_, mo) in page.resources().x_objects.entries() {
             if let Some(Some(Object::Stream(stream))) = mo.as_obj_ref().map(|o| self.pdf.xref().get::<Object>(o.into())) {
                 if let Some(Object::Name(sub_type)) = stream.dict().get::<Object>(SUBTYPE) {
@@ -32,31 +84,44 @@ impl PageSource for PdfSource {
                         let h = Self::get_u32_value(&stream, HEIGHT);
                         max_width = max_width.max(w);
                         max_height = max_height.max(h);
+                        let area = w as u64 * h as u64;
+                        if largest.as_ref().is_none_or(|x| x.0 < area) {
+                            largest = Some((area, stream, w, h));
+                        }
                     }
                 }
             }
         }
         if max_height == 0 {
-            max_height = DEFAULT_PAGE_HEIGHT;
-        }
-        let render_settings = {
-            let viewport = page.media_box();
-            let original_height = viewport.height() as f32;
-            let scale = max_height as f32 / original_height;
-            RenderSettings {
-                x_scale: scale,
-                y_scale: scale,
-                ..Default::default()
-            }
-        };
-        let pixmap = render(page, &Default::default(), &render_settings);
-        Ok(pixmap.take_png())        
+            max_height = self.render.page_height;
+        }
+        let render_settings = self.render.settings(max_height, page.media_box().height() as f32);
+        // hayro 遇到不支持的内容时可能直接 panic，当作渲染失败处理
+        let pixmap = std::panic::catch_unwind(AssertUnwindSafe(|| render(page, &Default::default(), &render_settings))).ok();
+        match (pixmap, largest) {
+            // 没有内嵌图片时空白页就是原样
+            (Some(pixmap), None) => self.encode_pixmap(pixmap),
+            (Some(pixmap), Some(_)) if !is_blank(&pixmap) => self.encode_pixmap(pixmap),
+            (_, Some((_, stream, w, h))) => match decode_embedded_image(&stream, w, h) {
+                Some(image) => {
+                    eprintln!("pdf 第 {} 页渲染失败，改用内嵌图片", index + 1);
+                    Ok(encode_image(&image, &self.transcode)?)
+                },
+                None => anyhow::bail!("pdf 第 {} 页渲染失败，内嵌图片也无法解码", index + 1),
+            },
+            (None, None) => anyhow::bail!("pdf 第 {} 页渲染失败", index + 1),
+        }
     }
 
     fn page_count(&self) -> usize {
         self.pdf.pages().len()
     }
 
+    fn page_text(&self, index: usize) -> Option<String> {
+        let text = page_text(self.pdf.pages().get(index)?);
+        (!text.trim().is_empty()).then_some(text)
+    }
+
     fn sha256(&self) -> &[u8; 32] {
         &self.sha256
     }
@@ -65,15 +130,54 @@ impl PageSource for PdfSource {
 impl PdfSource {
     pub fn new(file_path: impl AsRef<Path>) -> anyhow::Result<Self> {
         let file_content = std::fs::read(file_path.as_ref())?;
-        let sha256 = sha2::Sha256::digest(file_content.as_slice()).into();
-        let pdf = Pdf::new(Arc::new(file_content)).map_err(|_| anyhow::anyhow!("加载 pdf 文件失败"))?;
+        Self::from_bytes(file_content)
+    }
+
+    /// 从内存中的 pdf 文件内容构建，哈希基于文件内容计算
+    pub fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let sha256 = sha2::Sha256::digest(bytes.as_slice()).into();
+        let pdf = Pdf::new(Arc::new(bytes)).map_err(|_| anyhow::anyhow!("加载 pdf 文件失败"))?;
 
         Ok(Self {
             sha256,
             pdf,
+            transcode: Default::default(),
+            render: Default::default(),
         })
     }
 
+    pub fn with_transcode(mut self, transcode: TranscodeOptions) -> Self {
+        self.transcode = transcode;
+        self
+    }
+
+    pub fn with_render(mut self, render: PdfRenderOptions) -> Self {
+        self.render = render;
+        self
+    }
+
+    /// 不超采样时 PNG 直接沿用 hayro 的编码，保留透明背景；其余情况先铺上白底
+    fn encode_pixmap(&self, pixmap: Pixmap) -> anyhow::Result<FileBytes> {
+        if !self.render.antialias && self.transcode.format == TranscodeFormat::Png {
+            return Ok(pixmap.take_png());
+        }
+
+        let (width, height) = (pixmap.width() as u32, pixmap.height() as u32);
+        // 预乘透明度的 RGBA 铺在白底上
+        let rgb: Vec<u8> = pixmap.data_as_u8_slice()
+            .chunks_exact(4)
+            .flat_map(|p| {
+                let background = 255 - p[3];
+                [p[0].saturating_add(background), p[1].saturating_add(background), p[2].saturating_add(background)]
+            })
+            .collect();
+        let mut image = DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, rgb).unwrap());
+        if self.render.antialias {
+            image = image.resize_exact((width / SUPERSAMPLE).max(1), (height / SUPERSAMPLE).max(1), FilterType::Triangle);
+        }
+        Ok(encode_image(&image, &self.transcode)?)
+    }
+
     fn get_u32_value(stream: &Stream, key: &[u8]) -> u32 {
         if let Some(Object::Number(x)) = stream.dict().get::<Object>(key) {
             x.as_f32() as u32
@@ -91,3 +195,171 @@ impl PdfSource {
         }
     }
 }
+
+/// 尺寸为 0 或没有画上任何内容；hayro 渲染前会先铺上白底，整页只有底色也算空白
+fn is_blank(pixmap: &Pixmap) -> bool {
+    pixmap.width() == 0 || pixmap.height() == 0 || pixmap.data_as_u8_slice().chunks_exact(4).all(|p| p[3] == 0 || p == [255; 4])
+}
+
+/// 不经渲染直接解码内嵌图片：DCT 编码的数据本身就是 JPEG 文件，其余只支持每分量 8 位的灰度、RGB 像素
+fn decode_embedded_image(stream: &Stream, width: u32, height: u32) -> Option<DynamicImage> {
+    if let Some(Object::Name(filter)) = stream.dict().get::<Object>(FILTER) {
+        if filter.as_str().as_bytes() == DCT_DECODE {
+            return image::load_from_memory_with_format(stream.raw_data().as_ref(), ImageFormat::Jpeg).ok();
+        }
+    }
+    // 索引色的像素是调色板下标，不能当作灰度
+    if let Some(Object::Array(color_space)) = stream.dict().get::<Object>(COLORSPACE) {
+        if color_space.iter::<Name>().next().is_some_and(|x| x.as_str().as_bytes() == INDEXED) {
+            return None;
+        }
+    }
+    if !matches!(stream.dict().get::<Object>(BITS_PER_COMPONENT), Some(Object::Number(x)) if x.as_f32() == 8.) {
+        return None;
+    }
+
+    let data = stream.decoded().ok()?;
+    let pixels = width as usize * height as usize;
+    if data.len() == pixels * 3 {
+        RgbImage::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+    } else if data.len() == pixels {
+        GrayImage::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+    } else {
+        None
+    }
+}
+
+/// TJ 中的间距（千分之一字号）比该值更靠后时视为词间空格
+const TJ_SPACE: f32 = -200.;
+
+/// 从内容流中取出简单字体写出的文字，字节按 Latin-1 解码
+///
+/// Type0 等复合字体的字符码要借助 ToUnicode 映射才能还原，直接跳过
+fn page_text(page: &Page) -> String {
+    let mut text = String::new();
+    let mut simple_font = true;
+    let push = |text: &mut String, bytes: &[u8], simple_font: bool| {
+        if simple_font {
+            text.extend(bytes.iter().map(|&x| x as char).filter(|x| !x.is_control()));
+        }
+    };
+    let new_line = |text: &mut String| {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+    };
+    for instruction in page.typed_operations() {
+        match instruction {
+            TypedInstruction::TextFont(font) => {
+                simple_font = page.resources()
+                    .get_font(font.0, Box::new(|_| None), Box::new(|dict: Dict| Some(dict.get::<Name>(SUBTYPE).is_none_or(|x| x.as_str().as_bytes() != TYPE0))))
+                    .unwrap_or(true);
+            },
+            TypedInstruction::ShowText(x) => push(&mut text, &x.0.get(), simple_font),
+            TypedInstruction::NextLineAndShowText(x) => {
+                new_line(&mut text);
+                push(&mut text, &x.0.get(), simple_font);
+            },
+            TypedInstruction::ShowTextWithParameters(x) => {
+                new_line(&mut text);
+                push(&mut text, &x.2.get(), simple_font);
+            },
+            TypedInstruction::ShowTexts(x) => {
+                for item in x.0.iter::<Object>() {
+                    match item {
+                        Object::String(s) => push(&mut text, &s.get(), simple_font),
+                        Object::Number(n) if n.as_f32() < TJ_SPACE && simple_font && !text.ends_with([' ', '\n']) => text.push(' '),
+                        _ => {},
+                    }
+                }
+            },
+            TypedInstruction::NextLine(_)
+            | TypedInstruction::NextLineAndSetLeading(_)
+            | TypedInstruction::NextLineUsingLeading(_)
+            | TypedInstruction::SetTextMatrix(_)
+            | TypedInstruction::EndText(_) => new_line(&mut text),
+            _ => {},
+        }
+    }
+    text.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::tests::{pdf_fixture, temp_path};
+
+    #[test]
+    fn bytes_hash_matches_file() {
+        let content = pdf_fixture(&["", ""]);
+        let path = temp_path("from_bytes.pdf");
+        std::fs::write(path.as_path(), content.as_slice()).unwrap();
+        let from_file = PdfSource::new(path.as_path());
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        let from_file = from_file.unwrap();
+        let from_bytes = PdfSource::from_bytes(content).unwrap();
+        assert_eq!(from_bytes.sha256(), from_file.sha256());
+        assert_eq!(from_bytes.page_count(), 2);
+        assert!(PdfSource::from_bytes(b"not a pdf".to_vec()).is_err());
+    }
+
+    #[test]
+    fn text_layer_of_born_digital_page() {
+        let source = PdfSource::from_bytes(pdf_fixture(&["Chapter One", ""])).unwrap();
+        assert_eq!(source.page_text(0).as_deref(), Some("Chapter One"));
+        assert_eq!(source.page_text(1), None);
+        assert_eq!(source.page_text(2), None);
+    }
+
+    /// 一页的 pdf，内嵌一张 2×2 的 RGB 图片，内容流为 `content`
+    fn image_pdf_fixture(pixels: &[u8; 12], content: &str) -> FileBytes {
+        let mut image = b"<< /Type /XObject /Subtype /Image /Width 2 /Height 2 /ColorSpace /DeviceRGB /BitsPerComponent 8 /Length 12 >>\nstream\n".to_vec();
+        image.extend(pixels);
+        image.extend(b"\nendstream");
+        let objects = [
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+            b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 60 80] /Resources << /XObject << /Im1 5 0 R >> >> /Contents 4 0 R >>".to_vec(),
+            format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content).into_bytes(),
+            image,
+        ];
+
+        let mut bytes = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(bytes.len());
+            bytes.extend(format!("{} 0 obj\n", index + 1).into_bytes());
+            bytes.extend(object);
+            bytes.extend(b"\nendobj\n");
+        }
+        let xref = bytes.len();
+        bytes.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+        for offset in offsets {
+            bytes.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+        }
+        bytes.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).into_bytes());
+        bytes
+    }
+
+    #[test]
+    fn embedded_image_used_when_render_is_blank() {
+        let pixels = [200, 10, 10, 10, 200, 10, 10, 10, 200, 90, 90, 90];
+        // 内容流引用了不存在的图片，渲染出来什么都没有
+        let mut source = PdfSource::from_bytes(image_pdf_fixture(&pixels, "q 60 0 0 80 0 0 cm /Missing Do Q")).unwrap();
+        let page = image::load_from_memory(&source.get_page_bytes(0).unwrap()).unwrap().to_rgb8();
+        assert_eq!((page.width(), page.height()), (2, 2));
+        assert_eq!(page.into_raw(), pixels);
+    }
+
+    #[test]
+    fn antialias_reaches_render_settings() {
+        let plain = PdfRenderOptions::default().settings(1600, 800.);
+        let antialias = PdfRenderOptions { antialias: true, ..Default::default() }.settings(1600, 800.);
+        assert_eq!((plain.x_scale, plain.y_scale), (2., 2.));
+        assert_eq!((antialias.x_scale, antialias.y_scale), (2. * SUPERSAMPLE as f32, 2. * SUPERSAMPLE as f32));
+
+        let config = Config { pdf_antialias: true, pdf_page_height: 900, ..Default::default() };
+        assert_eq!(PdfRenderOptions::from(&config), PdfRenderOptions { page_height: 900, antialias: true });
+    }
+}
@@ -0,0 +1,249 @@
+use unrar::{Archive, error::{Code, UnrarError}};
+use tauri::async_runtime::Sender;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::fs::File;
+
+use super::{PageSource, FileBytes, SolidPage, check_valid_ext, cal_sha256, lower_thread_priority, page_name_cmp};
+use shared::{Capabilities, NeedPassword};
+
+/// rar / cbr 压缩包
+///
+/// unrar 只能从头顺序解压，非固实压缩包按页读取时跳过前面的条目，固实压缩包一次性解压全部页面
+pub struct RarSource {
+    sha256: [u8; 32],
+    file_path: PathBuf,
+    password: Option<String>,
+    file_names: Vec<PathBuf>,
+    solid: bool,
+    max_page_bytes: u64,
+    low_priority: bool,
+}
+
+impl PageSource for RarSource {
+    fn get_page_bytes(&mut self, index: usize) -> anyhow::Result<FileBytes> {
+        let file_name = self.file_names[index].as_path();
+        let mut archive = self.open().open_for_processing()?;
+        while let Some(header) = archive.read_header()? {
+            if header.entry().filename == file_name {
+                let declared = header.entry().unpacked_size;
+                if self.max_page_bytes > 0 && declared > self.max_page_bytes {
+                    anyhow::bail!("{} 解压后大小 {} 超过上限 {}", file_name.to_string_lossy(), declared, self.max_page_bytes);
+                }
+                return Ok(header.read()?.0);
+            }
+            archive = header.skip()?;
+        }
+
+        anyhow::bail!("压缩包中找不到 {}", file_name.to_string_lossy())
+    }
+
+    fn page_count(&self) -> usize {
+        self.file_names.len()
+    }
+
+    fn sha256(&self) -> &[u8; 32] {
+        &self.sha256
+    }
+
+    fn is_solid(&self) -> bool {
+        self.solid
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_password: true,
+            ..Capabilities::new(self.solid, false)
+        }
+    }
+
+    fn get_all_page_bytes(&mut self, tx: Sender<SolidPage>) -> bool {
+        let map: HashMap<PathBuf, usize> = self.file_names.iter().cloned().enumerate().map(|(a, b)| (b, a)).collect();
+        let file_path = self.file_path.clone();
+        let password = self.password.clone();
+        let max_page_bytes = self.max_page_bytes;
+        let low_priority = self.low_priority;
+        std::thread::spawn(move || {
+            if low_priority {
+                lower_thread_priority();
+            }
+            let send_all = || -> Result<(), UnrarError> {
+                let mut archive = open_archive(file_path.as_path(), password.as_deref()).open_for_processing()?;
+                while let Some(header) = archive.read_header()? {
+                    let entry = header.entry();
+                    archive = match map.get(&entry.filename) {
+                        Some(&index) if max_page_bytes == 0 || entry.unpacked_size <= max_page_bytes => {
+                            let (buffer, archive) = header.read()?;
+                            if let Err(e) = tx.blocking_send((index, Ok(buffer))) {
+                                eprintln!("管道发送出错：{}", e);
+                            }
+                            archive
+                        },
+                        Some(&index) => {
+                            let e = anyhow::anyhow!("{} 解压后大小 {} 超过上限 {}", entry.filename.to_string_lossy(), entry.unpacked_size, max_page_bytes);
+                            if let Err(e) = tx.blocking_send((index, Err(e))) {
+                                eprintln!("管道发送出错：{}", e);
+                            }
+                            header.skip()?
+                        },
+                        None => header.skip()?,
+                    };
+                    if tx.is_closed() {
+                        break;
+                    }
+                }
+                Ok(())
+            };
+            if let Err(e) = send_all() {
+                eprintln!("解压 rar 失败：{}", e);
+            }
+        });
+
+        true
+    }
+}
+
+impl RarSource {
+    pub fn new(file_path: impl AsRef<Path>, password: Option<String>, case_insensitive_sort: bool) -> anyhow::Result<Self> {
+        let file_path = file_path.as_ref().to_path_buf();
+        let sha256 = cal_sha256(&mut File::open(file_path.as_path())?)?;
+
+        let listing = open_archive(file_path.as_path(), password.as_deref()).open_for_listing().map_err(password_error)?;
+        let solid = listing.is_solid();
+        let mut encrypted = false;
+        let mut file_names = Vec::new();
+        for entry in listing {
+            let entry = entry.map_err(password_error)?;
+            encrypted |= entry.is_encrypted();
+            if entry.is_file() && check_valid_ext(entry.filename.as_path()) {
+                file_names.push(entry.filename);
+            }
+        }
+        file_names.sort_by(|a, b| page_name_cmp(a.to_string_lossy().as_ref(), b.to_string_lossy().as_ref(), case_insensitive_sort));
+
+        if encrypted {
+            match password.as_deref() {
+                Some(password) => Self::check_password(file_path.as_path(), password)?,
+                None => anyhow::bail!(NeedPassword),
+            }
+        } else {
+            eprintln!("没有密码");
+        }
+
+        Ok(Self {
+            sha256,
+            file_path,
+            password,
+            file_names,
+            solid,
+            max_page_bytes: 0,
+            low_priority: false,
+        })
+    }
+
+    pub fn with_max_page_bytes(mut self, max_page_bytes: u64) -> Self {
+        self.max_page_bytes = max_page_bytes;
+        self
+    }
+
+    pub fn with_low_priority(mut self, low_priority: bool) -> Self {
+        self.low_priority = low_priority;
+        self
+    }
+
+    fn open(&self) -> Archive<'_> {
+        open_archive(self.file_path.as_path(), self.password.as_deref())
+    }
+
+    /// 试解第一个加密条目，rar4 密码错误时只会报数据校验失败，同样视为密码错误
+    fn check_password(file_path: &Path, password: &str) -> anyhow::Result<()> {
+        let mut archive = open_archive(file_path, Some(password)).open_for_processing().map_err(password_error)?;
+        while let Some(header) = archive.read_header().map_err(password_error)? {
+            if header.entry().is_encrypted() {
+                return match header.test() {
+                    Ok(_) => Ok(()),
+                    Err(e) if e.code == Code::BadData => anyhow::bail!(NeedPassword),
+                    Err(e) => Err(password_error(e)),
+                };
+            }
+            archive = header.skip()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn open_archive<'a>(file_path: &'a Path, password: Option<&'a str>) -> Archive<'a> {
+    match password {
+        Some(password) => Archive::with_password(file_path, password),
+        None => Archive::new(file_path),
+    }
+}
+
+fn password_error(e: UnrarError) -> anyhow::Error {
+    match e.code {
+        Code::MissingPassword | Code::BadPassword => anyhow::anyhow!(NeedPassword),
+        _ => anyhow::anyhow!(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{SourceOptions, create_source};
+    use crate::source::tests::{png_fixture, temp_path};
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        !bytes.iter().fold(!0u32, |crc, &byte| {
+            (0..8).fold(crc ^ byte as u32, |crc, _| if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 })
+        })
+    }
+
+    /// 块头的校验值为除校验值本身以外各字节 CRC32 的低 16 位
+    fn block(body: &[u8]) -> Vec<u8> {
+        let mut block = (crc32(body) as u16).to_le_bytes().to_vec();
+        block.extend(body);
+        block
+    }
+
+    /// 只存储不压缩的 rar4 压缩包
+    fn rar_fixture(entries: &[(&str, &[u8])]) -> FileBytes {
+        let mut content = b"Rar!\x1a\x07\x00".to_vec();
+        content.extend(block(&[0x73, 0, 0, 13, 0, 0, 0, 0, 0, 0, 0]));
+        for (name, data) in entries {
+            let mut header = vec![0x74];
+            header.extend(0x8000u16.to_le_bytes());
+            header.extend((32 + name.len() as u16).to_le_bytes());
+            header.extend((data.len() as u32).to_le_bytes());
+            header.extend((data.len() as u32).to_le_bytes());
+            // Windows 主机，1980-01-01，rar 2.9 存储方式
+            header.push(2);
+            header.extend(crc32(data).to_le_bytes());
+            header.extend(0x0021_0000u32.to_le_bytes());
+            header.extend([29, 0x30]);
+            header.extend((name.len() as u16).to_le_bytes());
+            header.extend(0x20u32.to_le_bytes());
+            header.extend(name.as_bytes());
+            content.extend(block(&header));
+            content.extend(*data);
+        }
+        content.extend(block(&[0x7b, 0, 0x40, 7, 0]));
+        content
+    }
+
+    #[test]
+    fn small_cbr_opens_in_natural_order() {
+        let pages: Vec<FileBytes> = (0..3).map(|x| png_fixture(4, 6, x * 100)).collect();
+        let path = temp_path("book.cbr");
+        std::fs::write(path.as_path(), rar_fixture(&[("p10.png", &pages[2]), ("info.txt", b"text"), ("p2.png", &pages[1]), ("p1.png", &pages[0])])).unwrap();
+        let source = create_source(path.as_path(), None, &SourceOptions::default());
+        let mut source = source.unwrap();
+        let read: Vec<FileBytes> = (0..source.page_count()).map(|x| source.get_page_bytes(x).unwrap()).collect();
+        let solid = source.is_solid();
+        drop(source);
+        std::fs::remove_file(path.as_path()).unwrap();
+        assert_eq!(read, pages);
+        assert!(!solid);
+    }
+}
@@ -5,19 +5,27 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::fs::File;
 
-use super::{PageSource, FileBytes, check_valid_ext, cal_sha256};
-use shared::NeedPassword;
+use super::{PageSource, FileBytes, ReadSeek, SolidPage, check_valid_ext, cal_sha256, lower_thread_priority, page_name_cmp, read_capped};
+use shared::{Capabilities, NeedPassword};
 
 pub struct SevenzSource {
     sha256: [u8; 32],
-    sevenz_archive: Option<ArchiveReader<File>>,
+    sevenz_archive: Option<ArchiveReader<Box<dyn ReadSeek>>>,
     file_names: Vec<String>,
+    max_page_bytes: u64,
+    low_priority: bool,
 }
     
 impl PageSource for SevenzSource {
     fn get_page_bytes(&mut self, index: usize) -> anyhow::Result<FileBytes> {
         let file_name = self.file_names[index].as_str();
-        Ok(self.sevenz_archive.as_mut().unwrap().read_file(file_name)?)
+        let sevenz_archive = self.sevenz_archive.as_mut().unwrap();
+        // read_file 会整个读入内存，先按声明的大小拦一次
+        let declared = sevenz_archive.archive().files.iter().find(|x| x.name() == file_name).map_or(0, |x| x.size());
+        if self.max_page_bytes > 0 && declared > self.max_page_bytes {
+            anyhow::bail!("{} 解压后大小 {} 超过上限 {}", file_name, declared, self.max_page_bytes);
+        }
+        Ok(sevenz_archive.read_file(file_name)?)
     }
 
     fn page_count(&self) -> usize {
@@ -32,18 +40,29 @@ impl PageSource for SevenzSource {
         self.sevenz_archive.as_ref().unwrap().archive().is_solid
     }
 
-    fn get_all_page_bytes(&mut self, tx: Sender<(usize, FileBytes)>) -> bool {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_password: true,
+            ..Capabilities::new(self.is_solid(), false)
+        }
+    }
+
+    fn get_all_page_bytes(&mut self, tx: Sender<SolidPage>) -> bool {
         let map: HashMap<String, usize> = std::mem::take(&mut self.file_names).into_iter().enumerate().map(|(a, b)| (b, a)).collect();
         let mut sevenz_archive = self.sevenz_archive.take().unwrap();
+        let max_page_bytes = self.max_page_bytes;
+        let low_priority = self.low_priority;
         std::thread::spawn(move || {
+            if low_priority {
+                lower_thread_priority();
+            }
             sevenz_archive.for_each_entries(|entry, reader| {
                 // std::thread::sleep(std::time::Duration::from_millis(1000));
                 if let Some(&index) = map.get(entry.name()) {
-                    let mut buffer = Vec::new();
-                    if reader.read_to_end(&mut buffer).is_ok() {
-                        if let Err(e) = tx.blocking_send((index, buffer)) {
-                            eprintln!("管道发送出错：{}", e);
-                        }
+                    let content = read_capped(reader, entry.size(), max_page_bytes)
+                        .map_err(|e| anyhow::anyhow!("读取 {} 失败：{}", entry.name(), e));
+                    if let Err(e) = tx.blocking_send((index, content)) {
+                        eprintln!("管道发送出错：{}", e);
                     }
                 }
                 Ok(!tx.is_closed())
@@ -55,29 +74,46 @@ impl PageSource for SevenzSource {
 }
 
 impl SevenzSource {
-    pub fn new(file_path: impl AsRef<Path>, password: Option<String>) -> anyhow::Result<Self> {
-        let mut file = File::open(file_path.as_ref())?;
-        let sha256: [u8; 32] = cal_sha256(&mut file)?;
+    pub fn new(file_path: impl AsRef<Path>, password: Option<String>, case_insensitive_sort: bool) -> anyhow::Result<Self> {
+        let file = File::open(file_path.as_ref())?;
+        Self::from_reader(file, password, case_insensitive_sort)
+    }
+
+    /// 从任意可随机读取的数据构建，例如压缩包中内嵌的 7z
+    pub fn from_reader(mut reader: impl ReadSeek + 'static, password: Option<String>, case_insensitive_sort: bool) -> anyhow::Result<Self> {
+        let sha256: [u8; 32] = cal_sha256(&mut reader)?;
 
-        let sevenz_archive = match Self::check_password(file, password) {
+        let sevenz_archive = match Self::check_password(Box::new(reader), password) {
             Ok(x) => x,
             Err(SevenzError::MaybeBadPassword(_)) | Err(SevenzError::PasswordRequired) => anyhow::bail!(NeedPassword),
             Err(e) => anyhow::bail!(e),
         };
 
-        let file_names = Self::generate_toc(&sevenz_archive);
+        let file_names = Self::generate_toc(&sevenz_archive, case_insensitive_sort);
         let sevenz_archive = Some(sevenz_archive);
 
         Ok(Self {
             sha256,
             sevenz_archive,
             file_names,
+            max_page_bytes: 0,
+            low_priority: false,
         })
     }
 
-    fn check_password(file: File, password: Option<String>) -> Result<ArchiveReader<File>, SevenzError> {
+    pub fn with_max_page_bytes(mut self, max_page_bytes: u64) -> Self {
+        self.max_page_bytes = max_page_bytes;
+        self
+    }
+
+    pub fn with_low_priority(mut self, low_priority: bool) -> Self {
+        self.low_priority = low_priority;
+        self
+    }
+
+    fn check_password(reader: Box<dyn ReadSeek>, password: Option<String>) -> Result<ArchiveReader<Box<dyn ReadSeek>>, SevenzError> {
         let pwd = password.map(|x| x.as_str().into()).unwrap_or_default();
-        let mut sevenz_archive = ArchiveReader::new(file, pwd)?;
+        let mut sevenz_archive = ArchiveReader::new(reader, pwd)?;
         sevenz_archive.for_each_entries(|_, reader| {
             let mut buffer = [0; 1 << 14];
             _ = reader.read(&mut buffer)?;
@@ -85,11 +121,50 @@ impl SevenzSource {
         }).map(|()| sevenz_archive)
     }
 
-    fn generate_toc(sevenz_archive: &ArchiveReader<File>) -> Vec<String> {
+    fn generate_toc(sevenz_archive: &ArchiveReader<Box<dyn ReadSeek>>, case_insensitive_sort: bool) -> Vec<String> {
         let mut v: Vec<String> = sevenz_archive.archive().files.iter().filter_map(|entry| {
             (!entry.is_directory() && check_valid_ext(entry.name())).then_some(entry.name().to_string())
         }).collect();
-        v.sort_unstable();
+        v.sort_unstable_by(|a, b| page_name_cmp(a, b, case_insensitive_sort));
         v
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::tests::png_fixture;
+    use sevenz_rust2::{ArchiveEntry, ArchiveWriter, SourceReader};
+    use std::io::Cursor;
+
+    /// 把（文件名，内容）写成 7z，`solid` 为真时所有文件压在同一块中
+    fn sevenz_fixture(entries: &[(&str, &[u8])], solid: bool) -> FileBytes {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        if solid {
+            let names = entries.iter().map(|x| ArchiveEntry::new_file(x.0)).collect();
+            let readers = entries.iter().map(|x| SourceReader::new(x.1)).collect();
+            writer.push_archive_entries(names, readers).unwrap();
+        } else {
+            for (name, content) in entries {
+                writer.push_archive_entry(ArchiveEntry::new_file(name), Some(*content)).unwrap();
+            }
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn solid_archive_is_not_random_access() {
+        let pages = [png_fixture(4, 4, 0), png_fixture(4, 4, 255)];
+        let entries = [("01.png", pages[0].as_slice()), ("02.png", pages[1].as_slice())];
+
+        let solid = SevenzSource::from_reader(Cursor::new(sevenz_fixture(&entries, true)), None, false).unwrap();
+        let capabilities = solid.capabilities();
+        assert!(capabilities.solid);
+        assert!(!capabilities.random_access);
+        assert!(capabilities.supports_password);
+
+        let mut separate = SevenzSource::from_reader(Cursor::new(sevenz_fixture(&entries, false)), None, false).unwrap();
+        assert!(separate.capabilities().random_access);
+        assert_eq!(separate.get_page_bytes(1).unwrap(), pages[1]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// 按顺序首尾相接的分卷文件，读起来与合并后的单个文件相同
+pub struct SplitReader {
+    parts: Vec<File>,
+    /// 各分卷在合并后的起始位置，末尾另有总长度
+    offsets: Vec<u64>,
+    pos: u64,
+}
+
+impl SplitReader {
+    pub fn open(paths: &[PathBuf]) -> io::Result<Self> {
+        let mut parts = Vec::with_capacity(paths.len());
+        let mut offsets = vec![0];
+        for path in paths {
+            let file = File::open(path)?;
+            offsets.push(offsets.last().unwrap() + file.metadata()?.len());
+            parts.push(file);
+        }
+        Ok(Self { parts, offsets, pos: 0 })
+    }
+
+    fn len(&self) -> u64 {
+        *self.offsets.last().unwrap()
+    }
+}
+
+impl Read for SplitReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len() {
+            return Ok(0);
+        }
+        // 每次只读当前所在的分卷，跨卷的部分由调用方下一次读取
+        let index = self.offsets.partition_point(|&offset| offset <= self.pos) - 1;
+        let part = &mut self.parts[index];
+        part.seek(SeekFrom::Start(self.pos - self.offsets[index]))?;
+        let n = part.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for SplitReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.len().checked_add_signed(x),
+            SeekFrom::Current(x) => self.pos.checked_add_signed(x),
+        };
+        self.pos = target.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "定位到了文件开头之前"))?;
+        Ok(self.pos)
+    }
+}
+
+/// `name.zip.001` 这类按字节切分的分卷：返回去掉序号后的文件名，以及序号的位数
+fn split_base(file_name: &str) -> Option<(&str, usize)> {
+    let (base, number) = file_name.rsplit_once('.')?;
+    (number.len() >= 3 && number.bytes().all(|x| x.is_ascii_digit())).then_some((base, number.len()))
+}
+
+/// 打开分卷中的任意一卷时，从第一卷起收集连续编号的全部分卷；不是分卷命名时返回 `None`
+pub fn split_parts(path: &Path) -> Option<Vec<PathBuf>> {
+    let file_name = path.file_name()?.to_str()?;
+    let (base, width) = split_base(file_name)?;
+    let parts: Vec<PathBuf> = (1..)
+        .map(|x| path.with_file_name(format!("{}.{:0width$}", base, x)))
+        .take_while(|x| x.is_file())
+        .collect();
+    (!parts.is_empty()).then_some(parts)
+}
+
+/// 分卷合并后的文件名，用于判断格式，如 `name.zip.001` 得到 `name.zip`
+pub fn split_joined_name(path: &Path) -> Option<&str> {
+    split_base(path.file_name()?.to_str()?).map(|(base, _)| base)
+}
+
+/// `name.z01`、`name.z02`……加 `name.zip` 的分卷 zip，各卷内的偏移量从该卷开头算起，无法简单拼接
+pub fn is_spanned_zip(path: &Path) -> bool {
+    let Some(ext) = path.extension().and_then(|x| x.to_str()) else {
+        return false;
+    };
+    let ext = ext.to_ascii_lowercase();
+    if ext == "zip" {
+        path.with_extension("z01").is_file()
+    } else {
+        ext.len() == 3 && ext.starts_with('z') && ext[1..].bytes().all(|x| x.is_ascii_digit())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{SourceOptions, create_source};
+    use crate::source::tests::{png_fixture, temp_path, zip_fixture};
+
+    #[test]
+    fn split_zip_reads_like_unsplit() {
+        let pages = [png_fixture(6, 9, 0), png_fixture(6, 9, 120), png_fixture(6, 9, 240)];
+        let archive = zip_fixture(&[("01.png", &pages[0]), ("02.png", &pages[1]), ("03.png", &pages[2])]);
+        let dir = temp_path("split_zip");
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        let (first, second) = archive.split_at(archive.len() / 2);
+        std::fs::write(dir.join("book.zip.001"), first).unwrap();
+        std::fs::write(dir.join("book.zip.002"), second).unwrap();
+        std::fs::write(dir.join("whole.zip"), archive.as_slice()).unwrap();
+
+        let read_all = |path: PathBuf| {
+            let mut source = create_source(path.as_path(), None, &SourceOptions::default()).unwrap();
+            (0..source.page_count()).map(|x| source.get_page_bytes(x).unwrap()).collect::<Vec<_>>()
+        };
+        let whole = read_all(dir.join("whole.zip"));
+        let split = read_all(dir.join("book.zip.001"));
+        // 从后面的分卷打开也从第一卷读起
+        let from_second = read_all(dir.join("book.zip.002"));
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+
+        assert_eq!(whole, pages);
+        assert_eq!(split, whole);
+        assert_eq!(from_second, whole);
+    }
+}
@@ -7,7 +7,7 @@ use bzip2::read::BzDecoder;
 use std::{io::Read, path::Path};
 use std::fs::File;
 
-use super::{PageSource, FileBytes, check_valid_ext, cal_sha256};
+use super::{PageSource, FileBytes, check_valid_ext, cal_sha256, page_name_cmp, read_capped};
 use shared::{EXT_TAR, EXT_XZ, EXT_GZ, EXT_BZ2};
 
 pub struct TarSource {
@@ -36,40 +36,43 @@ impl PageSource for TarSource {
 }
 
 impl TarSource {
-    pub fn new(file_path: impl AsRef<Path>, ext: &str) -> anyhow::Result<Self> {
+    pub fn new(file_path: impl AsRef<Path>, ext: &str, max_page_bytes: u64, case_insensitive_sort: bool) -> anyhow::Result<Self> {
         let mut file = File::open(file_path.as_ref())?;
         let sha256 = cal_sha256(&mut file)?;
         
         match ext {
-            EXT_TAR => Self::from(sha256, file),
-            EXT_XZ => Self::from(sha256, XzDecoder::new(file)),
-            EXT_GZ => Self::from(sha256, GzDecoder::new(file)),
-            EXT_BZ2 => Self::from(sha256, BzDecoder::new(file)),
+            EXT_TAR => Self::from(sha256, file, max_page_bytes, case_insensitive_sort),
+            EXT_XZ => Self::from(sha256, XzDecoder::new(file), max_page_bytes, case_insensitive_sort),
+            EXT_GZ => Self::from(sha256, GzDecoder::new(file), max_page_bytes, case_insensitive_sort),
+            EXT_BZ2 => Self::from(sha256, BzDecoder::new(file), max_page_bytes, case_insensitive_sort),
             _ => unreachable!(),
         }
         
     }
 
-    pub fn from<R: Read>(sha256: [u8; 32], r: R) -> anyhow::Result<Self> {
+    /// 超过 `max_page_bytes` 的条目跳过，0 表示不限制
+    pub fn from<R: Read>(sha256: [u8; 32], r: R, max_page_bytes: u64, case_insensitive_sort: bool) -> anyhow::Result<Self> {
         let mut archive = Archive::new(r);
 
         let mut images_with_path: Vec<(_, FileBytes)> = archive.entries()?
             .flatten()
-            .filter_map(|mut entry| 
-                (entry.header()
-                    .entry_type()
-                    .is_file()
-                &&
-                entry.path().is_ok_and(check_valid_ext))
-                .then(|| (entry.path().unwrap().to_path_buf(), {
-                    let mut buffer = Vec::with_capacity(entry.header().size().unwrap() as usize);
-                    entry.read_to_end(&mut buffer).unwrap();
-                    buffer
-                }))
-            )
+            .filter_map(|entry| {
+                if !(entry.header().entry_type().is_file() && entry.path().is_ok_and(check_valid_ext)) {
+                    return None;
+                }
+                let path = entry.path().unwrap().to_path_buf();
+                let declared = entry.header().size().unwrap_or_default();
+                match read_capped(entry, declared, max_page_bytes) {
+                    Ok(buffer) => Some((path, buffer)),
+                    Err(e) => {
+                        eprintln!("读取 {} 失败：{}", path.to_string_lossy(), e);
+                        None
+                    },
+                }
+            })
             .collect();
 
-        images_with_path.sort_by_cached_key(|entry| entry.0.clone());
+        images_with_path.sort_by(|a, b| page_name_cmp(a.0.to_string_lossy().as_ref(), b.0.to_string_lossy().as_ref(), case_insensitive_sort));
         
         let images: Vec<FileBytes> = images_with_path.into_iter()
             .map(|(_, file_bytes)| file_bytes)
@@ -81,3 +84,46 @@ impl TarSource {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::source::{SourceOptions, create_source};
+    use crate::source::tests::{png_fixture, tar_fixture, temp_path};
+
+    #[test]
+    fn cbt_opens_as_tar() {
+        let pages = [png_fixture(4, 4, 0), png_fixture(4, 4, 255)];
+        let path = temp_path("book.cbt");
+        std::fs::write(path.as_path(), tar_fixture(&[("2.png", &pages[1]), ("1.png", &pages[0])])).unwrap();
+        let source = create_source(path.as_path(), None, &SourceOptions::default());
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        let mut source = source.unwrap();
+        assert_eq!(source.page_count(), 2);
+        assert_eq!(source.get_page_bytes(0).unwrap(), pages[0]);
+        assert_eq!(source.get_page_bytes(1).unwrap(), pages[1]);
+    }
+
+    #[test]
+    fn tar_gz_of_jpegs_opens() {
+        use std::io::Write;
+
+        let pages: Vec<Vec<u8>> = (0..3).map(|x| {
+            let mut buffer = std::io::Cursor::new(Vec::new());
+            image::GrayImage::from_pixel(8, 8, image::Luma([x * 100])).write_to(&mut buffer, image::ImageFormat::Jpeg).unwrap();
+            buffer.into_inner()
+        }).collect();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&tar_fixture(&[("p10.jpg", &pages[2]), ("p2.jpg", &pages[1]), ("p1.jpg", &pages[0])])).unwrap();
+        let path = temp_path("book.tar.gz");
+        std::fs::write(path.as_path(), encoder.finish().unwrap()).unwrap();
+        let source = create_source(path.as_path(), None, &SourceOptions::default());
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        let mut source = source.unwrap();
+        assert_eq!(source.page_count(), 3);
+        for (index, page) in pages.iter().enumerate() {
+            assert_eq!(&source.get_page_bytes(index).unwrap(), page);
+        }
+    }
+}
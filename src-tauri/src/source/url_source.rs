@@ -0,0 +1,258 @@
+use reqwest::Url;
+use reqwest::blocking::Client;
+use reqwest::header::CONTENT_TYPE;
+use scraper::{Html, Selector};
+use tauri::async_runtime::Sender;
+
+use reqwest::blocking::Response;
+
+use std::fs::File;
+use std::io::{self, Cursor};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::{PageSource, FileBytes, SolidPage, SourceOptions, check_valid_ext, cal_sha256, create_source, page_name_cmp, read_capped};
+
+/// 单次请求的超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub fn is_url(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// 网页上的图片列表：目录索引页，或每行一个图片网址的 `.txt` 清单
+///
+/// 网络上无法像本地文件一样随机读取，按固实压缩包的方式从头逐张下载，下载完一张发送一张
+pub struct UrlListSource {
+    sha256: [u8; 32],
+    urls: Vec<Url>,
+    client: Client,
+    max_page_bytes: u64,
+}
+
+impl PageSource for UrlListSource {
+    fn get_page_bytes(&mut self, index: usize) -> anyhow::Result<FileBytes> {
+        download(&self.client, &self.urls[index], self.max_page_bytes)
+    }
+
+    fn page_count(&self) -> usize {
+        self.urls.len()
+    }
+
+    fn sha256(&self) -> &[u8; 32] {
+        &self.sha256
+    }
+
+    fn is_solid(&self) -> bool {
+        true
+    }
+
+    fn page_name(&self, index: usize) -> Option<String> {
+        self.urls.get(index).map(file_name)
+    }
+
+    fn get_all_page_bytes(&mut self, tx: Sender<SolidPage>) -> bool {
+        let urls = self.urls.clone();
+        let client = self.client.clone();
+        let max_page_bytes = self.max_page_bytes;
+        std::thread::spawn(move || {
+            for (index, url) in urls.iter().enumerate() {
+                if let Err(e) = tx.blocking_send((index, download(&client, url, max_page_bytes))) {
+                    eprintln!("管道发送出错：{}", e);
+                }
+                if tx.is_closed() {
+                    break;
+                }
+            }
+        });
+
+        true
+    }
+}
+
+/// 下载 `url` 指向的内容：压缩包、pdf 等保存到临时目录后交给对应的源读取，网页与文本按图片列表处理
+///
+/// 下载的文件以哈希命名，重复打开同一个网址不会另存一份；关闭程序时由 [`clear_downloads`] 清理
+pub fn create_url_source(url: &str, password: Option<String>, options: &SourceOptions) -> anyhow::Result<Box<dyn PageSource>> {
+    let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+    let url = Url::parse(url)?;
+    let response = fetch(&client, &url)?;
+    // 以重定向后的地址为准，相对链接据此解析
+    let url = response.url().clone();
+    let content_type = response.headers().get(CONTENT_TYPE).and_then(|x| x.to_str().ok()).unwrap_or_default().to_ascii_lowercase();
+    let name = file_name(&url);
+
+    let is_html = content_type.starts_with("text/html");
+    if is_html || content_type.starts_with("text/plain") || name.to_ascii_lowercase().ends_with(".txt") {
+        let bytes = read_response(response, &url, options.max_page_bytes)?;
+        let text = String::from_utf8_lossy(bytes.as_ref());
+        let urls = if is_html {
+            listing_urls(&url, text.as_ref(), options.case_insensitive_sort)
+        } else {
+            manifest_urls(&url, text.as_ref())
+        };
+        if urls.is_empty() {
+            anyhow::bail!("{} 中没有找到图片", url);
+        }
+        let sha256 = cal_sha256(Cursor::new(bytes.as_slice()))?;
+        return Ok(Box::new(UrlListSource { sha256, urls, client, max_page_bytes: options.max_page_bytes }));
+    }
+
+    let ext = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase())
+        .or_else(|| ext_for_mime(content_type.as_str()).map(String::from))
+        .ok_or_else(|| anyhow::anyhow!("无法判断 {} 的格式", url))?;
+    let path = save_response(response, &url, ext.as_str())?;
+    create_source(path.as_path(), password, options)
+}
+
+fn download_dir() -> PathBuf {
+    std::env::temp_dir().join("local-manga-reader-downloads")
+}
+
+/// 删除下载的压缩包等文件，关闭程序时调用
+pub fn clear_downloads() {
+    let dir = download_dir();
+    if dir.is_dir() {
+        if let Err(e) = std::fs::remove_dir_all(dir.as_path()) {
+            eprintln!("清理下载目录失败：{}", e);
+        }
+    }
+}
+
+/// 边下载边写入临时文件，完成后按内容的哈希改名，已有同名文件时直接沿用
+fn save_response(mut response: Response, url: &Url, ext: &str) -> anyhow::Result<PathBuf> {
+    static PART_COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = download_dir();
+    std::fs::create_dir_all(dir.as_path())?;
+    let part_path = dir.join(format!("{}-{}.part", std::process::id(), PART_COUNT.fetch_add(1, Ordering::Relaxed)));
+    let saved = File::create(part_path.as_path()).and_then(|mut file| io::copy(&mut response, &mut file))
+        .and_then(|_| cal_sha256(File::open(part_path.as_path())?));
+    let sha256 = match saved {
+        Ok(x) => x,
+        Err(e) => {
+            _ = std::fs::remove_file(part_path.as_path());
+            anyhow::bail!("下载 {} 失败：{}", url, e);
+        },
+    };
+
+    let hex: String = sha256.iter().map(|b| format!("{:02x}", b)).collect();
+    let path = dir.join(format!("{}.{}", hex, ext));
+    if path.is_file() {
+        std::fs::remove_file(part_path.as_path())?;
+    } else {
+        std::fs::rename(part_path, path.as_path())?;
+    }
+    Ok(path)
+}
+
+fn fetch(client: &Client, url: &Url) -> anyhow::Result<Response> {
+    client.get(url.clone()).send().and_then(|x| x.error_for_status())
+        .map_err(|e| anyhow::anyhow!("下载 {} 失败：{}", url, e))
+}
+
+/// 读取响应的内容，超过 `max` 字节时放弃，0 表示不限制
+fn read_response(response: Response, url: &Url, max: u64) -> anyhow::Result<FileBytes> {
+    let declared = response.content_length().unwrap_or(0);
+    read_capped(response, declared, max).map_err(|e| anyhow::anyhow!("下载 {} 失败：{}", url, e))
+}
+
+fn download(client: &Client, url: &Url, max: u64) -> anyhow::Result<FileBytes> {
+    read_response(fetch(client, url)?, url, max)
+}
+
+/// 网址最后一段路径，已解码
+fn file_name(url: &Url) -> String {
+    let name = url.path_segments().and_then(|mut x| x.next_back()).unwrap_or_default();
+    urlencoding::decode(name).map(|x| x.into_owned()).unwrap_or_else(|_| name.to_string())
+}
+
+/// 目录索引页中链接到图片的网址，按文件名排序
+fn listing_urls(base: &Url, html: &str, case_insensitive_sort: bool) -> Vec<Url> {
+    let selector = Selector::parse("a[href]").unwrap();
+    let mut urls: Vec<Url> = Html::parse_document(html).select(&selector)
+        .filter_map(|x| x.value().attr("href"))
+        .filter_map(|href| base.join(href).ok())
+        .filter(|url| check_valid_ext(file_name(url)))
+        .collect();
+    urls.sort_by(|a, b| page_name_cmp(file_name(a).as_str(), file_name(b).as_str(), case_insensitive_sort).then_with(|| a.as_str().cmp(b.as_str())));
+    urls.dedup();
+    urls
+}
+
+/// 清单中每行一个网址，可以是相对地址；空行与 `#` 开头的行忽略，顺序保持不变
+fn manifest_urls(base: &Url, text: &str) -> Vec<Url> {
+    text.lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .filter_map(|x| base.join(x).ok())
+        .filter(|x| matches!(x.scheme(), "http" | "https"))
+        .collect()
+}
+
+/// 网址没有后缀时由内容类型推断
+fn ext_for_mime(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    Some(match mime {
+        "application/zip" | "application/x-zip-compressed" => "zip",
+        "application/vnd.comicbook+zip" | "application/x-cbz" => "cbz",
+        "application/vnd.rar" | "application/x-rar-compressed" | "application/vnd.comicbook-rar" => "rar",
+        "application/x-7z-compressed" => "7z",
+        "application/x-tar" => "tar",
+        "application/pdf" => "pdf",
+        "application/epub+zip" => "epub",
+        "application/x-mobipocket-ebook" => "mobi",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_keeps_order_and_resolves_relative_urls() {
+        let base = Url::parse("https://example.com/book/list.txt").unwrap();
+        let text = "# 封面\n002.jpg\n\n  https://cdn.example.com/001.png  \nftp://example.com/x.jpg\n../extra/003.webp\n";
+        let urls: Vec<String> = manifest_urls(&base, text).iter().map(Url::to_string).collect();
+        assert_eq!(urls, [
+            "https://example.com/book/002.jpg",
+            "https://cdn.example.com/001.png",
+            "https://example.com/extra/003.webp",
+        ]);
+    }
+
+    #[test]
+    fn mime_maps_to_extension() {
+        assert_eq!(ext_for_mime("application/zip"), Some("zip"));
+        assert_eq!(ext_for_mime("application/vnd.comicbook+zip; charset=binary"), Some("cbz"));
+        assert_eq!(ext_for_mime("application/pdf"), Some("pdf"));
+        assert_eq!(ext_for_mime("application/octet-stream"), None);
+    }
+
+    #[test]
+    fn listing_keeps_image_links_in_natural_order() {
+        let base = Url::parse("https://example.com/book/").unwrap();
+        let html = r#"<a href="../">上级目录</a><a href="10.jpg">10.jpg</a><a href="notes.txt">notes</a>
+            <a href="2.png">2.png</a><a href="/book/10.jpg">重复</a><a href="%E5%B0%81%E9%9D%A2.webp">封面</a>"#;
+        let urls: Vec<String> = listing_urls(&base, html, false).iter().map(Url::to_string).collect();
+        assert_eq!(urls, [
+            "https://example.com/book/2.png",
+            "https://example.com/book/10.jpg",
+            "https://example.com/book/%E5%B0%81%E9%9D%A2.webp",
+        ]);
+    }
+
+    #[test]
+    fn failed_page_download_is_sent_as_error() {
+        let urls = vec![Url::parse("http://127.0.0.1:1/001.png").unwrap()];
+        let client = Client::builder().timeout(Duration::from_secs(5)).build().unwrap();
+        let mut source = UrlListSource { sha256: [0; 32], urls, client, max_page_bytes: 0 };
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        assert!(source.get_all_page_bytes(tx));
+        let (index, content) = rx.blocking_recv().unwrap();
+        assert_eq!(index, 0);
+        assert!(content.is_err());
+    }
+}
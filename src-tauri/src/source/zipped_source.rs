@@ -6,23 +6,28 @@ use zip::{
 
 use std::path::Path;
 use std::fs::File;
-use std::io;
+use std::io::{self, Read, Seek, SeekFrom};
 
-use super::{PageSource, FileBytes, check_valid_ext, cal_sha256};
-use shared::NeedPassword;
+use super::{PageSource, FileBytes, ReadSeek, check_valid_ext, cal_sha256, is_mac_artifact, volume_name_cmp, page_name_cmp, read_capped, spill_file};
+use shared::{Capabilities, NeedPassword};
+
+type BoxedZipArchive = ZipArchive<Box<dyn ReadSeek>>;
 
 pub struct ZippedSource {
     sha256: [u8; 32],
     password: Option<Vec<u8>>,
-    zip_archive: ZipArchive<File>,
+    zip_archive: BoxedZipArchive,
     indice_table: Vec<usize>,
+    max_page_bytes: u64,
+    /// 压缩包本身的大小，用于限制内嵌压缩包展开后的总大小
+    archive_len: u64,
 }
     
 impl PageSource for ZippedSource {
     fn get_page_bytes(&mut self, index: usize) -> anyhow::Result<FileBytes> {
         if let Some(&index) = self.indice_table.get(index) {
             let file = self.zip_archive.by_index_with_options(index, ZipReadOptions::new().password(self.password.as_deref()))?;
-            Ok(Self::zip_file_to_bytes(file)?)
+            Ok(Self::zip_file_to_bytes(file, self.max_page_bytes)?)
         } else {
             Ok(Default::default())
         }
@@ -35,17 +40,36 @@ impl PageSource for ZippedSource {
     fn sha256(&self) -> &[u8; 32] {
         &self.sha256
     }
+
+    /// zip 的条目可以单独解压，按页读取代价低
+    fn prefetch_hint(&self) -> usize {
+        2
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_password: true,
+            ..Capabilities::new(false, false)
+        }
+    }
 }
 
 impl ZippedSource {
-    pub fn new(file_path: impl AsRef<Path>, password: Option<String>) -> anyhow::Result<Self> {
-        let mut file = File::open(file_path.as_ref())?;
-        let sha256 = cal_sha256(&mut file)?;
+    pub fn new(file_path: impl AsRef<Path>, password: Option<String>, case_insensitive_sort: bool) -> anyhow::Result<Self> {
+        let file = File::open(file_path.as_ref())?;
+        Self::from_reader(file, password, case_insensitive_sort)
+    }
+
+    /// 从任意可随机读取的数据构建，例如内存中的 `Cursor<Vec<u8>>`
+    pub fn from_reader(mut reader: impl ReadSeek + 'static, password: Option<String>, case_insensitive_sort: bool) -> anyhow::Result<Self> {
+        let archive_len = reader.seek(SeekFrom::End(0))?;
+        let sha256 = cal_sha256(&mut reader)?;
         let password = password.map(|x| x.into_bytes());
         let pwd = password.as_deref();
-        let mut zip_archive = ZipArchive::new(file)?;
+        let reader: Box<dyn ReadSeek> = Box::new(reader);
+        let mut zip_archive = ZipArchive::new(reader)?;
         if zip_archive.is_empty() {
-            return Ok(Self { sha256, password: None, zip_archive, indice_table: Default::default() })
+            return Ok(Self { sha256, password: None, zip_archive, indice_table: Default::default(), max_page_bytes: 0, archive_len })
         }
 
         if let Some(pwd) = pwd {
@@ -67,7 +91,7 @@ impl ZippedSource {
                     .then_some((index, entry.name().to_string()))
                 })
                 .collect();
-            indice_file_name_table.sort_by(|a, b| a.1.cmp(&b.1));
+            indice_file_name_table.sort_by(|a, b| page_name_cmp(a.1.as_str(), b.1.as_str(), case_insensitive_sort));
             indice_file_name_table.into_iter().map(|(index, _)| index).collect()
         };
 
@@ -76,10 +100,21 @@ impl ZippedSource {
             password,
             zip_archive,
             indice_table,
+            max_page_bytes: 0,
+            archive_len,
         })
     }
+
+    pub fn with_max_page_bytes(mut self, max_page_bytes: u64) -> Self {
+        self.max_page_bytes = max_page_bytes;
+        self
+    }
+
+    pub fn archive_len(&self) -> u64 {
+        self.archive_len
+    }
     
-    fn get_index_of_an_encrypted_file(zip_archive: &mut ZipArchive<File>) -> anyhow::Result<Option<usize>> {
+    fn get_index_of_an_encrypted_file(zip_archive: &mut BoxedZipArchive) -> anyhow::Result<Option<usize>> {
         for index in 0..zip_archive.len() {
             match zip_archive.by_index(index) {
                 Err(InvalidPassword) | Err(UnsupportedArchive(shared::NEED_PASSWORD)) => return Ok(Some(index)),
@@ -91,18 +126,109 @@ impl ZippedSource {
         Ok(None)        
     }
 
-    fn zip_file_to_bytes(mut file: ZipFile<'_, File>) -> io::Result<FileBytes> {
-        let mut buffer = Vec::with_capacity(file.size() as usize);
-        io::copy(&mut file, &mut buffer)?;
-        Ok(buffer)
+    fn zip_file_to_bytes(file: ZipFile<'_, Box<dyn ReadSeek>>, max: u64) -> io::Result<FileBytes> {
+        let declared = file.size();
+        read_capped(file, declared, max)
+    }
+
+    /// 读取压缩包内所有 pdf 文件的内容，按文件名排序
+    pub fn embedded_pdfs(&mut self, case_insensitive_sort: bool, cjk_numeral_sort: bool) -> anyhow::Result<Vec<FileBytes>> {
+        let mut indice_file_name_table: Vec<(usize, String)> = (0..self.zip_archive.len())
+            .filter_map(|index| {
+                let name = self.zip_archive.name_for_index(index)?;
+                let path = Path::new(name);
+                (!is_mac_artifact(path)
+                    && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(shared::EXT_PDF)))
+                .then(|| (index, name.to_string()))
+            })
+            .collect();
+        indice_file_name_table.sort_by(|a, b| volume_name_cmp(a.1.as_str(), b.1.as_str(), case_insensitive_sort, cjk_numeral_sort));
+
+        indice_file_name_table.into_iter().map(|(index, _)| {
+            let file = self.zip_archive.by_index_with_options(index, ZipReadOptions::new().password(self.password.as_deref()))?;
+            Ok(Self::zip_file_to_bytes(file, self.max_page_bytes)?)
+        }).collect()
     }
 
-    pub fn rebuild_indice_table(&mut self, img_paths: &[&Path]) {
-        self.indice_table.clear();
-        let indice_table: Vec<usize> = img_paths.iter().map(|&path| {
-            self.zip_archive.index_for_path(path).unwrap_or(usize::MAX)
+    /// 将压缩包内嵌的 zip、cbz、7z 压缩包解压到临时文件，返回文件名与文件，按文件名排序
+    ///
+    /// 写出的总大小从 `budget` 中扣除，超出时返回错误，防止压缩炸弹占满磁盘
+    pub fn nested_archives(&mut self, case_insensitive_sort: bool, cjk_numeral_sort: bool, budget: &mut u64) -> anyhow::Result<Vec<(String, File)>> {
+        let mut indice_file_name_table: Vec<(usize, String)> = (0..self.zip_archive.len())
+            .filter_map(|index| {
+                let name = self.zip_archive.name_for_index(index)?;
+                let path = Path::new(name);
+                let ext = path.extension().unwrap_or_default().to_ascii_lowercase();
+                (!is_mac_artifact(path) && [shared::EXT_ZIP, shared::EXT_CBZ, shared::EXT_7Z].iter().any(|&x| ext == x))
+                .then(|| (index, name.to_string()))
+            })
+            .collect();
+        indice_file_name_table.sort_by(|a, b| volume_name_cmp(a.1.as_str(), b.1.as_str(), case_insensitive_sort, cjk_numeral_sort));
+
+        indice_file_name_table.into_iter().map(|(index, name)| {
+            if *budget == 0 {
+                anyhow::bail!("内嵌压缩包的总大小超过上限");
+            }
+            let file = self.zip_archive.by_index_with_options(index, ZipReadOptions::new().password(self.password.as_deref()))?;
+            let mut spill = spill_file()?;
+            let written = io::copy(&mut file.take(*budget + 1), &mut spill)?;
+            if written > *budget {
+                anyhow::bail!("内嵌压缩包的总大小超过上限");
+            }
+            *budget -= written;
+            spill.seek(SeekFrom::Start(0))?;
+            Ok((name, spill))
+        }).collect()
+    }
+
+    /// 按给定顺序重建页表，压缩包里找不到的路径跳过，返回各路径是否找到
+    pub fn rebuild_indice_table(&mut self, img_paths: &[&Path]) -> Vec<bool> {
+        let indices: Vec<Option<usize>> = img_paths.iter().map(|&path| {
+            let index = self.zip_archive.index_for_path(path);
+            if index.is_none() {
+                eprintln!("压缩包中找不到 {}，已跳过", path.to_string_lossy());
+            }
+            index
         }).collect();
 
-        self.indice_table = indice_table;
+        self.indice_table = indices.iter().flatten().copied().collect();
+        indices.iter().map(Option::is_some).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::tests::{png_fixture, temp_path, zip_fixture};
+    use std::io::Cursor;
+
+    #[test]
+    fn opens_zip_from_memory() {
+        let pages = [png_fixture(4, 4, 0), png_fixture(4, 4, 255)];
+        let archive = zip_fixture(&[("b.png", &pages[1]), ("notes.txt", b"text"), ("a.png", &pages[0])]);
+        let path = temp_path("from_reader.zip");
+        std::fs::write(path.as_path(), archive.as_slice()).unwrap();
+        let from_file = ZippedSource::new(path.as_path(), None, false);
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        let mut source = ZippedSource::from_reader(Cursor::new(archive), None, false).unwrap();
+        assert_eq!(source.sha256(), from_file.unwrap().sha256());
+        assert_eq!(source.page_count(), 2);
+        assert_eq!(source.get_page_bytes(0).unwrap(), pages[0]);
+        assert_eq!(source.get_page_bytes(1).unwrap(), pages[1]);
+    }
+
+    #[test]
+    fn entry_expanding_past_cap_is_rejected() {
+        // 压缩后只有几 KB，解压后 8 MB
+        let bomb = vec![0u8; 8 << 20];
+        let page = png_fixture(4, 4, 0);
+        let archive = zip_fixture(&[("a.png", &page), ("b.png", &bomb)]);
+        assert!(archive.len() < 1 << 20);
+
+        let mut source = ZippedSource::from_reader(Cursor::new(archive), None, false).unwrap().with_max_page_bytes(1 << 20);
+        assert_eq!(source.get_page_bytes(0).unwrap(), page);
+        let e = source.get_page_bytes(1).unwrap_err();
+        assert!(e.to_string().contains("超过上限"), "{}", e);
     }
 }
\ No newline at end of file
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// 读取用户为各本漫画设置的标题，键为 sha256 的十六进制
+fn read_overrides(path: &Path) -> HashMap<String, String> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(s.as_str()).unwrap_or_else(|e| {
+            eprintln!("解析标题文件失败：{}", e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+pub fn get_override(path: &Path, key: &str) -> Option<String> {
+    read_overrides(path).remove(key)
+}
+
+/// 标题为空时移除覆盖
+pub fn set_override(path: &Path, key: &str, title: &str) -> io::Result<()> {
+    let mut overrides = read_overrides(path);
+    let title = title.trim();
+    if title.is_empty() {
+        overrides.remove(key);
+    } else {
+        overrides.insert(key.to_string(), title.to_string());
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&overrides)?)
+}
+
+/// 标题的优先级：用户设置 > 书中元数据 > 文件名
+pub fn resolve_title(title_override: Option<String>, metadata: Option<String>, book_path: &Path) -> String {
+    title_override
+        .or(metadata.filter(|x| !x.trim().is_empty()))
+        .unwrap_or_else(|| {
+            let name = if book_path.is_dir() { book_path.file_name() } else { book_path.file_stem() };
+            name.unwrap_or(book_path.as_os_str()).to_string_lossy().into_owned()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::tests::temp_path;
+
+    #[test]
+    fn override_beats_metadata_beats_filename() {
+        let book = Path::new("/books/download_final2.cbz");
+        let metadata = || Some("元数据标题".to_string());
+        assert_eq!(resolve_title(Some("自定义标题".to_string()), metadata(), book), "自定义标题");
+        assert_eq!(resolve_title(None, metadata(), book), "元数据标题");
+        // 空白的元数据视为没有
+        assert_eq!(resolve_title(None, Some("  ".to_string()), book), "download_final2");
+        assert_eq!(resolve_title(None, None, book), "download_final2");
+    }
+
+    #[test]
+    fn override_is_persisted_and_cleared_by_blank_title() {
+        let path = temp_path("titles.json");
+        set_override(path.as_path(), "abc", " 第一卷 ").unwrap();
+        assert_eq!(get_override(path.as_path(), "abc").as_deref(), Some("第一卷"));
+        set_override(path.as_path(), "abc", "").unwrap();
+        assert_eq!(get_override(path.as_path(), "abc"), None);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt::Display;
+use std::sync::mpsc;
+
+/// 阅读期间阻止屏幕休眠，drop 时释放
+///
+/// Windows 下 `SetThreadExecutionState` 只对调用线程生效，
+/// 所以由一个常驻线程持有 `KeepAwake`，直到本结构体被 drop
+pub struct WakeLock {
+    _tx: mpsc::Sender<()>,
+}
+
+impl WakeLock {
+    pub fn acquire() -> Option<Self> {
+        Self::acquire_with(|| {
+            keepawake::Builder::default()
+                .display(true)
+                .idle(true)
+                .reason("正在阅读漫画")
+                .app_name("local-manga-reader")
+                .app_reverse_domain("io.github.yurikodx.local-manga-reader")
+                .create()
+        })
+    }
+
+    /// 在常驻线程中用 `create` 取得系统的常亮锁并一直持有，便于测试时替换
+    fn acquire_with<T, E: Display>(create: impl FnOnce() -> Result<T, E> + Send + 'static) -> Option<Self> {
+        let (tx, rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            match create() {
+                Ok(awake) => {
+                    _ = result_tx.send(true);
+                    // 发送端 drop 后 recv 返回 Err，随即释放
+                    _ = rx.recv();
+                    drop(awake);
+                    eprintln!("已释放屏幕常亮");
+                },
+                Err(e) => {
+                    eprintln!("保持屏幕常亮失败：{}", e);
+                    _ = result_tx.send(false);
+                },
+            }
+        });
+
+        result_rx.recv().unwrap_or_default().then_some(Self { _tx: tx })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// drop 时通知测试线程，代替系统的常亮锁
+    struct FakeAwake(mpsc::Sender<()>);
+
+    impl Drop for FakeAwake {
+        fn drop(&mut self) {
+            _ = self.0.send(());
+        }
+    }
+
+    #[test]
+    fn lock_held_until_dropped() {
+        let (released_tx, released_rx) = mpsc::channel();
+        let lock = WakeLock::acquire_with(move || Ok::<_, String>(FakeAwake(released_tx)));
+        assert!(lock.is_some());
+        assert!(released_rx.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(lock);
+        assert!(released_rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn failed_lock_is_none() {
+        assert!(WakeLock::acquire_with(|| Err::<(), _>("不支持")).is_none());
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use tauri::{PhysicalPosition, PhysicalSize, WebviewWindow};
+
+/// 主窗口的位置与大小，均为物理像素
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    #[serde(default)]
+    pub fullscreen: bool,
+}
+
+/// 显示器的可用区域
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Area {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Area {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        let (x, y) = (x as i64, y as i64);
+        x >= self.x as i64 && x < self.x as i64 + self.width as i64
+            && y >= self.y as i64 && y < self.y as i64 + self.height as i64
+    }
+}
+
+pub fn load(path: &Path) -> Option<WindowGeometry> {
+    let s = std::fs::read_to_string(path).ok()?;
+    toml::from_str(s.as_str()).inspect_err(|e| eprintln!("解析窗口状态失败：{}", e)).ok()
+}
+
+pub fn save(path: &Path, geometry: &WindowGeometry) -> io::Result<()> {
+    std::fs::write(path, toml::to_string(geometry).map_err(io::Error::other)?)
+}
+
+/// 最大化或全屏时窗口尺寸即屏幕尺寸，此时保留上次记下的位置与大小，只更新状态标记
+pub fn capture(window: &WebviewWindow, previous: Option<WindowGeometry>) -> tauri::Result<WindowGeometry> {
+    let maximized = window.is_maximized()?;
+    let fullscreen = window.is_fullscreen()?;
+    if let Some(previous) = previous.filter(|_| maximized || fullscreen) {
+        return Ok(WindowGeometry { maximized, fullscreen, ..previous });
+    }
+
+    let position = window.outer_position()?;
+    let size = window.inner_size()?;
+    Ok(WindowGeometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        maximized,
+        fullscreen,
+    })
+}
+
+/// 全屏在窗口移回显示器之后设置，显示器已不存在时会在移入的显示器上全屏
+pub fn restore(window: &WebviewWindow, geometry: WindowGeometry) -> tauri::Result<()> {
+    let monitors: Vec<Area> = window.available_monitors()?.iter().map(|monitor| {
+        let area = monitor.work_area();
+        Area {
+            x: area.position.x,
+            y: area.position.y,
+            width: area.size.width,
+            height: area.size.height,
+        }
+    }).collect();
+    let geometry = clamp_to_monitors(geometry, monitors.as_slice());
+
+    window.set_size(PhysicalSize::new(geometry.width, geometry.height))?;
+    window.set_position(PhysicalPosition::new(geometry.x, geometry.y))?;
+    if geometry.maximized {
+        window.maximize()?;
+    }
+    Ok(())
+}
+
+/// 配置中明确写了就按配置，否则沿用上次关闭时的状态
+pub fn start_fullscreen(saved: Option<&WindowGeometry>, configured: Option<bool>) -> bool {
+    configured.unwrap_or(saved.is_some_and(|x| x.fullscreen))
+}
+
+/// 把窗口放回某个显示器内
+///
+/// 左上角所在的显示器优先，找不到时（如显示器已拔掉）放到第一个显示器上，
+/// 窗口比显示器大时缩小到显示器大小
+pub fn clamp_to_monitors(geometry: WindowGeometry, monitors: &[Area]) -> WindowGeometry {
+    let Some(monitor) = monitors.iter().find(|x| x.contains(geometry.x, geometry.y)).or(monitors.first()) else {
+        return geometry;
+    };
+
+    let width = geometry.width.min(monitor.width).max(1);
+    let height = geometry.height.min(monitor.height).max(1);
+    let max_x = monitor.x.saturating_add(monitor.width.saturating_sub(width) as i32);
+    let max_y = monitor.y.saturating_add(monitor.height.saturating_sub(height) as i32);
+    WindowGeometry {
+        x: geometry.x.clamp(monitor.x, max_x),
+        y: geometry.y.clamp(monitor.y, max_y),
+        width,
+        height,
+        ..geometry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::tests::temp_path;
+
+    const GEOMETRY: WindowGeometry = WindowGeometry { x: -40, y: 30, width: 1280, height: 900, maximized: true, fullscreen: false };
+
+    #[test]
+    fn geometry_round_trips_and_is_clamped() {
+        let path = temp_path("window.toml");
+        save(path.as_path(), &GEOMETRY).unwrap();
+        let loaded = load(path.as_path());
+        std::fs::write(path.as_path(), "width = [").unwrap();
+        let broken = load(path.as_path());
+        std::fs::remove_file(path.as_path()).unwrap();
+        assert_eq!(loaded, Some(GEOMETRY));
+        assert_eq!(broken, None);
+
+        // 窗口比屏幕大且左边伸出屏幕时缩小并移回
+        let monitor = Area { x: 0, y: 0, width: 1024, height: 768 };
+        let clamped = clamp_to_monitors(GEOMETRY, &[monitor]);
+        assert_eq!(clamped, WindowGeometry { x: 0, y: 0, width: 1024, height: 768, ..GEOMETRY });
+        let inside = WindowGeometry { x: 100, y: 50, width: 800, height: 600, ..GEOMETRY };
+        assert_eq!(clamp_to_monitors(inside, &[monitor]), inside);
+        assert_eq!(clamp_to_monitors(inside, &[]), inside);
+    }
+
+    #[test]
+    fn configured_fullscreen_wins_over_saved_state() {
+        let fullscreen = WindowGeometry { fullscreen: true, ..GEOMETRY };
+        assert!(start_fullscreen(Some(&fullscreen), None));
+        assert!(!start_fullscreen(Some(&GEOMETRY), None));
+        assert!(!start_fullscreen(None, None));
+        assert!(!start_fullscreen(Some(&fullscreen), Some(false)));
+        assert!(start_fullscreen(Some(&GEOMETRY), Some(true)));
+    }
+
+    #[test]
+    fn window_on_removed_monitor_moves_to_first_one() {
+        // 上次在右侧的副屏上全屏，副屏已拔掉
+        let saved = WindowGeometry { x: 2100, y: 100, width: 800, height: 600, maximized: false, fullscreen: true };
+        let primary = Area { x: 0, y: 0, width: 1920, height: 1080 };
+        let left = Area { x: -1280, y: 0, width: 1280, height: 1024 };
+        let restored = clamp_to_monitors(saved, &[primary, left]);
+        assert_eq!(restored, WindowGeometry { x: 1120, y: 100, ..saved });
+        assert!(restored.fullscreen);
+        // 左上角在其他显示器上时留在那个显示器
+        let on_left = WindowGeometry { x: -1000, ..saved };
+        assert_eq!(clamp_to_monitors(on_left, &[primary, left]), on_left);
+    }
+}
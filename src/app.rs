@@ -1,4 +1,4 @@
-use js_sys::{Object, Reflect, Date};
+use js_sys::{Object, Reflect, Date, Math};
 use leptos::{
     prelude::*,
     task::spawn_local,
@@ -6,15 +6,17 @@ use leptos::{
     html,
     ev,
 };
-use web_sys::{KeyboardEvent, HtmlCanvasElement, CanvasRenderingContext2d};
+use web_sys::{KeyboardEvent, HtmlCanvasElement, HtmlImageElement, CanvasRenderingContext2d};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use wasm_bindgen::prelude::*;
 use leptoaster::{Toaster, provide_toaster, expect_toaster};
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
 
-use shared::{CreateMangaResult, ImageData, LoadPage};
-use shared::config::{Config, InputAction};
+use shared::{Capabilities, CreateMangaResult, ImageData, LaunchTarget, LoadPage, PagePart, SavedQueue, SearchHit, SpreadPage};
+use shared::config::{BarColors, Config, EndOfBookAction, FitMode, ImageRendering, InputAction, LaunchConfig, Preset, RightClickAction};
 
 #[wasm_bindgen]
 extern "C" {
@@ -37,10 +39,146 @@ struct CreateMangaPayload<'a> {
     pwd: Option<String>,
 }
 
+#[derive(Deserialize, Serialize)]
+struct PagePayload<'a> {
+    path: &'a str,
+    index: usize,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SetTitlePayload<'a> {
+    title: &'a str,
+}
+
+#[derive(Deserialize, Serialize)]
+struct PageIndexPayload {
+    index: usize,
+}
+
+#[derive(Serialize)]
+struct ThumbnailsPayload {
+    start: usize,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct PreloadRangePayload {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SearchPayload<'a> {
+    query: &'a str,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SpreadPayload {
+    pages: Vec<SpreadPage>,
+    reverse: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct QueuePayload {
+    queue: SavedQueue,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ProgressPayload {
+    sha256: [u8; 32],
+    page: usize,
+}
+
+#[derive(Serialize)]
+struct RecentBookPayload {
+    older: bool,
+}
+
 #[derive(Deserialize, Serialize)]
 struct SetCurrentPayload {
     current: usize,
     size: usize,
+    rtl: bool,
+}
+
+/// 右键菜单项，除了转发已有的键位动作，还包括只在菜单中提供的页面操作
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    Action(InputAction),
+    ExportPage,
+    ExportSpread,
+    CopyPage,
+    SetTitle,
+    PinPage,
+    TrimCache,
+    Rescan,
+    Settings,
+    OpenUrl,
+}
+
+impl MenuItem {
+    const ALL: [(&str, MenuItem); 14] = [
+        ("打开", MenuItem::Action(InputAction::Open)),
+        ("打开文件夹", MenuItem::Action(InputAction::OpenFolder)),
+        ("打开网址", MenuItem::OpenUrl),
+        ("导出当前页", MenuItem::ExportPage),
+        ("导出当前画面", MenuItem::ExportSpread),
+        ("复制当前页", MenuItem::CopyPage),
+        ("跳转到页", MenuItem::Action(InputAction::PageJump)),
+        ("设置标题", MenuItem::SetTitle),
+        ("固定/取消固定当前页", MenuItem::PinPage),
+        ("切换阅读方向", MenuItem::Action(InputAction::ReverseReading)),
+        ("清理缓存", MenuItem::TrimCache),
+        ("重新扫描文件夹", MenuItem::Rescan),
+        ("显示帮助", MenuItem::Action(InputAction::ShowHelp)),
+        ("设置", MenuItem::Settings),
+    ];
+}
+
+/// `App` 中注册的后端事件监听数（不含 `ToastPoster` 的）
+const LISTENER_COUNT: usize = 4;
+
+/// 宽高比超过该值的页面视为跨页大图，略宽于正方形的单页（如带出血的扫描）不拆
+const WIDE_PAGE_RATIO: f64 = 1.2;
+
+/// 连续这么多次翻页都停在未载入的页上，视为载入跟不上翻页
+const LAG_STREAK: u32 = 3;
+/// 两次翻页间隔超过该值（毫秒）时重新计数
+const LAG_INTERVAL_MS: f64 = 2000.;
+/// 提示过后的冷却时间（毫秒）
+const LAG_HINT_COOLDOWN_MS: f64 = 5. * 60. * 1000.;
+
+/// 随机跳页时避开最近跳到过的这么多页
+const RANDOM_PAGE_HISTORY: usize = 5;
+
+/// 统计快速连续翻到未载入页的次数，决定何时提示载入跟不上
+#[derive(Debug, Default)]
+struct LagDetector {
+    streak: u32,
+    last_turn: Option<f64>,
+    last_hint: Option<f64>,
+}
+
+impl LagDetector {
+    /// 记录一次翻页，返回是否该提示
+    fn record(&mut self, now: f64, landed_on_loading: bool) -> bool {
+        let quick = self.last_turn.is_some_and(|x| now - x <= LAG_INTERVAL_MS);
+        self.last_turn = Some(now);
+        if !landed_on_loading {
+            self.streak = 0;
+            return false;
+        }
+        self.streak = if quick { self.streak + 1 } else { 1 };
+
+        let cooled = self.last_hint.is_none_or(|x| now - x >= LAG_HINT_COOLDOWN_MS);
+        if self.streak >= LAG_STREAK && cooled {
+            self.streak = 0;
+            self.last_hint = Some(now);
+            true
+        } else {
+            false
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -58,37 +196,220 @@ pub fn App() -> impl IntoView {
 
     let (size, set_size) = signal(2_usize);
     let (sha256, set_sha256) = signal([0_u8; 32]);
+    let epoch = StoredValue::new(0_u64);
+    let capabilities = StoredValue::new(Capabilities::default());
     let img_datas = StoredValue::new(vec![ImageData::NoData; 0]);
-    let (showing_img, set_showing_img) = signal(vec![ImageData::NoData; size.get_untracked()]);
+    // 已载入、等待下一帧统一重建显示层的源页
+    let pending_loads = StoredValue::new(Vec::<usize>::new());
+    // 后端检测出的空白页（源页索引），开启跳过空白页时翻页略过
+    let blank_pages = StoredValue::new(HashSet::<usize>::new());
+    // 固定在缓存中的页，换书后清空
+    let pinned_pages = StoredValue::new(HashSet::<usize>::new());
+    let skip_blank_pages = StoredValue::new(false);
+    let space_scrolls_in_continuous = StoredValue::new(true);
+    let (showing_img, set_showing_img) = signal(vec![(ImageData::NoData, PagePart::Whole, None); size.get_untracked()]);
+    // 显示层：第 i 个显示页对应的（源页索引，部分）
+    let page_views = StoredValue::new(vec![(0_usize, PagePart::Whole); 0]);
     let (reading_direction, set_reading_direction) = signal(true);
     let (empty_manga, set_empty_manga) = signal(true);
     let (page_count, set_page_count) = signal(0_usize);
     let (cmd_map, set_cmd_map) = signal(HashMap::new());
     let (scroll_threshold, set_scroll_threshold) = signal(3.0_f64);
-    let (current_page, set_current_page) = signal(0);
+    let (invert_scroll, set_invert_scroll) = signal(false);
+    let (current_page, set_current_page) = signal(0_usize);
     let (show_page_number, set_show_page_number) = signal(false);
+    let (fit_mode, set_fit_mode) = signal(FitMode::FitScreen);
+    // 适应窗口时在此基础上的缩放倍数与平移量，不保存，翻页或切换显示方式时恢复
+    let (zoom, set_zoom) = signal(1.0_f64);
+    let (pan, set_pan) = signal((0.0_f64, 0.0_f64));
+    let pan_drag = StoredValue::new(None::<PanDrag>);
+    // 长条滚动模式：整本书纵向排列，滚动阅读
+    let (scroll_mode, set_scroll_mode) = signal(false);
+    let (page_number_offset, set_page_number_offset) = signal(0_i64);
     let (toaster_loaded, set_toaster_loaded) = signal(false);
+    // 已注册的后端事件监听数，全部注册后才通知后端发送初始状态
+    let (listening, set_listening) = signal(0_usize);
     let path = StoredValue::new(String::new());
     let (loaded_indices, set_loaded_indices) = signal(vec![false; 0]);
+    let source_chapter_starts = StoredValue::new(vec![0_usize; 0]);
+    let (chapter_starts, set_chapter_starts) = signal(vec![0_usize; 0]);
     let (bar_height, set_bar_height) = signal(String::from("0px"));
+    let (bar_colors, set_bar_colors) = signal(BarColors::preset());
     let (toast_stacked, set_toast_stacked) = signal(false);
+    let (split_wide_pages, set_split_wide_pages) = signal(false);
+    let (spread_ratio_threshold, set_spread_ratio_threshold) = signal(0.0_f64);
+    // 与显示层一一对应，是否为需要单独占一屏的跨页
+    let spread_views = StoredValue::new(vec![false; 0]);
+    let launch_config = StoredValue::new(LaunchConfig::default());
+    let view_defaults = StoredValue::new(ViewDefaults::from(&Config::default()));
+    // 当前阅读方向是否来自书中的标记
+    let direction_from_book = StoredValue::new(false);
+    let (right_click_action, set_right_click_action) = signal(RightClickAction::Page);
+    let (end_of_book_action, set_end_of_book_action) = signal(EndOfBookAction::Stay);
+    let display_pad_width = StoredValue::new(0_usize);
+    let pick_multiple_files = StoredValue::new(false);
+    let enable_drag_drop = StoredValue::new(true);
+    // 多选打开时排队等待阅读的书
+    let queue = StoredValue::new(VecDeque::<String>::new());
+    // 重新载入后要回到的源页
+    let restore_page = StoredValue::new(None::<usize>);
+    // 最近随机跳到过的页
+    let random_pages = StoredValue::new(VecDeque::<usize>::new());
+    let (ui_margin, set_ui_margin) = signal(0_u32);
+    let (image_rendering, set_image_rendering) = signal(ImageRendering::Auto);
+    let (grayscale, set_grayscale) = signal(false);
+    let (invert, set_invert) = signal(false);
+    let (invert_brightness, set_invert_brightness) = signal(1.0_f64);
+    let (vignette, set_vignette) = signal(false);
+    let (loading_gif_delay, set_loading_gif_delay) = signal(0_u32);
+    let (spinner_current_only, set_spinner_current_only) = signal(false);
+    let (announce_page_changes, set_announce_page_changes) = signal(true);
+    let (slideshow, set_slideshow) = signal(false);
+    let slideshow_interval = StoredValue::new(5_u32);
+    let (window_focused, set_window_focused) = signal(true);
+    let (reduced_motion, set_reduced_motion) = signal(None::<bool>);
+    let page_count_presets = StoredValue::new(vec![1_usize, 2, 4]);
+    let (context_menu_pos, set_context_menu_pos) = signal(None::<(i32, i32)>);
+    let (show_thumbnails, set_show_thumbnails) = signal(false);
+    // 本次打开期间各源页的旋转角度，换书时清空
+    let (rotations, set_rotations) = signal(HashMap::<usize, i32>::new());
+
+    // 从当前页开始同屏显示的页数，遇到跨页时少于 `size`
+    let screen_size = move || spread_views.with_value(|x| screen_len(x, current_page.get_untracked(), size.get_untracked()));
 
     let refresh_showing = move || {
         let current = current_page.get_untracked();
-        let size = size.get_untracked();
-        let mut v = img_datas.with_value(|x| x[current..x.len().min(current + size)].to_vec());
+        let size = screen_size();
+        let mut v: Vec<(ImageData, PagePart, Option<usize>)> = page_views.with_value(|views| {
+            let shown = views.get(current..views.len().min(current.saturating_add(size))).unwrap_or_default();
+            img_datas.with_value(|x| shown.iter().map(|&(index, part)| (x[index].clone(), part, Some(index))).collect())
+        });
         v.resize(size, Default::default());
         set_showing_img.set(v);
     };
 
+    // 将显示页范围换算为源页范围，供后端预加载使用
+    let to_source_range = move |current: usize, size: usize| -> (usize, usize) {
+        page_views.with_value(|views| {
+            let first = views.get(current).map_or(current, |x| x.0);
+            let last = views.get(current.saturating_add(size).saturating_sub(1)).or(views.last())
+                .map_or(first.saturating_add(size).saturating_sub(1), |x| x.0);
+            (first, last.saturating_sub(first) + 1)
+        })
+    };
+
+    // 按源页加载情况重建显示层，并尽量保持当前所看的源页不变
+    let rebuild_page_views = move || {
+        let split = split_wide_pages.get_untracked();
+        let spread_threshold = spread_ratio_threshold.get_untracked();
+        let rtl = reading_direction.get_untracked();
+        let current = current_page.get_untracked();
+        let anchor = page_views.with_value(|views| views.get(current).copied());
+
+        let views: Vec<(usize, PagePart)> = img_datas.with_value(|x| x.iter().enumerate().flat_map(|(index, image_data)| {
+            views_of(index, image_data, split, rtl)
+        }).collect());
+
+        let first_view_of = |source_index: usize| views.iter().position(|x| x.0 == source_index);
+        let loaded: Vec<bool> = img_datas.with_value(|x| views.iter().map(|&(index, _)| x[index].path().is_some()).collect());
+        let spreads: Vec<bool> = img_datas.with_value(|x| views.iter().map(|&(index, part)| {
+            spread_threshold > 0. && part == PagePart::Whole && matches!(x[index], ImageData::Loaded(_, _)) && x[index].aspect_ratio() > spread_threshold
+        }).collect());
+        let starts: Vec<usize> = source_chapter_starts.with_value(|x| x.iter().filter_map(|&start| first_view_of(start)).collect());
+        let new_current = anchor.and_then(|(index, part)| views.iter().position(|&x| x == (index, part)).or(first_view_of(index)));
+
+        let view_count = views.len();
+        *page_views.write_value() = views;
+        spread_views.set_value(spreads);
+        set_page_count.set(view_count);
+        set_loaded_indices.set(loaded);
+        set_chapter_starts.set(starts);
+        if let Some(new_current) = new_current {
+            if new_current != current {
+                set_current_page.set(new_current);
+            }
+        }
+    };
+
     Effect::new(move || {
-        if toaster_loaded.get() {
-            spawn_local(async move {
-                invoke("read_config", JsValue::null()).await;
-            });
+        reading_direction.track();
+        split_wide_pages.track();
+        spread_ratio_threshold.track();
+        rebuild_page_views();
+        refresh_showing();
+    });
+
+    // 所有动画统一由根元素上的 reduced-motion 类关闭
+    Effect::new(move || {
+        let reduced = resolve_reduced_motion(reduced_motion.get(), prefers_reduced_motion());
+        if let Some(root) = document().document_element() {
+            _ = root.class_list().toggle_with_force("reduced-motion", reduced);
         }
     });
 
+    // 页面可能大于窗口，切换或换页后原始尺寸移到页面中央，适应宽高时移到阅读起点
+    Effect::new(move || {
+        showing_img.track();
+        let rtl = reading_direction.get_untracked();
+        match fit_mode.get() {
+            FitMode::FitScreen => {},
+            FitMode::OriginalSize => request_animation_frame(center_viewer),
+            FitMode::FitWidth | FitMode::FitHeight => request_animation_frame(move || start_viewer(rtl)),
+        }
+    });
+
+    // 浮层的边距由根元素上的 CSS 变量统一控制，安全区域在样式表中另外叠加
+    Effect::new(move || {
+        let margin = ui_margin.get();
+        if let Some(root) = document().document_element().and_then(|x| x.dyn_into::<web_sys::HtmlElement>().ok()) {
+            _ = root.style().set_property("--ui-margin", ui_margin_css(margin).as_str());
+        }
+    });
+
+    Effect::new(move || {
+        let rendering = image_rendering.get();
+        if let Some(root) = document().document_element().and_then(|x| x.dyn_into::<web_sys::HtmlElement>().ok()) {
+            _ = root.style().set_property("--page-rendering", rendering.css_value());
+        }
+    });
+
+    Effect::new(move || {
+        let filter = page_filter(grayscale.get(), invert.get(), invert_brightness.get());
+        if let Some(root) = document().document_element().and_then(|x| x.dyn_into::<web_sys::HtmlElement>().ok()) {
+            _ = root.style().set_property("--page-filter", filter.as_str());
+        }
+    });
+
+    Effect::new(move || {
+        current_page.track();
+        fit_mode.track();
+        scroll_mode.track();
+        set_zoom.set(1.);
+        set_pan.set((0., 0.));
+        pan_drag.set_value(None);
+    });
+
+    Effect::new(move || {
+        let zoom = zoom.get();
+        let (x, y) = pan.get();
+        if let Some(root) = document().document_element().and_then(|x| x.dyn_into::<web_sys::HtmlElement>().ok()) {
+            let style = root.style();
+            _ = style.set_property("--zoom", zoom.to_string().as_str());
+            _ = style.set_property("--pan-x", format!("{}px", x).as_str());
+            _ = style.set_property("--pan-y", format!("{}px", y).as_str());
+        }
+    });
+
+    // 缩放只在适应窗口的分页视图中生效，其余方式本身就能滚动查看
+    let zoom_by = move |factor: f64, anchor: (f64, f64)| {
+        if fit_mode.get_untracked() != FitMode::FitScreen || scroll_mode.get_untracked() {
+            return;
+        }
+        let (next, next_pan) = zoom_at(zoom.get_untracked(), pan.get_untracked(), factor, anchor);
+        set_zoom.set(next);
+        set_pan.set(next_pan);
+    };
+
     let get_input = |prompt: &str| -> Option<String> {
         web_sys::window().and_then(|win| win.prompt_with_message(prompt).ok()).unwrap_or_default()
     };
@@ -118,10 +439,38 @@ pub fn App() -> impl IntoView {
         }
     };
 
+    // 多选时第一本直接打开，其余替换原有的队列
     let pick_manga = move || {
+        set_empty_manga.set(false);
+        let multiple = pick_multiple_files.get_value();
+        spawn_local(async move {
+            let picked: Vec<String> = if multiple {
+                serde_wasm_bindgen::from_value(invoke("pick_files", JsValue::null()).await).unwrap_or_default()
+            } else {
+                let resp: Option<String> = serde_wasm_bindgen::from_value(invoke("pick_file", JsValue::null()).await).unwrap();
+                resp.into_iter().collect()
+            };
+            let mut picked = picked.into_iter();
+            if let Some(x) = picked.next() {
+                if multiple {
+                    *queue.write_value() = picked.collect();
+                    let queued = queue.with_value(VecDeque::len);
+                    if queued > 0 {
+                        emit("toast", format!("I已将其余 {} 本加入队列", queued).as_str());
+                    }
+                }
+                *path.write_value() = x;
+                create_manga(None);
+            } else {
+                cancelled_create();
+            }
+        });
+    };
+
+    let pick_folder = move || {
         set_empty_manga.set(false);
         spawn_local(async move {
-            let resp: Option<String> = serde_wasm_bindgen::from_value(invoke("pick_file", JsValue::null()).await).unwrap();
+            let resp: Option<String> = serde_wasm_bindgen::from_value(invoke("pick_folder", JsValue::null()).await).unwrap();
             if let Some(x) = resp {
                 *path.write_value() = x;
                 create_manga(None);
@@ -131,11 +480,71 @@ pub fn App() -> impl IntoView {
         });
     };
 
+    // 接着打开另一本，沿用当前的阅读方向与同屏页数，从第一页开始
+    let open_in_sequence = move |next: String, what: &str| {
+        let name = next.rsplit(['/', '\\']).next().unwrap_or_default();
+        emit("toast", format!("I{}：{}", what, pad_numbers(name, display_pad_width.get_value())).as_str());
+        *path.write_value() = next;
+        create_manga(None);
+    };
+
+    // 在最近读过的书之间切换，文件已不存在的由后端跳过
+    let open_recent = move |older: bool| {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&RecentBookPayload { older }).unwrap();
+            let next: Option<String> = serde_wasm_bindgen::from_value(invoke("recent_book", args).await).unwrap_or_default();
+            match next {
+                Some(next) => {
+                    set_empty_manga.set(false);
+                    open_in_sequence(next, "最近读过");
+                },
+                None => emit("toast", "W没有其他最近读过的书"),
+            }
+        });
+    };
+
+    let open_next_volume = move || {
+        spawn_local(async move {
+            let next: Option<String> = serde_wasm_bindgen::from_value(invoke("next_volume", JsValue::null()).await).unwrap_or_default();
+            match next {
+                Some(next) => open_in_sequence(next, "下一卷"),
+                None => emit("toast", "W没有下一卷了"),
+            }
+        });
+    };
+
+    Effect::new(move || {
+        if toaster_loaded.get() && listening.get() == LISTENER_COUNT {
+            spawn_local(async move {
+                invoke("frontend_ready", JsValue::null()).await;
+                // 从命令行指定的书打开时不再恢复队列，页码超出范围时由载入处限制
+                let target: Option<LaunchTarget> = serde_wasm_bindgen::from_value(invoke("launch_target", JsValue::null()).await).unwrap_or_default();
+                if let Some(LaunchTarget { path, page }) = target {
+                    restore_page.set_value(page.map(|x| x.saturating_sub(1)));
+                    set_empty_manga.set(false);
+                    open_in_sequence(path, "打开");
+                    return;
+                }
+                // 未开启恢复或没有未读完的队列时返回空
+                let saved: Option<SavedQueue> = serde_wasm_bindgen::from_value(invoke("load_queue", JsValue::null()).await).unwrap_or_default();
+                if let Some(SavedQueue { current, remaining }) = saved {
+                    *queue.write_value() = remaining.into();
+                    set_empty_manga.set(false);
+                    open_in_sequence(current, "继续上次的队列");
+                }
+            });
+        }
+    });
+
     let page_next = move |count: usize| {
         let current = current_page.get_untracked();
         let page_count = page_count.get_untracked();
-        if current + count < page_count {
+        if current.saturating_add(count) < page_count {
             set_current_page.set(current + count);
+        } else if let Some(next) = queue.write_value().pop_front() {
+            open_in_sequence(next, "下一本");
+        } else if end_of_book_action.get_untracked() == EndOfBookAction::NextVolume {
+            open_next_volume();
         } else {
             emit("toast", "W没啦！");
         }
@@ -148,79 +557,382 @@ pub fn App() -> impl IntoView {
         }
     };
 
+    // 整屏翻页，跨页前后的配对随之顺延
+    let screen_next = move || {
+        let step = screen_size();
+        let skipped = if skip_blank_pages.get_value() {
+            let start = current_page.get_untracked().saturating_add(step);
+            let flags: Vec<bool> = blank_pages.with_value(|blank| page_views.with_value(|views| {
+                views.iter().skip(start).map(|x| blank.contains(&x.0)).collect()
+            }));
+            blank_run(flags.as_slice())
+        } else {
+            0
+        };
+        if skipped > 0 {
+            emit("toast", format!("I跳过了 {} 页空白页", skipped).as_str());
+        }
+        page_next(step + skipped);
+    };
+    // 连续滚动模式下向下滚动约一屏，已到底部时才翻到下一本
+    let scroll_next = move || {
+        let Ok(Some(viewer)) = document().query_selector(".scroll-viewer") else {
+            return screen_next();
+        };
+        let viewport = window().inner_height().ok().and_then(|x| x.as_f64()).unwrap_or(viewer.client_height() as f64);
+        match space_scroll_target(viewer.scroll_top() as f64, viewer.scroll_height() as f64, viewer.client_height() as f64, viewport) {
+            Some(top) => viewer.set_scroll_top(top.round() as i32),
+            None => page_next(page_count.get_untracked()),
+        }
+    };
+    let screen_last = move || {
+        let current = current_page.get_untracked();
+        page_last(spread_views.with_value(|x| current - prev_screen_start(x, current, size.get_untracked())));
+    };
+
+    // 自动翻页：窗口失去焦点时暂停，到最后一页时停止，不会接着打开队列中的书
+    Effect::new(move || {
+        if !slideshow.get() || !window_focused.get() {
+            return;
+        }
+        let interval = Duration::from_secs(slideshow_interval.get_value() as u64);
+        let handle = set_interval_with_handle(move || {
+            if current_page.get_untracked().saturating_add(screen_size()) < page_count.get_untracked() {
+                screen_next();
+            } else {
+                set_slideshow.set(false);
+                emit("toast", "I自动翻页已到最后一页");
+            }
+        }, interval);
+        if let Ok(handle) = handle {
+            on_cleanup(move || handle.clear());
+        }
+    });
+
+    window_event_listener(ev::blur, move |_| set_window_focused.set(false));
+    window_event_listener(ev::focus, move |_| set_window_focused.set(true));
+
     let jump_to = move |target: usize| {
         let target = target.min(page_count.get_untracked().saturating_sub(size.get_untracked()));
         set_current_page.set(target);
     };
 
+    // 跳到当前页之后的第一处匹配，之后没有时从头找
+    let search = move || {
+        let Some(query) = get_input("请输入要搜索的文字：") else {
+            return;
+        };
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SearchPayload { query: query.as_str() }).unwrap();
+            let hits: Vec<SearchHit> = serde_wasm_bindgen::from_value(invoke("search", args).await).unwrap_or_default();
+            let current = current_page.get_untracked();
+            let targets: Vec<(usize, &SearchHit)> = page_views.with_value(|views| {
+                hits.iter().filter_map(|hit| views.iter().position(|x| x.0 == hit.page).map(|x| (x, hit))).collect()
+            });
+            match targets.iter().find(|x| x.0 > current).or(targets.first()) {
+                Some(&(target, hit)) => {
+                    jump_to(target);
+                    emit("toast", format!("I共 {} 页匹配：{}", targets.len(), hit.snippet).as_str());
+                },
+                None => emit("toast", "I没有找到"),
+            }
+        });
+    };
+
     let jump = move || {
-        let prompt = format!("请输入目标页码（共 {} 页）：", page_count.get_untracked());
-        if let Some(page_index_s) = get_input(prompt.as_str()) {
-            let page_index_s: String = page_index_s.chars().filter(|x| '0' <= *x && *x <= '9').collect();
-            if let Ok(index) = page_index_s.parse::<usize>() {
-                jump_to(index);
-            }            
+        let offset = page_number_offset.get_untracked();
+        let last = display_page_number(page_count.get_untracked().saturating_sub(1), offset);
+        let mut prompt = format!("请输入目标页码（共 {} 页）：", last);
+        // 输入有误时在提示中说明原因并重新询问，取消则放弃跳转
+        while let Some(input) = get_input(prompt.as_str()) {
+            match parse_page_input(input.as_str(), offset, page_count.get_untracked()) {
+                Ok(index) => {
+                    jump_to(index);
+                    break;
+                },
+                Err(message) => prompt = format!("{}，请重新输入目标页码（共 {} 页）：", message, last),
+            }
         }
     };
 
-    let action_handler = move |input_action_code: &str| {
-        match cmd_map.with(|x| x.get(input_action_code).copied()) {
-            Some(input_action) => match input_action {
-                InputAction::PageNext => page_next(size.get_untracked()),
-                InputAction::PageLast => page_last(size.get_untracked()),
-                InputAction::PageLeft => if reading_direction.get_untracked() {
-                    page_next(size.get_untracked())
-                } else {
-                    page_last(size.get_untracked())
-                },
-                InputAction::PageRight => if reading_direction.get_untracked() {
-                    page_last(size.get_untracked())
-                } else {
-                    page_next(size.get_untracked())
-                },
-                InputAction::PageStepNext => page_next(1),
-                InputAction::PageStepLast => page_last(1),
-                InputAction::PageStepLeft => if reading_direction.get_untracked() {
-                    page_next(1)
-                } else {
-                    page_last(1)
-                },
-                InputAction::PageStepRight => if reading_direction.get_untracked() {
-                    page_last(1)
+    // 旋转当前画面中的所有页，转回原位的从表中移除
+    let rotate_showing = move |degrees: i32| {
+        let indices: Vec<usize> = showing_img.with_untracked(|x| x.iter().filter_map(|x| x.2).collect());
+        set_rotations.update(|rotations| {
+            for index in indices {
+                let rotation = (rotations.get(&index).copied().unwrap_or(0) + degrees).rem_euclid(360);
+                if rotation == 0 {
+                    rotations.remove(&index);
                 } else {
-                    page_next(1)
-                },
-                InputAction::PageHome => jump_to(0),
-                InputAction::PageEnd => jump_to(usize::MAX),
-                InputAction::PageJump => jump(),
-                InputAction::PageCountMinus => {
-                    let size_before = size.get_untracked();
-                    if size_before > 1 {
-                        set_size.set(size_before - 1);
+                    rotations.insert(index, rotation);
+                }
+            }
+        });
+    };
+
+    let do_action = move |input_action: InputAction| {
+        if slideshow.get_untracked() && is_navigation(input_action) {
+            set_slideshow.set(false);
+        }
+        match input_action {
+            InputAction::PageNext => if scroll_mode.get_untracked() && space_scrolls_in_continuous.get_value() {
+                scroll_next()
+            } else {
+                screen_next()
+            },
+            InputAction::PageLast => screen_last(),
+            InputAction::PageLeft => if reading_direction.get_untracked() {
+                screen_next()
+            } else {
+                screen_last()
+            },
+            InputAction::PageRight => if reading_direction.get_untracked() {
+                screen_last()
+            } else {
+                screen_next()
+            },
+            InputAction::PageStepNext => page_next(1),
+            InputAction::PageStepLast => page_last(1),
+            InputAction::PageStepLeft => if reading_direction.get_untracked() {
+                page_next(1)
+            } else {
+                page_last(1)
+            },
+            InputAction::PageStepRight => if reading_direction.get_untracked() {
+                page_last(1)
+            } else {
+                page_next(1)
+            },
+            InputAction::PageHome => jump_to(0),
+            InputAction::PageEnd => jump_to(usize::MAX),
+            InputAction::PageJump => jump(),
+            InputAction::PageCountMinus => {
+                let size_before = size.get_untracked();
+                if size_before > 1 {
+                    set_size.set(size_before - 1);
+                }
+            }
+            InputAction::PageCountPlus => {
+                let size_before = size.get_untracked();
+                set_size.set(size_before + 1);
+            },
+            InputAction::ReverseReading => {
+                set_reading_direction.set(!reading_direction.get_untracked());
+            }
+            InputAction::Open => pick_manga(),
+            InputAction::OpenFolder => pick_folder(),
+            InputAction::RecentNext => open_recent(true),
+            InputAction::RecentPrev => open_recent(false),
+            InputAction::RandomPage => {
+                let current = current_page.get_untracked();
+                let target = random_pages.with_value(|recent| {
+                    let recent: Vec<usize> = recent.iter().copied().chain(std::iter::once(current)).collect();
+                    random_page(page_count.get_untracked(), size.get_untracked(), recent.as_slice(), Math::random())
+                });
+                random_pages.update_value(|recent| {
+                    recent.push_back(target);
+                    if recent.len() > RANDOM_PAGE_HISTORY {
+                        recent.pop_front();
                     }
+                });
+                jump_to(target);
+            },
+            InputAction::Fullscreen => {
+                spawn_local(async move {
+                    invoke("toggle_fullscreen", JsValue::null()).await;
+                });
+            },
+            InputAction::ShowHelp => {
+                spawn_local(async move {
+                    invoke("show_guide", JsValue::null()).await;
+                });
+            },
+            InputAction::HidePageNumber => {
+                set_show_page_number.set(!show_page_number.get_untracked());
+            },
+            InputAction::ResetView => {
+                // 只恢复显示相关的状态，保留当前页
+                let defaults = view_defaults.get_value();
+                set_reading_direction.set(defaults.right_to_left);
+                set_show_page_number.set(defaults.show_page_number);
+                set_size.set(defaults.size);
+                set_fit_mode.set(defaults.fit_mode);
+                set_scroll_mode.set(defaults.scroll_mode);
+                set_rotations.set(defaults.rotations);
+                set_image_rendering.set(defaults.image_rendering);
+                set_grayscale.set(defaults.grayscale);
+                set_invert.set(defaults.invert);
+                set_invert_brightness.set(defaults.invert_brightness);
+                set_vignette.set(defaults.vignette);
+            },
+            InputAction::Search => search(),
+            InputAction::RotateClockwise => rotate_showing(90),
+            InputAction::RotateCounterClockwise => rotate_showing(-90),
+            InputAction::ShowThumbnails => {
+                if !empty_manga.get_untracked() {
+                    set_show_thumbnails.set(!show_thumbnails.get_untracked());
                 }
-                InputAction::PageCountPlus => {
-                    let size_before = size.get_untracked();
-                    set_size.set(size_before + 1);
-                },
-                InputAction::ReverseReading => {
-                    set_reading_direction.set(!reading_direction.get_untracked());
+            },
+            InputAction::Toggle1to1 => {
+                let fallback = launch_config.with_value(|x| x.fit_mode);
+                set_fit_mode.set(toggle_original_size(fit_mode.get_untracked(), fallback));
+            },
+            InputAction::ToggleRendering => {
+                let next = image_rendering.get_untracked().next();
+                set_image_rendering.set(next);
+                emit("toast", format!("I缩放插值：{}", next.css_value()).as_str());
+            },
+            InputAction::ToggleSlideshow => {
+                let on = !slideshow.get_untracked();
+                set_slideshow.set(on);
+                if on {
+                    emit("toast", format!("I开始自动翻页，每 {} 秒一屏", slideshow_interval.get_value()).as_str());
+                } else {
+                    emit("toast", "I已停止自动翻页");
                 }
-                InputAction::Open => pick_manga(),
-                InputAction::Fullscreen => {
-                    spawn_local(async move {
-                        invoke("toggle_fullscreen", JsValue::null()).await;
-                    });
-                },
-                InputAction::ShowHelp => {
+            },
+            InputAction::ToggleGrayscale => set_grayscale.set(!grayscale.get_untracked()),
+            InputAction::ToggleInvert => set_invert.set(!invert.get_untracked()),
+            InputAction::ToggleVignette => set_vignette.set(!vignette.get_untracked()),
+            InputAction::CycleFitMode => {
+                let next = fit_mode.get_untracked().next();
+                set_fit_mode.set(next);
+                emit("toast", format!("I缩放方式：{}", fit_mode_label(next)).as_str());
+            },
+            InputAction::ToggleScrollMode => {
+                set_scroll_mode.set(!scroll_mode.get_untracked());
+            },
+            InputAction::ZoomIn => zoom_by(ZOOM_STEP, (0., 0.)),
+            InputAction::ZoomOut => zoom_by(ZOOM_STEP.recip(), (0., 0.)),
+            InputAction::ZoomReset => {
+                set_zoom.set(1.);
+                set_pan.set((0., 0.));
+            },
+            InputAction::CyclePageCount => {
+                let next = page_count_presets.with_value(|x| next_page_count(x, size.get_untracked(), page_count.get_untracked()));
+                if let Some(next) = next {
+                    set_size.set(next);
+                }
+            },
+        }
+    };
+
+    // 当前页为显示中的第一页，未载入时给出提示
+    let page_command = move |cmd: &'static str| {
+        let current = current_page.get_untracked();
+        let Some(index) = page_views.with_value(|views| views.get(current).map(|x| x.0)) else {
+            return;
+        };
+        let loaded = img_datas.with_value(|x| x.get(index).and_then(ImageData::path).map(String::from));
+        match loaded {
+            Some(path) => spawn_local(async move {
+                let args = serde_wasm_bindgen::to_value(&PagePayload { path: path.as_str(), index }).unwrap();
+                invoke(cmd, args).await;
+            }),
+            None => emit("toast", "I当前页尚未载入"),
+        }
+    };
+
+    // 固实压缩包载入完成前，解压线程正忙，不提供单页操作
+    let menu_item_enabled = move |item: MenuItem| {
+        match item {
+            MenuItem::Action(_) | MenuItem::Settings | MenuItem::OpenUrl => true,
+            MenuItem::SetTitle => !empty_manga.get_untracked(),
+            // 只需当前显示的页已写好缓存，固实压缩包也不必等整本载入完
+            MenuItem::ExportPage | MenuItem::CopyPage => {
+                loaded_indices.with_untracked(|x| views_loaded(x, current_page.get_untracked(), 1))
+            },
+            MenuItem::ExportSpread => {
+                loaded_indices.with_untracked(|x| views_loaded(x, current_page.get_untracked(), screen_size()))
+            },
+            MenuItem::TrimCache | MenuItem::PinPage => !empty_manga.get_untracked() && !capabilities.with_value(|x| x.solid),
+            MenuItem::Rescan => !empty_manga.get_untracked() && capabilities.with_value(|x| x.watchable),
+        }
+    };
+
+    let select_menu_item = move |item: MenuItem| {
+        match item {
+            MenuItem::Action(input_action) => do_action(input_action),
+            MenuItem::ExportPage => page_command("export_page"),
+            MenuItem::ExportSpread => {
+                let current = current_page.get_untracked();
+                let shown = page_views.with_value(|views| views.get(current..views.len().min(current.saturating_add(screen_size()))).unwrap_or_default().to_vec());
+                let pages: Option<Vec<SpreadPage>> = img_datas.with_value(|x| shown.iter().map(|&(index, part)| {
+                    x.get(index).and_then(ImageData::path).map(|path| SpreadPage { path: path.to_string(), index, part })
+                }).collect());
+                match pages {
+                    Some(pages) if !pages.is_empty() => spawn_local(async move {
+                        let args = serde_wasm_bindgen::to_value(&SpreadPayload { pages, reverse: reading_direction.get_untracked() }).unwrap();
+                        invoke("export_spread", args).await;
+                    }),
+                    _ => emit("toast", "I当前画面尚未全部载入"),
+                }
+            },
+            MenuItem::CopyPage => page_command("copy_page"),
+            MenuItem::SetTitle => {
+                // 取消输入时不做修改，输入空白则恢复默认标题
+                if let Some(title) = get_input("请输入标题（留空恢复默认）：") {
                     spawn_local(async move {
-                        invoke("show_guide", JsValue::null()).await;
+                        let args = serde_wasm_bindgen::to_value(&SetTitlePayload { title: title.as_str() }).unwrap();
+                        invoke("set_title_override", args).await;
                     });
-                },
-                InputAction::HidePageNumber => {
-                    set_show_page_number.set(!show_page_number.get_untracked());
-                },
+                }
+            },
+            MenuItem::PinPage => {
+                let current = current_page.get_untracked();
+                let Some(index) = page_views.with_value(|views| views.get(current).map(|x| x.0)) else {
+                    return;
+                };
+                let pinned = pinned_pages.with_value(|x| x.contains(&index));
+                if pinned {
+                    pinned_pages.write_value().remove(&index);
+                } else {
+                    pinned_pages.write_value().insert(index);
+                }
+                spawn_local(async move {
+                    let args = serde_wasm_bindgen::to_value(&PageIndexPayload { index }).unwrap();
+                    invoke(if pinned { "unpin_page" } else { "pin_page" }, args).await;
+                    emit("toast", format!("I已{}固定第 {} 页", if pinned { "取消" } else { "" }, index + 1).as_str());
+                });
+            },
+            MenuItem::TrimCache => {
+                spawn_local(async move {
+                    invoke("trim_cache", JsValue::null()).await;
+                });
             },
+            MenuItem::OpenUrl => {
+                if let Some(url) = get_input("输入漫画的网址（压缩包、pdf，或图片目录页、图片网址清单）").map(|x| x.trim().to_string()).filter(|x| !x.is_empty()) {
+                    open_in_sequence(url, "打开");
+                }
+            },
+            MenuItem::Settings => {
+                spawn_local(async move {
+                    invoke("show_settings", JsValue::null()).await;
+                });
+            },
+            MenuItem::Rescan => {
+                let current = current_page.get_untracked();
+                let Some(index) = page_views.with_value(|views| views.get(current).map(|x| x.0)) else {
+                    return;
+                };
+                spawn_local(async move {
+                    let args = serde_wasm_bindgen::to_value(&PageIndexPayload { index }).unwrap();
+                    let moved: Option<usize> = serde_wasm_bindgen::from_value(invoke("rescan", args).await).unwrap_or_default();
+                    match moved {
+                        Some(index) => {
+                            restore_page.set_value(Some(index));
+                            create_manga(None);
+                        },
+                        None => emit("toast", "I文件夹没有变化"),
+                    }
+                });
+            },
+        }
+    };
+
+    let action_handler = move |input_action_code: &str| {
+        match cmd_map.with(|x| x.get(input_action_code).copied()) {
+            Some(input_action) => do_action(input_action),
             None => {
                 #[cfg(debug_assertions)]
                 {
@@ -234,34 +946,89 @@ pub fn App() -> impl IntoView {
     };
 
     let on_mousedown = move |ev: ev::MouseEvent| {
+        // 菜单打开时，点击其他位置只关闭菜单
+        if context_menu_pos.get_untracked().is_some() {
+            set_context_menu_pos.set(None);
+            return;
+        }
         if empty_manga.get_untracked() {
             pick_manga();
-        } else {
-            match ev.button() {
-                0 => action_handler("LeftClick"),
-                1 => action_handler("MiddleClick"),
-                2 => action_handler("RightClick"),
-                _ => {}
-            }
+        } else if ev.button() == 0 && zoom.get_untracked() > 1. {
+            // 放大后左键按下先当作拖动，松开时没有移动才算点击
+            pan_drag.set_value(Some(PanDrag { start: (ev.client_x(), ev.client_y()), pan: pan.get_untracked(), moved: false }));
+        } else if let Some(code) = mouse_button_code(ev.button(), right_click_action.get_untracked()) {
+            action_handler(code);
+        }
+    };
+
+    let on_contextmenu = move |ev: ev::MouseEvent| {
+        match right_click_action.get_untracked() {
+            RightClickAction::Page => ev.prevent_default(),
+            RightClickAction::ContextMenu => {
+                ev.prevent_default();
+                set_context_menu_pos.set(Some((ev.client_x(), ev.client_y())));
+            },
+            RightClickAction::None => {},
         }
     };
 
     let on_wheel = move |ev: leptos::ev::WheelEvent| {
+        if ev.ctrl_key() {
+            ev.prevent_default(); // 阻止 webview 自身缩放
+            let factor = if ev.delta_y() < 0. { ZOOM_STEP } else { ZOOM_STEP.recip() };
+            zoom_by(factor, viewer_offset(ev.client_x(), ev.client_y()));
+            return;
+        }
+        if fit_mode.get_untracked() != FitMode::FitScreen || scroll_mode.get_untracked() {
+            return; // 页面可能超出窗口时滚轮用于滚动查看页面
+        }
         ev.prevent_default(); // 阻止页面本身滚动
-        let dy = ev.delta_y();
-        if dy.abs() > scroll_threshold.get_untracked().abs() {
-            if dy.is_sign_positive() {
-                action_handler("WheelDown");
-            } else {
-                action_handler("WheelUp");
-            }
+        if let Some(code) = wheel_code(ev.delta_y(), scroll_threshold.get_untracked(), invert_scroll.get_untracked()) {
+            action_handler(code);
         }
     };
 
+    window_event_listener(ev::mousemove, move |ev: ev::MouseEvent| {
+        let Some(mut drag) = pan_drag.get_value() else {
+            return;
+        };
+        let (dx, dy) = (ev.client_x() - drag.start.0, ev.client_y() - drag.start.1);
+        if !drag.moved && dx.abs().max(dy.abs()) <= DRAG_THRESHOLD {
+            return;
+        }
+        drag.moved = true;
+        pan_drag.set_value(Some(drag));
+        set_pan.set((drag.pan.0 + dx as f64, drag.pan.1 + dy as f64));
+    });
+
+    window_event_listener(ev::mouseup, move |_| {
+        let Some(drag) = pan_drag.get_value() else {
+            return;
+        };
+        pan_drag.set_value(None);
+        if !drag.moved {
+            action_handler("LeftClick");
+        }
+    });
+
+    // 点击菜单以外的任何位置都关闭菜单，菜单自身会阻止冒泡
+    window_event_listener(ev::mousedown, move |_| {
+        if context_menu_pos.get_untracked().is_some() {
+            set_context_menu_pos.set(None);
+        }
+    });
+
     window_event_listener(ev::keydown, move |ev: KeyboardEvent| {
         #[cfg(not(debug_assertions))]
         ev.prevent_default();
-        action_handler(ev.code().as_str());
+        if context_menu_pos.get_untracked().is_some() {
+            if ev.code() == "Escape" {
+                set_context_menu_pos.set(None);
+                return;
+            }
+            set_context_menu_pos.set(None);
+        }
+        action_handler(ev.code().as_str());
     });
 
     // 监听拖拽事件
@@ -269,31 +1036,63 @@ pub fn App() -> impl IntoView {
         let closure = Closure::wrap(Box::new(move |event: JsValue| {
             // 直接提取 event.payload.paths
             if let Some(payload) = extract_payload_from_event::<DragDropPayload>(event) {
-                if let Some(x) = payload.paths.into_iter().next() {
-                    *path.write_value() = x;
-                    set_empty_manga.set(false);
-                    create_manga(None);
+                match dropped_path(payload.paths, enable_drag_drop.get_value()) {
+                    Ok(Some(x)) => {
+                        *path.write_value() = x;
+                        set_empty_manga.set(false);
+                        create_manga(None);
+                    },
+                    Ok(None) => {},
+                    Err(()) => emit("toast", "I已关闭拖放打开，请通过选择文件打开"),
                 }
             }
         }) as Box<dyn FnMut(JsValue)>);
  
         let _ = listen("tauri://drag-drop", closure.as_ref().into()).await;
         closure.forget();
+        set_listening.update(|x| *x += 1);
     });
 
     // 监听漫画加载
     spawn_local(async move {
         let closure = Closure::wrap(Box::new(move |event: JsValue| {
             match extract_payload_from_event::<CreateMangaResult>(event).unwrap() {
-                CreateMangaResult::Success(sha256, page_count) => {
+                CreateMangaResult::Success { sha256, epoch: this_epoch, page_count, chapter_starts, capabilities: this_capabilities, right_to_left, last_page } => {
                     set_sha256.set(sha256);
+                    // 书自带的方向只作用于这本书，换书后恢复配置的方向
+                    match right_to_left {
+                        Some(right_to_left) => set_reading_direction.set(right_to_left),
+                        None if direction_from_book.get_value() => {
+                            set_reading_direction.set(launch_config.with_value(|x| x.reading_from_right_to_left));
+                        },
+                        None => {},
+                    }
+                    direction_from_book.set_value(right_to_left.is_some());
+                    epoch.set_value(this_epoch);
+                    capabilities.set_value(this_capabilities);
+                    set_rotations.set(HashMap::new());
                     set_current_page.set(0);
-                    set_page_count.set(page_count);
-                    set_loaded_indices.set(vec![false; page_count]);
+                    *source_chapter_starts.write_value() = chapter_starts;
+                    page_views.write_value().clear();
                     img_datas.write_value().clear();
+                    LOADING_SINCE.with_borrow_mut(HashMap::clear);
                     img_datas.write_value().resize(page_count, ImageData::Loading);
+                    blank_pages.write_value().clear();
+                    pinned_pages.write_value().clear();
+                    rebuild_page_views();
+                    // 重新扫描后回到原来看的那页，否则回到上次看到的页；书变短时停在最后一页
+                    if let Some(index) = restore_page.write_value().take().or(last_page) {
+                        if let Some(target) = page_views.with_value(|views| resume_view(views, index, page_count)) {
+                            set_current_page.set(target);
+                        }
+                    }
                     refresh_showing();
                     emit("toast", "S载入漫画成功");
+                    let saved = SavedQueue { current: path.get_value(), remaining: queue.with_value(|x| x.iter().cloned().collect()) };
+                    spawn_local(async move {
+                        let args = serde_wasm_bindgen::to_value(&QueuePayload { queue: saved }).unwrap();
+                        invoke("save_queue", args).await;
+                    });
                 },
                 CreateMangaResult::NeedPassword => create_manga_with_pwd(),
                 CreateMangaResult::Other(e) => {
@@ -306,56 +1105,124 @@ pub fn App() -> impl IntoView {
  
         let _ = listen("load_manga", closure.as_ref().into()).await;
         closure.forget();
+        set_listening.update(|x| *x += 1);
     });
 
     // 监听页面加载
     spawn_local(async move {
         let closure = Closure::wrap(Box::new(move |event: JsValue| {
-            let LoadPage { sha256: this_sha256, index, len: _, image_data } = extract_payload_from_event(event).unwrap();
-            if this_sha256 == sha256.get_untracked() {
+            let load_page: LoadPage = extract_payload_from_event(event).unwrap();
+            if load_page.belongs_to(&sha256.get_untracked(), epoch.get_value()) {
+                let LoadPage { index, image_data, blank, .. } = load_page;
+                if !matches!(image_data, ImageData::Loading) {
+                    LOADING_SINCE.with_borrow_mut(|x| x.remove(&index));
+                }
                 *img_datas.write_value().get_mut(index).unwrap() = image_data;
-                set_loaded_indices.set(img_datas.with_value(|x| x.iter().map(|x| matches!(x, ImageData::Loaded(_, _))).collect()));
-                let current = current_page.get_untracked();
-                if current <= index && index < current + size.get_untracked() {
-                    refresh_showing();
+                // 清出缓存时的事件不带检测结果，已记下的空白页保留到换书
+                if blank {
+                    blank_pages.write_value().insert(index);
+                }
+                // 同一帧内到达的多页只重建一次显示层，避免整本书载入时反复重建
+                let first = pending_loads.with_value(Vec::is_empty);
+                pending_loads.write_value().push(index);
+                if first {
+                    request_animation_frame(move || {
+                        let loaded = std::mem::take(&mut *pending_loads.write_value());
+                        rebuild_page_views();
+                        let current = current_page.get_untracked();
+                        let size = size.get_untracked();
+                        let showing = page_views.with_value(|views| {
+                            views.get(current..views.len().min(current.saturating_add(size))).is_some_and(|x| x.iter().any(|x| loaded.contains(&x.0)))
+                        });
+                        if showing {
+                            refresh_showing();
+                        }
+                    });
                 }
             }
         }) as Box<dyn FnMut(JsValue)>);
  
         let _ = listen("load_page", closure.as_ref().into()).await;
         closure.forget();
+        set_listening.update(|x| *x += 1);
     });
 
     // 监听配置加载
     spawn_local(async move {
         let closure = Closure::wrap(Box::new(move |event: JsValue| {
             let config: Config = extract_payload_from_event(event).unwrap();
+            view_defaults.set_value(ViewDefaults::from(&config));
             let key_bind = config.key_bind;
             log!("{:?}", key_bind);
             set_cmd_map.set(key_bind.into());
             set_scroll_threshold.set(config.scroll_threshold);
+            set_invert_scroll.set(config.invert_scroll);
             set_bar_height.set(config.loading_bar_height);
+            set_bar_colors.set(config.bar_colors);
             set_toast_stacked.set(config.toast_stacked);
+            set_split_wide_pages.set(config.split_wide_pages);
+            set_spread_ratio_threshold.set(config.spread_ratio_threshold);
+            set_right_click_action.set(config.right_click_action);
+            set_end_of_book_action.set(config.end_of_book_action);
+            set_reduced_motion.set(config.reduced_motion);
+            page_count_presets.set_value(config.page_count_presets);
+            display_pad_width.set_value(config.display_pad_width);
+            pick_multiple_files.set_value(config.pick_multiple_files);
+            enable_drag_drop.set_value(config.enable_drag_drop);
+            set_ui_margin.set(config.ui_margin);
+            set_image_rendering.set(config.image_rendering);
+            set_invert_brightness.set(config.invert_brightness);
+            set_vignette.set(config.vignette);
+            set_loading_gif_delay.set(config.loading_gif_delay_ms);
+            set_spinner_current_only.set(config.spinner_current_only);
+            skip_blank_pages.set_value(config.skip_blank_pages);
+            space_scrolls_in_continuous.set_value(config.space_scrolls_in_continuous);
+            set_announce_page_changes.set(config.announce_page_changes);
+            slideshow_interval.set_value(config.slideshow_interval_secs.max(1));
+            set_page_number_offset.set(config.page_number_offset);
             if empty_manga.get_untracked() {
                 set_reading_direction.set(config.launch_config.reading_from_right_to_left);
                 set_show_page_number.set(config.launch_config.show_page_number);
                 set_size.set(config.launch_config.page_num_per_screen.max(1));
+                set_fit_mode.set(config.launch_config.fit_mode);
             }
+            launch_config.set_value(config.launch_config);
         }) as Box<dyn FnMut(JsValue)>);
  
         let _ = listen("load_config", closure.as_ref().into()).await;
         closure.forget();
+        set_listening.update(|x| *x += 1);
+    });
+
+    // 长条滚动模式下显示全部页，每次重建显示层都会更新 loaded_indices
+    let scroll_views = Signal::derive(move || {
+        loaded_indices.track();
+        page_views.with_value(|views| img_datas.with_value(|x| {
+            views.iter().map(|&(index, part)| (x[index].clone(), part, Some(index))).collect::<Vec<_>>()
+        }))
     });
 
+    let lag_detector = StoredValue::new(LagDetector::default());
     Effect::new(move || {
         let current = current_page.get();
         let size = size.get();
+        let rtl = reading_direction.get();
         refresh_showing();
+        let landed_on_loading = showing_img.with_untracked(|x| x.iter().any(|(data, _, _)| matches!(data, ImageData::Loading)));
+        if lag_detector.write_value().record(Date::now(), landed_on_loading) {
+            emit("toast", "I加载速度跟不上翻页，可尝试减少每屏页数或启用预加载");
+        }
+        let (current, size) = to_source_range(current, spread_views.with_value(|x| screen_len(x, current, size)));
+        let sha256 = sha256.get_untracked();
         spawn_local(async move {
-            let payload = SetCurrentPayload { current, size };
+            let payload = SetCurrentPayload { current, size, rtl };
             let args = serde_wasm_bindgen::to_value(&payload).unwrap();
             log!("current_page = {}, size = {}", current, size);
             invoke("set_current", args).await;
+            if sha256.iter().any(|x| *x != 0) {
+                let args = serde_wasm_bindgen::to_value(&ProgressPayload { sha256, page: current }).unwrap();
+                invoke("save_progress", args).await;
+            }
         });
     });
 
@@ -369,6 +1236,7 @@ pub fn App() -> impl IntoView {
         let x = ev.offset_x() as f64;
         let coefficient = x / width;
         log!("click percent: {:.2}%", coefficient * 100.);
+        set_slideshow.set(false);
         jump_to((page_count.get_untracked() as f64 * coefficient) as usize);
     };
 
@@ -376,35 +1244,292 @@ pub fn App() -> impl IntoView {
         <Toaster stacked=toast_stacked />
         <ToastPoster set_toaster_loaded=set_toaster_loaded />
         <div class="main"
-            on:contextmenu=|ev| ev.prevent_default()
+            on:contextmenu=on_contextmenu
             on:wheel=on_wheel
         >
-            {move || {
-                let v = showing_img.get();
-                let flag = reading_direction.get();
-                let bar_height = bar_height.get();
-                
-                view! {
-                    <MultiImageViewer
-                        image_datas=v
-                        reverse=flag
-                        bar_height=bar_height
-                        on_mousedown=on_mousedown
-                    />
+            <Show
+                when=move || scroll_mode.get()
+                fallback=move || move || {
+                    let v = showing_img.get();
+                    let flag = reading_direction.get();
+                    let bar_height = bar_height.get();
+
+                    view! {
+                        <MultiImageViewer
+                            image_datas=v
+                            reverse=flag
+                            fit_mode=fit_mode.get()
+                            loading_gif_delay=loading_gif_delay.get()
+                            spinner_current_only=spinner_current_only.get()
+                            bar_height=bar_height
+                            rotations=rotations.get()
+                            on_mousedown=on_mousedown
+                        />
+                    }
                 }
-            }}
+            >
+                <ScrollViewer
+                    views=scroll_views
+                    current=current_page
+                    set_current=set_current_page
+                    loading_gif_delay=loading_gif_delay
+                    bar_height=bar_height
+                />
+            </Show>
+            // 暗角盖在页面之上、进度条之下，不拦截点击与滚轮
+            <Show when=move || vignette.get()>
+                <div class="vignette" aria-hidden="true"></div>
+            </Show>
             <LoadingBar
                 loaded_indices=loaded_indices
+                chapter_starts=chapter_starts
                 bar_height=bar_height
+                bar_colors=bar_colors
                 current_page=current_page
                 size=size
                 on_mousedown=on_mousedown_for_bar
                 reading_direction=reading_direction
             />
         </div>
+        // 读屏软件朗读的页码，视觉上隐藏
+        <div class="sr-only" role="status" aria-live="polite">
+            {move || {
+                if announce_page_changes.get() && !empty_manga.get() {
+                    page_announcement(current_page.get(), size.get(), page_count.get(), page_number_offset.get())
+                } else {
+                    String::new()
+                }
+            }}
+        </div>
         <Show when=move || show_page_number.get()>
-            <CounterDisplay current=current_page size=size page_count=page_count />
+            <CounterDisplay current=current_page size=size page_count=page_count offset=page_number_offset />
+        </Show>
+        <Show when=move || show_thumbnails.get()>
+            <ThumbnailGrid
+                views=page_views.get_value()
+                loaded_indices=loaded_indices
+                current=current_page.get_untracked()
+                offset=page_number_offset.get_untracked()
+                on_jump=move |index| {
+                    set_show_thumbnails.set(false);
+                    set_slideshow.set(false);
+                    jump_to(index);
+                }
+                on_close=move || set_show_thumbnails.set(false)
+            />
         </Show>
+        {move || context_menu_pos.get().map(|pos| view! {
+            <ContextMenu
+                pos=pos
+                enabled=menu_item_enabled
+                on_select=move |item| {
+                    set_context_menu_pos.set(None);
+                    select_menu_item(item);
+                }
+            />
+        })}
+    }
+}
+
+/// 在 `[0, page_count - size]` 中按 `random`（取自 `[0, 1)`）取一个起始页
+///
+/// 尽量避开 `recent` 中的页，可选的页都在其中时不再避开
+fn random_page(page_count: usize, size: usize, recent: &[usize], random: f64) -> usize {
+    let max = page_count.saturating_sub(size);
+    let pick = |len: usize| ((random * len as f64) as usize).min(len.saturating_sub(1));
+    let candidates: Vec<usize> = (0..=max).filter(|x| !recent.contains(x)).collect();
+    if candidates.is_empty() {
+        pick(max + 1)
+    } else {
+        candidates[pick(candidates.len())]
+    }
+}
+
+/// 源页 `page` 对应的第一个显示页，超出页数时取最后一页
+fn resume_view(views: &[(usize, PagePart)], page: usize, page_count: usize) -> Option<usize> {
+    let page = page.min(page_count.saturating_sub(1));
+    views.iter().position(|x| x.0 == page)
+}
+
+/// 把名字中每段不足 `width` 位的数字补零，不含数字的名字原样返回
+fn pad_numbers(name: &str, width: usize) -> String {
+    let mut padded = String::with_capacity(name.len());
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut digits = String::from(c);
+            while let Some(x) = chars.next_if(char::is_ascii_digit) {
+                digits.push(x);
+            }
+            padded.push_str(format!("{:0>width$}", digits).as_str());
+        } else {
+            padded.push(c);
+        }
+    }
+    padded
+}
+
+/// 内部索引换算为显示的页码，正文之前的页（页码不足 1）显示为带 # 的文件序号
+fn display_page_number(index: usize, offset: i64) -> String {
+    let number = index as i64 + 1 - offset;
+    if number >= 1 {
+        number.to_string()
+    } else {
+        format!("#{}", index + 1)
+    }
+}
+
+/// 输入的页码换算为内部索引，小于第一页时取 0，超出末页由调用处限制
+fn index_from_page_number(number: i64, offset: i64) -> usize {
+    number.saturating_sub(1).saturating_add(offset).max(0) as usize
+}
+
+/// 手动翻页的动作，自动翻页时遇到即停止
+fn is_navigation(action: InputAction) -> bool {
+    matches!(action,
+        InputAction::PageNext | InputAction::PageLast | InputAction::PageLeft | InputAction::PageRight
+        | InputAction::PageStepNext | InputAction::PageStepLast | InputAction::PageStepLeft | InputAction::PageStepRight
+        | InputAction::PageHome | InputAction::PageEnd | InputAction::PageJump | InputAction::RandomPage
+    )
+}
+
+/// 解析跳转时输入的页码，忽略数字以外的字符；超出范围的页码（包括数值溢出）取末页，空白与负数返回提示
+fn parse_page_input(input: &str, offset: i64, page_count: usize) -> Result<usize, &'static str> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("没有输入页码");
+    }
+    if input.starts_with('-') {
+        return Err("页码不能为负数");
+    }
+    let digits: String = input.chars().filter(char::is_ascii_digit).collect();
+    if digits.is_empty() {
+        return Err("没有找到数字");
+    }
+    let last = page_count.saturating_sub(1);
+    Ok(digits.parse::<i64>().map_or(last, |number| index_from_page_number(number, offset).min(last)))
+}
+
+/// 根元素上 `--ui-margin` 的值，样式表中各浮层再叠加安全区域
+fn ui_margin_css(margin: u32) -> String {
+    format!("{}px", margin)
+}
+
+/// 配置优先，未配置时跟随系统
+fn resolve_reduced_motion(config: Option<bool>, os_prefers: bool) -> bool {
+    config.unwrap_or(os_prefers)
+}
+
+/// 在原始尺寸与配置的适应方式之间切换，配置本身就是原始尺寸时切回整屏显示
+fn toggle_original_size(current: FitMode, fallback: FitMode) -> FitMode {
+    match current {
+        FitMode::OriginalSize if fallback == FitMode::OriginalSize => FitMode::FitScreen,
+        FitMode::OriginalSize => fallback,
+        _ => FitMode::OriginalSize,
+    }
+}
+
+/// 连续滚动模式下每次滚动视口高度的比例，留一点上一屏的内容便于衔接
+const SPACE_SCROLL_FACTOR: f64 = 0.9;
+
+/// 连续滚动模式下向后翻页时的新滚动位置，不超过底部；已在底部时返回 `None`，由调用处翻到下一本
+fn space_scroll_target(scroll_top: f64, scroll_height: f64, client_height: f64, viewport_height: f64) -> Option<f64> {
+    let bottom = (scroll_height - client_height).max(0.);
+    (scroll_top < bottom - 1.).then(|| (scroll_top + viewport_height * SPACE_SCROLL_FACTOR).min(bottom))
+}
+
+fn prefers_reduced_motion() -> bool {
+    window()
+        .match_media("(prefers-reduced-motion: reduce)")
+        .ok()
+        .flatten()
+        .is_some_and(|x| x.matches())
+}
+
+/// 取预设中比当前同屏页数大的下一个值，到头后回到最小值
+///
+/// 预设值限制在 1 到总页数之间，未打开漫画时不限制上限
+fn next_page_count(presets: &[usize], current: usize, page_count: usize) -> Option<usize> {
+    let max = if page_count == 0 { usize::MAX } else { page_count };
+    let mut presets: Vec<usize> = presets.iter().map(|&x| x.clamp(1, max)).collect();
+    presets.sort_unstable();
+    presets.dedup();
+    presets.iter().copied().find(|&x| x > current).or(presets.first().copied())
+}
+
+/// 从 `current` 开始同屏显示的页数：跨页单独占一屏，跨页之前的页不与它同屏
+fn screen_len(spreads: &[bool], current: usize, size: usize) -> usize {
+    let is_spread = |i: usize| spreads.get(i).copied().unwrap_or(false);
+    if is_spread(current) {
+        return 1;
+    }
+    (1..size).find(|&i| is_spread(current + i)).unwrap_or(size)
+}
+
+/// 向前翻一屏时的起始页：紧挨着的是跨页时只退一页，否则最多退 `size` 页且不越过跨页
+fn prev_screen_start(spreads: &[bool], current: usize, size: usize) -> usize {
+    let is_spread = |i: usize| spreads.get(i).copied().unwrap_or(false);
+    let start = current.saturating_sub(size.max(1));
+    if current > 0 && is_spread(current - 1) {
+        return current - 1;
+    }
+    (start..current).rev().find(|&i| is_spread(i)).map_or(start, |i| i + 1)
+}
+
+/// 将滚轮位移换算为键位表中的按键名，位移不超过阈值时忽略；开启反转时上下互换
+pub fn wheel_code(dy: f64, threshold: f64, invert: bool) -> Option<&'static str> {
+    if dy.abs() > threshold.abs() {
+        if dy.is_sign_positive() != invert {
+            Some("WheelDown")
+        } else {
+            Some("WheelUp")
+        }
+    } else {
+        None
+    }
+}
+
+/// 每次缩放的倍率与缩放范围
+const ZOOM_STEP: f64 = 1.25;
+const ZOOM_MIN: f64 = 0.25;
+const ZOOM_MAX: f64 = 8.;
+/// 移动超过该像素数才算拖动，否则视为点击
+const DRAG_THRESHOLD: i32 = 4;
+
+/// 放大后按住左键拖动平移
+#[derive(Clone, Copy)]
+struct PanDrag {
+    start: (i32, i32),
+    pan: (f64, f64),
+    moved: bool,
+}
+
+/// 以 `anchor`（相对视图中心的坐标）为不动点缩放，返回新的缩放倍数与平移量；回到原大小时平移归零
+fn zoom_at(zoom: f64, pan: (f64, f64), factor: f64, anchor: (f64, f64)) -> (f64, (f64, f64)) {
+    let next = (zoom * factor).clamp(ZOOM_MIN, ZOOM_MAX);
+    if (next - 1.).abs() < 1e-6 {
+        return (1., (0., 0.));
+    }
+    let k = next / zoom;
+    (next, (anchor.0 - (anchor.0 - pan.0) * k, anchor.1 - (anchor.1 - pan.1) * k))
+}
+
+/// 窗口坐标换算为相对分页视图中心的坐标
+fn viewer_offset(x: i32, y: i32) -> (f64, f64) {
+    let Ok(Some(viewer)) = document().query_selector(".multi-viewer") else {
+        return (0., 0.);
+    };
+    let rect = viewer.get_bounding_client_rect();
+    (x as f64 - rect.left() - rect.width() / 2., y as f64 - rect.top() - rect.height() / 2.)
+}
+
+/// 将鼠标按键换算为键位表中的按键名，右键是否参与翻页由配置决定
+pub fn mouse_button_code(button: i16, right_click_action: RightClickAction) -> Option<&'static str> {
+    match button {
+        0 => Some("LeftClick"),
+        1 => Some("MiddleClick"),
+        2 if right_click_action == RightClickAction::Page => Some("RightClick"),
+        _ => None,
     }
 }
 
@@ -413,6 +1538,14 @@ struct DragDropPayload {
     paths: Vec<String>,
 }
 
+/// 拖入的文件中要打开的那个，关闭拖放打开时返回 `Err`，由调用处提示
+fn dropped_path(paths: Vec<String>, enabled: bool) -> Result<Option<String>, ()> {
+    if !enabled {
+        return Err(());
+    }
+    Ok(paths.into_iter().next())
+}
+
 // 辅助函数：从事件对象中提取 payload
 fn extract_payload_from_event<T: DeserializeOwned>(event: JsValue) -> Option<T> {
     // 使用 serde 直接反序列化
@@ -447,22 +1580,54 @@ pub fn ToastPoster(set_toaster_loaded: WriteSignal<bool>) -> impl IntoView {
 
 #[component]
 pub fn MultiImageViewer(
-    image_datas: Vec<ImageData>,
+    image_datas: Vec<(ImageData, PagePart, Option<usize>)>,
     reverse: bool,
+    fit_mode: FitMode,
+    loading_gif_delay: u32,
+    /// 只有当前页（画面中的第一页）载入时显示动画
+    spinner_current_only: bool,
     bar_height: String,
+    /// 源页索引 -> 顺时针旋转的角度
+    rotations: HashMap<usize, i32>,
     on_mousedown: impl Fn(ev::MouseEvent) + 'static
 ) -> impl IntoView {
-    let aspect_ratio: f64 = image_datas.iter().map(|x| x.aspect_ratio()).sum();
+    let rotation_of = |index: Option<usize>| index.and_then(|x| rotations.get(&x).copied()).unwrap_or(0);
+    let aspect_ratio: f64 = image_datas.iter().map(|(x, part, index)| rotated_aspect_ratio(part_aspect_ratio(x, *part), rotation_of(*index))).sum();
     let width = (297. * aspect_ratio) as u32;
+    let image_datas: Vec<_> = image_datas.into_iter().enumerate()
+        .map(|(i, (src, part, index))| (src, part, index, rotation_of(index), still_loading(i == 0, spinner_current_only)))
+        .collect();
+    let page_view = move |(src, part, index, rotation, still): (ImageData, PagePart, Option<usize>, i32, bool)| {
+        if rotation == 0 {
+            return view! { <ImageViewer image_data=src part=part index=index loading_gif_delay=loading_gif_delay still=still /> }.into_any();
+        }
+        // 外框按旋转后的宽高比占位，图片在框内居中旋转；转 90° 时图片的高等于外框的宽
+        let inner = part_aspect_ratio(&src, part);
+        let outer = rotated_aspect_ratio(inner, rotation);
+        let inner_height = if rotation % 180 == 0 { 100. } else { outer * 100. };
+        let style = format!("aspect-ratio: {}; --inner-ar: {}; --inner-h: {}%; --rotation: {}deg;", outer, inner, inner_height, rotation);
+        view! {
+            <div class="rotated" style=style>
+                <ImageViewer image_data=src part=part index=index loading_gif_delay=loading_gif_delay still=still />
+            </div>
+        }.into_any()
+    };
     
     view! {
-        <div class="multi-viewer" style=format!("--bar-h: {};", bar_height)>
-        <div class="strip" style=format!("--w: {}px;", width) on:mousedown=on_mousedown>
+        <div class="multi-viewer"
+            role="region"
+            aria-label="页面"
+            class:actual-size=fit_mode == FitMode::OriginalSize
+            class:fit-width=fit_mode == FitMode::FitWidth
+            class:fit-height=fit_mode == FitMode::FitHeight
+            style=format!("--bar-h: {};", bar_height)
+        >
+        <div class="strip" style=format!("--w: {}px; --ar: {};", width, aspect_ratio) on:mousedown=on_mousedown>
             {
                 if reverse {
-                    image_datas.into_iter().rev().map(|src| view! { <ImageViewer image_data=src /> }).collect_view()
+                    image_datas.into_iter().rev().map(page_view).collect_view()
                 } else {
-                    image_datas.into_iter().map(|src| view! { <ImageViewer image_data=src /> }).collect_view()
+                    image_datas.into_iter().map(page_view).collect_view()
                 }
             }
         </div>
@@ -470,17 +1635,475 @@ pub fn MultiImageViewer(
     }
 }
 
+/// 所有页纵向排列的滚动视图，视口顶部所在的页即当前页
+///
+/// 按页与载入状态作键渲染，某页载入时只替换该页，滚动位置不受影响
+#[component]
+pub fn ScrollViewer(
+    views: Signal<Vec<(ImageData, PagePart, Option<usize>)>>,
+    current: ReadSignal<usize>,
+    set_current: WriteSignal<usize>,
+    loading_gif_delay: ReadSignal<u32>,
+    bar_height: ReadSignal<String>,
+) -> impl IntoView {
+    let container = NodeRef::<html::Div>::new();
+
+    let top_visible = move || -> Option<usize> {
+        let container = container.get_untracked()?;
+        let top = container.scroll_top();
+        let pages = container.children();
+        (0..pages.length()).find(|&i| {
+            pages.item(i).and_then(|x| x.dyn_into::<web_sys::HtmlElement>().ok())
+                .is_some_and(|x| x.offset_top() + x.offset_height() > top)
+        }).map(|i| i as usize)
+    };
+
+    let on_scroll = move |_| {
+        if let Some(index) = top_visible() {
+            if index != current.get_untracked() {
+                set_current.set(index);
+            }
+        }
+    };
+
+    // 由按键、进度条等改变当前页时滚动到该页；滚动引起的改变顶部已是该页，不再滚动
+    Effect::new(move || {
+        let index = current.get();
+        if top_visible() == Some(index) {
+            return;
+        }
+        if let Some(page) = container.get_untracked().and_then(|x| x.children().item(index as u32)) {
+            page.scroll_into_view();
+        }
+    });
+
+    view! {
+        <div class="scroll-viewer" node_ref=container style=move || format!("--bar-h: {};", bar_height.get()) on:scroll=on_scroll>
+            <For
+                each=move || views.get().into_iter().enumerate()
+                key=|(i, (data, part, _))| (*i, *part, data.path().map(String::from))
+                children=move |(_, (data, part, index))| {
+                    let style = format!("aspect-ratio: {};", part_aspect_ratio(&data, part));
+                    view! {
+                        <div class="scroll-page" style=style>
+                            <ImageViewer image_data=data part=part index=index loading_gif_delay=loading_gif_delay.get_untracked() />
+                        </div>
+                    }
+                }
+            />
+        </div>
+    }
+}
+
+fn center_viewer() {
+    let Ok(Some(viewer)) = document().query_selector(".multi-viewer") else {
+        return;
+    };
+    viewer.set_scroll_left(centered_scroll(viewer.scroll_width(), viewer.client_width()));
+    viewer.set_scroll_top(centered_scroll(viewer.scroll_height(), viewer.client_height()));
+}
+
+/// 滚到阅读起点：顶部，从右到左阅读时还要滚到最右边
+fn start_viewer(rtl: bool) {
+    let Ok(Some(viewer)) = document().query_selector(".multi-viewer") else {
+        return;
+    };
+    viewer.set_scroll_top(0);
+    viewer.set_scroll_left(if rtl { viewer.scroll_width() } else { 0 });
+}
+
+/// 一个源页对应的显示页：开启拆分时单张的宽图拆成左右两半，切块的长图不会是跨页
+///
+/// 从右到左阅读时先看右半边
+fn views_of(index: usize, image_data: &ImageData, split: bool, rtl: bool) -> Vec<(usize, PagePart)> {
+    if split && matches!(image_data, ImageData::Loaded(_, _)) && image_data.aspect_ratio() > WIDE_PAGE_RATIO {
+        if rtl {
+            vec![(index, PagePart::Right), (index, PagePart::Left)]
+        } else {
+            vec![(index, PagePart::Left), (index, PagePart::Right)]
+        }
+    } else {
+        vec![(index, PagePart::Whole)]
+    }
+}
+
+/// 从 `start` 起的 `count` 个显示页（不超出末尾）是否都已载入，一页都没有时为假
+fn views_loaded(loaded: &[bool], start: usize, count: usize) -> bool {
+    loaded.get(start..loaded.len().min(start.saturating_add(count)))
+        .is_some_and(|x| !x.is_empty() && x.iter().all(|&x| x))
+}
+
+/// 重置画面时恢复的显示状态：启动配置中的各项，不旋转，退出滚动模式
+#[derive(Clone, Debug, PartialEq)]
+struct ViewDefaults {
+    right_to_left: bool,
+    show_page_number: bool,
+    size: usize,
+    fit_mode: FitMode,
+    scroll_mode: bool,
+    rotations: HashMap<usize, i32>,
+    image_rendering: ImageRendering,
+    grayscale: bool,
+    invert: bool,
+    invert_brightness: f64,
+    vignette: bool,
+}
+
+impl From<&Config> for ViewDefaults {
+    fn from(config: &Config) -> Self {
+        let launch = &config.launch_config;
+        Self {
+            right_to_left: launch.reading_from_right_to_left,
+            show_page_number: launch.show_page_number,
+            size: launch.page_num_per_screen.max(1),
+            fit_mode: launch.fit_mode,
+            scroll_mode: false,
+            rotations: HashMap::new(),
+            image_rendering: config.image_rendering,
+            grayscale: false,
+            invert: false,
+            invert_brightness: config.invert_brightness,
+            vignette: config.vignette,
+        }
+    }
+}
+
+/// 页面的 CSS 滤镜，黑白与反色可以同时开启，反色时再按配置压低亮度
+fn page_filter(grayscale: bool, invert: bool, invert_brightness: f64) -> String {
+    let mut filters = Vec::new();
+    if grayscale {
+        filters.push(String::from("grayscale(1)"));
+    }
+    if invert {
+        filters.push(String::from("invert(1)"));
+        if invert_brightness < 1. {
+            filters.push(format!("brightness({})", invert_brightness));
+        }
+    }
+    if filters.is_empty() {
+        String::from("none")
+    } else {
+        filters.join(" ")
+    }
+}
+
+fn fit_mode_label(fit_mode: FitMode) -> &'static str {
+    match fit_mode {
+        FitMode::FitScreen => "适应窗口",
+        FitMode::FitWidth => "适应宽度",
+        FitMode::FitHeight => "适应高度",
+        FitMode::OriginalSize => "原始尺寸",
+    }
+}
+
+/// 让内容在视口中居中所需的滚动距离，内容不超出视口时为 0
+fn centered_scroll(content: i32, viewport: i32) -> i32 {
+    (content - viewport).max(0) / 2
+}
+
+/// 页面图片的替代文字，按源页序号计，不受页码偏移影响
+fn page_alt(index: Option<usize>, part: PagePart) -> String {
+    let Some(index) = index else {
+        return String::from("页面");
+    };
+    match part {
+        PagePart::Whole => format!("第 {} 页", index + 1),
+        PagePart::Left => format!("第 {} 页左半", index + 1),
+        PagePart::Right => format!("第 {} 页右半", index + 1),
+    }
+}
+
+/// 翻页时朗读的文字，页码与屏幕上的页码显示一致
+fn page_announcement(current: usize, size: usize, page_count: usize, offset: i64) -> String {
+    let total = display_page_number(page_count.saturating_sub(1), offset);
+    let last = current.saturating_add(size.max(1) - 1).min(page_count.saturating_sub(1));
+    if last > current {
+        format!("第 {} 至 {} 页，共 {} 页", display_page_number(current, offset), display_page_number(last, offset), total)
+    } else {
+        format!("第 {} 页，共 {} 页", display_page_number(current, offset), total)
+    }
+}
+
+/// 进度条画布上每页占用的像素数，留出绘制章节分隔线的精度
+const BAR_PAGE_PX: f64 = 4.;
+/// 章节分隔线的颜色
+const BAR_TICK_COLOR: &str = "#2c3e50";
+
+/// 进度条依次要画的矩形（颜色，起点，宽度）：先铺满未载入色，再画已载入的各段、当前屏与章节分隔线
+fn bar_fills<'a>(bits: &[bool], chapter_starts: &[usize], current: usize, size: usize, colors: &'a BarColors) -> Vec<(&'a str, f64, f64)> {
+    if bits.is_empty() {
+        return vec![(colors.unloaded.as_str(), 0., 1.)];
+    }
+
+    let mut fills = vec![(colors.unloaded.as_str(), 0., bits.len() as f64 * BAR_PAGE_PX)];
+    let mut iter = bits.iter().copied().chain([false]).enumerate();
+    while let Some(start) = iter.find_map(|(index, x)| x.then_some(index)) {
+        let end = iter.find_map(|(index, x)| (!x).then_some(index)).unwrap();
+        fills.push((colors.loaded.as_str(), start as f64 * BAR_PAGE_PX, (end - start) as f64 * BAR_PAGE_PX));
+    }
+    fills.push((colors.current.as_str(), current as f64 * BAR_PAGE_PX, size as f64 * BAR_PAGE_PX));
+    fills.extend(chapter_tick_xs(chapter_starts, bits.len(), BAR_PAGE_PX).into_iter().map(|x| (BAR_TICK_COLOR, x, 1.)));
+    fills
+}
+
+/// 章节分隔线在进度条画布上的横坐标，`k` 为每页的像素数；第一章开头与越界的起点不画
+fn chapter_tick_xs(chapter_starts: &[usize], page_count: usize, k: f64) -> Vec<f64> {
+    chapter_starts.iter().filter(|&&x| 0 < x && x < page_count).map(|&x| x as f64 * k).collect()
+}
+
+/// 开头连续空白页的数量；后面没有非空白页时返回 0，留在最后不跳过，以免越过书末
+fn blank_run(flags: &[bool]) -> usize {
+    flags.iter().position(|&blank| !blank).unwrap_or(0)
+}
+
+/// 未载入的页是否显示静态占位图：开启只在当前页显示动画时，当前页以外的都用静态图
+fn still_loading(is_current: bool, spinner_current_only: bool) -> bool {
+    spinner_current_only && !is_current
+}
+
+/// 旋转 90° 或 270° 时宽高互换
+fn rotated_aspect_ratio(aspect_ratio: f64, rotation: i32) -> f64 {
+    if rotation % 180 == 0 {
+        aspect_ratio
+    } else {
+        aspect_ratio.recip()
+    }
+}
+
+fn part_aspect_ratio(image_data: &ImageData, part: PagePart) -> f64 {
+    match part {
+        PagePart::Whole => image_data.aspect_ratio(),
+        PagePart::Left | PagePart::Right => image_data.aspect_ratio() / 2.,
+    }
+}
+
+/// 图片加载失败时是否请求重新编码：只有第一次失败时返回 `true`
+fn take_retry(retried: &mut bool) -> bool {
+    !std::mem::replace(retried, true)
+}
+
 #[component]
-pub fn ImageViewer(image_data: ImageData) -> impl IntoView {
+pub fn ImageViewer(
+    image_data: ImageData,
+    #[prop(optional)] part: PagePart,
+    /// 源页索引，图片无法显示时据此请求后端重新编码，也用作替代文字
+    index: Option<usize>,
+    /// 未载入时等待多久才显示载入动画（毫秒）
+    #[prop(optional)] loading_gif_delay: u32,
+    /// 未载入时显示静态占位图而不是动画
+    #[prop(optional)] still: bool,
+) -> impl IntoView {
     match image_data {
-        ImageData::Loaded(path, _) => {
+        ImageData::Loaded(path, aspect_ratio) => {
             let url = convert_file_src(path.as_str());
-            view! { <img src=url.as_str() /> }.into_any()
+            // 只重试一次，重新编码后仍然失败就保持原样，避免反复请求
+            let retried = StoredValue::new(false);
+            let on_error = {
+                let url = url.clone();
+                move |ev: ev::ErrorEvent| {
+                    let Some(index) = index else {
+                        return;
+                    };
+                    if !retried.try_update_value(take_retry).unwrap_or_default() {
+                        log!("第 {} 页重新编码后仍无法显示", index + 1);
+                        return;
+                    }
+                    let img: HtmlImageElement = event_target(&ev);
+                    let url = url.clone();
+                    spawn_local(async move {
+                        let args = serde_wasm_bindgen::to_value(&PageIndexPayload { index }).unwrap();
+                        let reencoded: bool = serde_wasm_bindgen::from_value(invoke("reencode_page", args).await).unwrap_or_default();
+                        if reencoded {
+                            // 文件名不变，加上查询参数绕过 WebView 对失败结果的缓存
+                            img.set_src(format!("{}?reencoded", url).as_str());
+                        }
+                    });
+                }
+            };
+            // 半页通过裁切整张图实现，无需后端另存图片
+            let style = match part {
+                PagePart::Whole => String::new(),
+                PagePart::Left => format!("aspect-ratio: {}; object-fit: cover; object-position: left;", aspect_ratio / 2.),
+                PagePart::Right => format!("aspect-ratio: {}; object-fit: cover; object-position: right;", aspect_ratio / 2.),
+            };
+            view! { <img src=url.as_str() alt=page_alt(index, part) style=style on:error=on_error /> }.into_any()
         },
-        ImageData::Loading => view! { 
-            <img class="loading-gif" src=shared::LOADING_GIF /> 
+        ImageData::Tiled(_, aspect_ratio, tiles) => view! {
+            <div class="tiles" role="img" aria-label=page_alt(index, part) style=format!("aspect-ratio: {};", aspect_ratio)>
+                {tiles.into_iter().map(|tile| view! { <img src=convert_file_src(tile.as_str()) alt="" /> }).collect_view()}
+            </div>
         }.into_any(),
-        ImageData::NoData => view! { <img src=shared::NO_DATA /> }.into_any(),
+        ImageData::Loading if still => view! { <img class="loading-gif" src=shared::LOADING_STILL alt=format!("{}，载入中", page_alt(index, part)) /> }.into_any(),
+        ImageData::Loading => {
+            // 先占位不显示，计时结束仍未载入才显示动画；载入后本视图被替换，计时随之取消
+            // 计时从该页第一次显示为载入中算起，其他页载入引起的重新渲染不会让计时重来
+            let now = js_sys::Date::now();
+            let started = index.map_or(now, |index| LOADING_SINCE.with_borrow_mut(|x| *x.entry(index).or_insert(now)));
+            let remaining = gif_delay_remaining(loading_gif_delay, started, now);
+            let (show_gif, set_show_gif) = signal(remaining == 0);
+            if remaining > 0 {
+                let handle = set_timeout_with_handle(move || set_show_gif.set(true), Duration::from_millis(remaining as u64));
+                if let Ok(handle) = handle {
+                    on_cleanup(move || handle.clear());
+                }
+            }
+            view! { 
+                <img class="loading-gif" class:waiting=move || !show_gif.get() src=shared::LOADING_GIF alt=format!("{}，载入中", page_alt(index, part)) /> 
+            }.into_any()
+        },
+        ImageData::Error(message) => view! {
+            <img class="page-error" src=shared::NO_DATA alt=format!("{}，载入失败：{}", page_alt(index, part), message) title=message />
+        }.into_any(),
+        ImageData::NoData => view! { <img src=shared::NO_DATA alt="" /> }.into_any(),
+    }
+}
+
+thread_local! {
+    /// 各源页开始显示为载入中的时间（毫秒），页面载入或换书时清除
+    static LOADING_SINCE: RefCell<HashMap<usize, f64>> = RefCell::new(HashMap::new());
+}
+
+/// 载入动画还需等待的毫秒数，0 表示立即显示
+fn gif_delay_remaining(delay_ms: u32, started: f64, now: f64) -> u32 {
+    let elapsed = (now - started).max(0.);
+    (delay_ms as f64 - elapsed).max(0.).ceil() as u32
+}
+
+/// 所有显示页的缩略图总览，点击跳到该页
+///
+/// 只为可见范围内已载入的页请求缩略图，滚动或有新页载入时补上
+#[component]
+pub fn ThumbnailGrid(
+    /// 打开时的显示层快照，（源页索引，部分）
+    views: Vec<(usize, PagePart)>,
+    loaded_indices: ReadSignal<Vec<bool>>,
+    current: usize,
+    offset: i64,
+    on_jump: impl Fn(usize) + Copy + 'static,
+    on_close: impl Fn() + Copy + 'static,
+) -> impl IntoView {
+    let container = NodeRef::<html::Div>::new();
+    // 源页索引 -> 缩略图路径
+    let thumbs = RwSignal::new(HashMap::<usize, String>::new());
+    // 请求中的源页，避免滚动时重复请求
+    let pending = StoredValue::new(HashSet::<usize>::new());
+    let sources = StoredValue::new(views.iter().map(|x| x.0).collect::<Vec<_>>());
+    // 最近一次请求优先载入的源页范围
+    let preloading = StoredValue::new(0..0);
+
+    let preload = move |range: std::ops::Range<usize>| {
+        if preloading.get_value() == range {
+            return;
+        }
+        preloading.set_value(range.clone());
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&PreloadRangePayload { start: range.start, end: range.end }).unwrap();
+            invoke("preload_range", args).await;
+        });
+    };
+
+    on_cleanup(move || preload(0..0));
+
+    let request_visible = move || {
+        let Some(container) = container.get_untracked() else {
+            return;
+        };
+        let (top, bottom) = (container.scroll_top(), container.scroll_top() + container.client_height());
+        let cells = container.children();
+        let visible: Vec<usize> = (0..cells.length()).filter(|&i| {
+            cells.item(i).and_then(|x| x.dyn_into::<web_sys::HtmlElement>().ok())
+                .is_some_and(|x| x.offset_top() + x.offset_height() > top && x.offset_top() < bottom)
+        }).map(|i| i as usize).collect();
+        let unloaded: Vec<usize> = loaded_indices.with_untracked(|loaded| sources.with_value(|sources| {
+            visible.iter().filter(|&&i| !loaded.get(i).copied().unwrap_or(false)).map(|&i| sources[i]).collect()
+        }));
+        if let (Some(&start), Some(&end)) = (unloaded.iter().min(), unloaded.iter().max()) {
+            preload(start..end + 1);
+        }
+        let missing: Vec<usize> = loaded_indices.with_untracked(|loaded| sources.with_value(|sources| pending.with_value(|pending| thumbs.with_untracked(|thumbs| {
+            visible.iter().filter(|&&i| loaded.get(i).copied().unwrap_or(false))
+                .map(|&i| sources[i])
+                .filter(|x| !pending.contains(x) && !thumbs.contains_key(x))
+                .collect()
+        }))));
+        let (Some(&start), Some(&end)) = (missing.iter().min(), missing.iter().max()) else {
+            return;
+        };
+        pending.update_value(|x| x.extend(start..=end));
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&ThumbnailsPayload { start, count: end - start + 1 }).unwrap();
+            let paths: Vec<Option<String>> = serde_wasm_bindgen::from_value(invoke("thumbnails", args).await).unwrap_or_default();
+            pending.update_value(|x| x.retain(|i| !(start..=end).contains(i)));
+            thumbs.update(|x| x.extend(paths.into_iter().enumerate().filter_map(|(i, path)| path.map(|path| (start + i, path)))));
+        });
+    };
+
+    Effect::new(move || {
+        let Some(container) = container.get() else {
+            return;
+        };
+        if let Some(cell) = container.children().item(current as u32) {
+            cell.scroll_into_view();
+        }
+        request_visible();
+    });
+
+    Effect::new(move || {
+        loaded_indices.track();
+        request_visible();
+    });
+
+    view! {
+        <div class="thumbnail-overlay" on:mousedown=|ev| ev.stop_propagation() on:click=move |_| on_close()>
+            <div class="thumbnail-grid" node_ref=container on:scroll=move |_| request_visible() on:click=|ev| ev.stop_propagation()>
+                {views.into_iter().enumerate().map(|(i, (index, part))| {
+                    let style = match part {
+                        PagePart::Whole => "",
+                        PagePart::Left => "object-fit: cover; object-position: left;",
+                        PagePart::Right => "object-fit: cover; object-position: right;",
+                    };
+                    view! {
+                        <div
+                            class="thumbnail"
+                            class:current=i == current
+                            class:loaded=move || loaded_indices.with(|x| x.get(i).copied().unwrap_or(false))
+                            on:click=move |_| on_jump(i)
+                        >
+                            {move || thumbs.with(|x| x.get(&index).cloned()).map(|path| view! { <img src=convert_file_src(path.as_str()) style=style /> })}
+                            <span>{display_page_number(i, offset)}</span>
+                        </div>
+                    }
+                }).collect_view()}
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn ContextMenu(
+    pos: (i32, i32),
+    enabled: impl Fn(MenuItem) -> bool + Copy + 'static,
+    on_select: impl Fn(MenuItem) + Copy + 'static
+) -> impl IntoView {
+    let (x, y) = pos;
+
+    view! {
+        <ul
+            class="context-menu"
+            style=format!("left: {}px; top: {}px;", x, y)
+            on:mousedown=|ev| ev.stop_propagation()
+            on:contextmenu=|ev| ev.prevent_default()
+        >
+            {MenuItem::ALL.into_iter().map(|(label, item)| {
+                let enabled = enabled(item);
+                view! {
+                    <li
+                        class:disabled=!enabled
+                        on:click=move |_| if enabled { on_select(item) }
+                    >{label}</li>
+                }
+            }).collect_view()}
+        </ul>
     }
 }
 
@@ -488,17 +2111,19 @@ pub fn ImageViewer(image_data: ImageData) -> impl IntoView {
 pub fn CounterDisplay(
     current: ReadSignal<usize>,
     size: ReadSignal<usize>,
-    page_count: ReadSignal<usize>
+    page_count: ReadSignal<usize>,
+    offset: ReadSignal<i64>,
 ) -> impl IntoView {
     view! {
-        <div class="counter-display">
+        <div class="counter-display" aria-hidden="true">
             {move ||
                 {
-                    let (cur, size, total) = (current.get(), size.get(), page_count.get());
+                    let (cur, size, total, offset) = (current.get(), size.get(), page_count.get(), offset.get());
+                    let total = display_page_number(total.saturating_sub(1), offset);
                     if size > 1 {
-                        format!("{} - {} / {}", cur + 1, cur + size, total)
+                        format!("{} - {} / {}", display_page_number(cur, offset), display_page_number(cur.saturating_add(size - 1), offset), total)
                     } else {
-                        format!("{} / {}", cur + 1, total)
+                        format!("{} / {}", display_page_number(cur, offset), total)
                     }
                 }
             }
@@ -509,7 +2134,9 @@ pub fn CounterDisplay(
 #[component]
 pub fn LoadingBar(
     loaded_indices: ReadSignal<Vec<bool>>,
+    chapter_starts: ReadSignal<Vec<usize>>,
     bar_height: ReadSignal<String>,
+    bar_colors: ReadSignal<BarColors>,
     current_page: ReadSignal<usize>,
     size: ReadSignal<usize>,
     reading_direction: ReadSignal<bool>,
@@ -518,46 +2145,30 @@ pub fn LoadingBar(
     let canvas_ref = NodeRef::<html::Canvas>::new();
     let (style, set_style) = signal(String::new());
 
-    let draw = move |canvas: HtmlCanvasElement, bits: &[bool], current: usize, size: usize| {
-        const H: f64 = 1.;
+    let draw = move |canvas: HtmlCanvasElement, bits: &[bool], chapter_starts: &[usize], current: usize, size: usize, colors: &BarColors| {
         canvas.set_height(1);
+        canvas.set_width(((bits.len() as f64 * BAR_PAGE_PX) as u32).max(1));
         let ctx = canvas
             .get_context("2d")
             .unwrap()
             .unwrap()
             .dyn_into::<CanvasRenderingContext2d>()
             .unwrap();
-        if bits.is_empty() {
-            canvas.set_width(1);
-            ctx.set_fill_style_str("#bfc9d1");
-            ctx.fill_rect(0., 0., 1., H);
-        } else {
-            let w = bits.len() as f64;
-
-            canvas.set_width(bits.len() as u32);
-
-            ctx.set_fill_style_str("#bfc9d1");
-            ctx.fill_rect(0.0, 0.0, w, H);
-
-            ctx.set_fill_style_str("#39C5BB");
-            let mut iter = bits.iter().copied().chain([false]).enumerate();
-            while let Some(start) = iter.find_map(|(index, x)| x.then_some(index)) {
-                let end = iter.find_map(|(index, x)| (!x).then_some(index)).unwrap();
-                ctx.fill_rect(start as f64, 0., (end - start) as f64, H);
-            }
-
-            ctx.set_fill_style_str("#E14A96");
-            ctx.fill_rect(current as f64, 0., size as f64, H);
+        // 从右到左时整条进度条已被镜像翻转
+        for (color, x, w) in bar_fills(bits, chapter_starts, current, size, colors) {
+            ctx.set_fill_style_str(color);
+            ctx.fill_rect(x, 0., w, 1.);
         }
     };
 
     Effect::new(move || {
         let loaded_indices = loaded_indices.get();
         let bits = loaded_indices.as_slice();
+        let chapter_starts = chapter_starts.get();
         let current = current_page.get();
         let size = size.get();
         let canvas = canvas_ref.get().expect("canvas not mounted");
-        draw(canvas, bits, current, size);
+        bar_colors.with(|colors| draw(canvas, bits, chapter_starts.as_slice(), current, size, colors));
     });
 
     Effect::new(move || {
@@ -579,3 +2190,406 @@ pub fn LoadingBar(
         />
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_view_restores_configured_defaults() {
+        let mut config = Config::preset();
+        config.launch_config = LaunchConfig {
+            reading_from_right_to_left: false,
+            show_page_number: false,
+            page_num_per_screen: 0,
+            fit_mode: FitMode::FitWidth,
+        };
+        config.image_rendering = ImageRendering::Pixelated;
+        config.invert_brightness = 0.8;
+        config.vignette = true;
+        let defaults = ViewDefaults::from(&config);
+        assert!(!defaults.right_to_left);
+        assert!(!defaults.show_page_number);
+        assert_eq!(defaults.size, 1);
+        assert_eq!(defaults.fit_mode, FitMode::FitWidth);
+        assert!(!defaults.scroll_mode);
+        assert!(defaults.rotations.is_empty());
+        assert_eq!(defaults.image_rendering, ImageRendering::Pixelated);
+        assert!(!defaults.grayscale && !defaults.invert);
+        assert_eq!(defaults.invert_brightness, 0.8);
+        assert!(defaults.vignette);
+    }
+
+    #[test]
+    fn wide_page_splits_in_reading_order() {
+        let wide = ImageData::Loaded(String::from("a.jpg"), 1.5);
+        assert_eq!(views_of(3, &wide, true, true), [(3, PagePart::Right), (3, PagePart::Left)]);
+        assert_eq!(views_of(3, &wide, true, false), [(3, PagePart::Left), (3, PagePart::Right)]);
+        assert_eq!(views_of(3, &wide, false, true), [(3, PagePart::Whole)]);
+        let nearly_square = ImageData::Loaded(String::from("b.jpg"), 1.05);
+        assert_eq!(views_of(4, &nearly_square, true, true), [(4, PagePart::Whole)]);
+    }
+
+    #[test]
+    fn loading_gif_waits_out_the_delay_across_rerenders() {
+        // 第一次显示时开始计时，100 ms 后重新渲染只需再等剩下的时间
+        assert_eq!(gif_delay_remaining(300, 1000., 1000.), 300);
+        assert_eq!(gif_delay_remaining(300, 1000., 1100.), 200);
+        assert_eq!(gif_delay_remaining(300, 1000., 1300.), 0);
+        assert_eq!(gif_delay_remaining(0, 1000., 1000.), 0);
+    }
+
+    #[test]
+    fn blank_run_stops_at_first_page_with_content() {
+        assert_eq!(blank_run(&[true, true, false, true]), 2);
+        assert_eq!(blank_run(&[false, true]), 0);
+        // 书末的空白页不跳过
+        assert_eq!(blank_run(&[true, true]), 0);
+    }
+
+    #[test]
+    fn chapter_ticks_at_page_offsets() {
+        assert_eq!(chapter_tick_xs(&[0, 3, 10], 12, 4.), [12., 40.]);
+        assert_eq!(chapter_tick_xs(&[0, 12], 12, 4.), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn page_actions_need_only_shown_pages_loaded() {
+        let loaded = [false, true, true, false];
+        assert!(views_loaded(&loaded, 1, 1));
+        assert!(views_loaded(&loaded, 1, 2));
+        assert!(!views_loaded(&loaded, 2, 2));
+        assert!(!views_loaded(&loaded, 0, 1));
+        assert!(!views_loaded(&loaded, 4, 1));
+        assert!(!views_loaded(&[], 0, 2));
+    }
+
+    /// 暗角盖在页面上，必须放行点击与滚轮
+    #[test]
+    fn vignette_lets_pointer_events_through() {
+        let css = include_str!("../styles.css");
+        let start = css.find(".vignette {").expect("styles.css 中缺少 .vignette");
+        let rule = &css[start..start + css[start..].find('}').unwrap()];
+        assert!(rule.contains("pointer-events: none;"));
+        assert!(rule.contains("position: fixed;"));
+        assert!(rule.contains("radial-gradient("));
+    }
+
+    #[test]
+    fn page_filters_combine() {
+        assert_eq!(page_filter(false, false, 0.8), "none");
+        assert_eq!(page_filter(true, false, 0.8), "grayscale(1)");
+        assert_eq!(page_filter(false, true, 1.), "invert(1)");
+        assert_eq!(page_filter(true, true, 0.8), "grayscale(1) invert(1) brightness(0.8)");
+    }
+
+    #[test]
+    fn right_button_pages_only_when_configured() {
+        assert_eq!(mouse_button_code(0, RightClickAction::ContextMenu), Some("LeftClick"));
+        assert_eq!(mouse_button_code(1, RightClickAction::None), Some("MiddleClick"));
+        assert_eq!(mouse_button_code(2, RightClickAction::Page), Some("RightClick"));
+        assert_eq!(mouse_button_code(2, RightClickAction::ContextMenu), None);
+        assert_eq!(mouse_button_code(2, RightClickAction::None), None);
+        assert_eq!(mouse_button_code(3, RightClickAction::Page), None);
+    }
+
+    #[test]
+    fn context_menu_items_dispatch_their_actions() {
+        let item = |label: &str| MenuItem::ALL.iter().find(|x| x.0 == label).map(|x| x.1);
+        assert_eq!(item("打开"), Some(MenuItem::Action(InputAction::Open)));
+        assert_eq!(item("导出当前页"), Some(MenuItem::ExportPage));
+        assert_eq!(item("复制当前页"), Some(MenuItem::CopyPage));
+        assert_eq!(item("跳转到页"), Some(MenuItem::Action(InputAction::PageJump)));
+        assert_eq!(item("切换阅读方向"), Some(MenuItem::Action(InputAction::ReverseReading)));
+        assert_eq!(item("显示帮助"), Some(MenuItem::Action(InputAction::ShowHelp)));
+        let labels: HashSet<&str> = MenuItem::ALL.iter().map(|x| x.0).collect();
+        assert_eq!(labels.len(), MenuItem::ALL.len());
+    }
+
+    #[test]
+    fn page_count_presets_cycle_and_wrap() {
+        let presets = [1, 2, 4];
+        let mut size = 1;
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            size = next_page_count(&presets, size, 100).unwrap();
+            seen.push(size);
+        }
+        assert_eq!(seen, [2, 4, 1, 2]);
+        // 预设超过总页数时按总页数算，重复的只保留一个
+        assert_eq!(next_page_count(&presets, 2, 3), Some(3));
+        assert_eq!(next_page_count(&presets, 3, 3), Some(1));
+        assert_eq!(next_page_count(&[], 1, 3), None);
+    }
+
+    #[test]
+    fn inverted_scroll_swaps_wheel_direction() {
+        assert_eq!(wheel_code(5., 3., false), Some("WheelDown"));
+        assert_eq!(wheel_code(5., 3., true), Some("WheelUp"));
+        assert_eq!(wheel_code(-5., 3., true), Some("WheelDown"));
+        assert_eq!(wheel_code(2., 3., true), None);
+    }
+
+    #[test]
+    fn custom_bar_colors_are_used_for_fills() {
+        let colors = BarColors { unloaded: "#111".to_string(), loaded: "#222".to_string(), current: "#333".to_string() };
+        let fills = bar_fills(&[true, true, false, true], &[0, 3], 2, 1, &colors);
+        assert_eq!(fills, [
+            ("#111", 0., 16.),
+            ("#222", 0., 8.),
+            ("#222", 12., 4.),
+            ("#333", 8., 4.),
+            (BAR_TICK_COLOR, 12., 1.),
+        ]);
+        assert_eq!(bar_fills(&[], &[], 0, 1, &colors), [("#111", 0., 1.)]);
+    }
+
+    #[test]
+    fn reduced_motion_config_overrides_os_preference() {
+        // 配置开启时无论系统如何都关闭动画，过渡立即完成
+        assert!(resolve_reduced_motion(Some(true), false));
+        assert!(resolve_reduced_motion(Some(true), true));
+        // 配置关闭时即使系统偏好减少动态也保留动画
+        assert!(!resolve_reduced_motion(Some(false), true));
+        // 未配置时跟随系统
+        assert!(resolve_reduced_motion(None, true));
+        assert!(!resolve_reduced_motion(None, false));
+    }
+
+    #[test]
+    fn page_number_offset_maps_both_ways() {
+        // +3：前三页是封面与扉页，第四个文件是正文第 1 页
+        assert_eq!(display_page_number(0, 3), "#1");
+        assert_eq!(display_page_number(2, 3), "#3");
+        assert_eq!(display_page_number(3, 3), "1");
+        assert_eq!(display_page_number(10, 3), "8");
+        assert_eq!(index_from_page_number(1, 3), 3);
+        assert_eq!(index_from_page_number(8, 3), 10);
+        // -2：第一个文件印着第 3 页
+        assert_eq!(display_page_number(0, -2), "3");
+        assert_eq!(display_page_number(5, -2), "8");
+        assert_eq!(index_from_page_number(8, -2), 5);
+        // 早于第一个文件的页码取 0
+        assert_eq!(index_from_page_number(1, -2), 0);
+        assert_eq!(index_from_page_number(i64::MIN, 3), 0);
+        for offset in [3, -2, 0] {
+            for index in 0..20 {
+                if let Ok(number) = display_page_number(index, offset).parse::<i64>() {
+                    assert_eq!(index_from_page_number(number, offset), index);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn toggle_1to1_switches_between_fit_and_original() {
+        for fit in [FitMode::FitScreen, FitMode::FitWidth, FitMode::FitHeight] {
+            let original = toggle_original_size(fit, fit);
+            assert_eq!(original, FitMode::OriginalSize);
+            assert_eq!(toggle_original_size(original, fit), fit);
+        }
+        // 手动切到其他适应方式后，再按一次同样进入原始尺寸
+        assert_eq!(toggle_original_size(FitMode::FitWidth, FitMode::FitScreen), FitMode::OriginalSize);
+        assert_eq!(toggle_original_size(FitMode::OriginalSize, FitMode::OriginalSize), FitMode::FitScreen);
+    }
+
+    #[test]
+    fn broken_image_is_retried_once() {
+        let mut retried = false;
+        assert!(take_retry(&mut retried));
+        // 重新编码后仍然失败时不再请求，避免循环
+        assert!(!take_retry(&mut retried));
+        assert!(!take_retry(&mut retried));
+    }
+
+    #[test]
+    fn lag_hint_needs_quick_streak_and_cools_down() {
+        let mut detector = LagDetector::default();
+        let mut now = 0.;
+        let mut turn = |detector: &mut LagDetector, gap: f64, loading: bool| {
+            now += gap;
+            detector.record(now, loading)
+        };
+        // 连续两次快速翻到未载入页还不提示，第三次提示
+        assert!(!turn(&mut detector, 0., true));
+        assert!(!turn(&mut detector, 500., true));
+        assert!(turn(&mut detector, 500., true));
+        // 冷却期内不再提示
+        for _ in 0..5 {
+            assert!(!turn(&mut detector, 500., true));
+        }
+        // 中间翻到已载入的页或间隔太久都会重新计数
+        let mut detector = LagDetector::default();
+        assert!(!turn(&mut detector, 0., true));
+        assert!(!turn(&mut detector, 500., true));
+        assert!(!turn(&mut detector, 500., false));
+        assert!(!turn(&mut detector, 500., true));
+        assert!(!turn(&mut detector, LAG_INTERVAL_MS + 1., true));
+        assert!(!turn(&mut detector, 500., true));
+        assert!(turn(&mut detector, 500., true));
+        // 冷却结束后可以再次提示
+        assert!(!turn(&mut detector, LAG_HINT_COOLDOWN_MS, true));
+        assert!(!turn(&mut detector, 500., true));
+        assert!(turn(&mut detector, 500., true));
+    }
+
+    #[test]
+    fn pad_numbers_pads_each_number_in_name() {
+        assert_eq!(pad_numbers("1", 3), "001");
+        assert_eq!(pad_numbers("cover", 3), "cover");
+        assert_eq!(pad_numbers("ch2_p7.jpg", 3), "ch002_p007.jpg");
+        // 已经够长的数字不截断
+        assert_eq!(pad_numbers("1234.png", 3), "1234.png");
+        assert_eq!(pad_numbers("第3话", 0), "第3话");
+    }
+
+    #[test]
+    fn ui_margin_insets_overlay_containers() {
+        assert_eq!(ui_margin_css(0), "0px");
+        assert_eq!(ui_margin_css(12), "12px");
+        // 计数器与载入条的位置都要经过带安全区域的边距变量
+        let styles = include_str!("../styles.css");
+        let rule = |selector: &str| {
+            let start = styles.find(format!("\n{} {{", selector).as_str()).unwrap();
+            let end = start + styles[start..].find('}').unwrap();
+            &styles[start..end]
+        };
+        assert!(rule(":root").contains("--ui-bottom: calc(var(--ui-margin) + env(safe-area-inset-bottom"));
+        let bar = rule(".loading-bar");
+        assert!(["bottom: var(--ui-bottom)", "left: var(--ui-left)", "var(--ui-right)"].iter().all(|x| bar.contains(x)), "{}", bar);
+        let counter = rule(".counter-display");
+        assert!(["var(--ui-top)", "var(--ui-right)"].iter().all(|x| counter.contains(x)), "{}", counter);
+    }
+
+    #[test]
+    fn random_page_stays_in_bounds_and_avoids_recent() {
+        for (page_count, size) in [(10_usize, 1), (10, 2), (3, 4), (1, 1), (0, 1)] {
+            let max = page_count.saturating_sub(size);
+            for step in 0..=100 {
+                let random = step as f64 / 100. * 0.999_999;
+                let page = random_page(page_count, size, &[], random);
+                assert!(page <= max, "{} / {} 页，每屏 {} 页", page, page_count, size);
+            }
+        }
+        // 最近跳过的页不再选中，全部跳过时退回到所有页中选
+        for step in 0..100 {
+            let page = random_page(5, 1, &[0, 2, 4], step as f64 / 100.);
+            assert!(page == 1 || page == 3);
+        }
+        assert_eq!(random_page(2, 1, &[0, 1], 0.9), 1);
+        assert_eq!(random_page(10, 1, &[], 1.), 9);
+    }
+
+    #[test]
+    fn resume_clamps_to_shorter_book() {
+        // 第 1 页是拆开的跨页，占两个显示页
+        let views = [(0, PagePart::Whole), (1, PagePart::Right), (1, PagePart::Left), (2, PagePart::Whole), (3, PagePart::Whole)];
+        assert_eq!(resume_view(&views, 0, 4), Some(0));
+        assert_eq!(resume_view(&views, 2, 4), Some(3));
+        assert_eq!(resume_view(&views, 1, 4), Some(1));
+        // 上次看到的页超出了现在的页数
+        assert_eq!(resume_view(&views, 120, 4), Some(4));
+        assert_eq!(resume_view(&[], 3, 0), None);
+    }
+
+    #[test]
+    fn zoom_keeps_anchor_fixed_and_clamps() {
+        // 光标下的内容点缩放前后在屏幕上的位置不变
+        let (zoom, pan) = zoom_at(1., (0., 0.), 2., (100., -50.));
+        assert_eq!(zoom, 2.);
+        assert_eq!(pan, (-100., 50.));
+        let (zoom, pan) = zoom_at(zoom, pan, 2., (100., -50.));
+        assert_eq!(zoom, 4.);
+        assert_eq!(pan, (-300., 150.));
+        assert_eq!(zoom_at(4., pan, 100., (0., 0.)).0, ZOOM_MAX);
+        assert_eq!(zoom_at(0.5, (0., 0.), 0.01, (0., 0.)).0, ZOOM_MIN);
+        // 回到原大小时平移归零
+        assert_eq!(zoom_at(ZOOM_STEP, (30., 40.), ZOOM_STEP.recip(), (5., 5.)), (1., (0., 0.)));
+    }
+
+    #[test]
+    fn spread_shows_alone_and_shifts_pairing() {
+        // 第 2 页是跨页，两页一屏
+        let spreads = [false, false, true, false, false, false];
+        assert_eq!(screen_len(&spreads, 0, 2), 2);
+        assert_eq!(screen_len(&spreads, 1, 2), 1);
+        assert_eq!(screen_len(&spreads, 2, 2), 1);
+        assert_eq!(screen_len(&spreads, 3, 2), 2);
+        // 比例还没加载到的页按普通页处理
+        assert_eq!(screen_len(&[], 7, 2), 2);
+        assert_eq!(prev_screen_start(&spreads, 3, 2), 2);
+        assert_eq!(prev_screen_start(&spreads, 2, 2), 0);
+        assert_eq!(prev_screen_start(&spreads, 4, 2), 3);
+        assert_eq!(prev_screen_start(&spreads, 5, 2), 3);
+        assert_eq!(prev_screen_start(&spreads, 0, 2), 0);
+    }
+
+    #[test]
+    fn disabled_drag_drop_opens_nothing() {
+        let paths = || vec!["a.cbz".to_string(), "b.cbz".to_string()];
+        // 只有 Ok(Some) 才会调用 create_manga
+        assert_eq!(dropped_path(paths(), false), Err(()));
+        assert_eq!(dropped_path(paths(), true), Ok(Some("a.cbz".to_string())));
+        assert_eq!(dropped_path(Vec::new(), true), Ok(None));
+    }
+
+    #[test]
+    fn sideways_rotation_swaps_aspect_ratio() {
+        assert_eq!(rotated_aspect_ratio(0.5, 0), 0.5);
+        assert_eq!(rotated_aspect_ratio(0.5, 90), 2.);
+        assert_eq!(rotated_aspect_ratio(0.5, 180), 0.5);
+        assert_eq!(rotated_aspect_ratio(0.5, 270), 2.);
+        assert_eq!(rotated_aspect_ratio(0.5, -90), 2.);
+    }
+
+    #[test]
+    fn oversized_page_input_clamps_to_last_page() {
+        assert_eq!(parse_page_input("999999999999999999999", 0, 120), Ok(119));
+        assert_eq!(parse_page_input("p. 12", 0, 120), Ok(11));
+        assert_eq!(parse_page_input("500", 0, 120), Ok(119));
+        assert_eq!(parse_page_input("1", 2, 120), Ok(2));
+        assert!(parse_page_input("  ", 0, 120).is_err());
+        assert!(parse_page_input("-3", 0, 120).is_err());
+        assert!(parse_page_input("abc", 0, 120).is_err());
+    }
+
+    #[test]
+    fn only_current_page_animates_when_configured() {
+        assert!(still_loading(false, true));
+        assert!(!still_loading(true, true));
+        assert!(!still_loading(false, false));
+        assert!(!still_loading(true, false));
+    }
+
+    #[test]
+    fn manual_navigation_stops_slideshow() {
+        assert!(is_navigation(InputAction::PageNext));
+        assert!(is_navigation(InputAction::PageJump));
+        assert!(is_navigation(InputAction::RandomPage));
+        // 切换幻灯片本身与不翻页的动作不打断
+        assert!(!is_navigation(InputAction::ToggleSlideshow));
+        assert!(!is_navigation(InputAction::ZoomIn));
+    }
+
+    #[test]
+    fn live_region_text_follows_page_changes() {
+        assert_eq!(page_announcement(4, 1, 120, 0), "第 5 页，共 120 页");
+        assert_eq!(page_announcement(5, 1, 120, 0), "第 6 页，共 120 页");
+        assert_eq!(page_announcement(4, 2, 120, 0), "第 5 至 6 页，共 120 页");
+        // 末屏只剩一页时不报不存在的页
+        assert_eq!(page_announcement(119, 2, 120, 0), "第 120 页，共 120 页");
+        assert_eq!(page_alt(Some(2), PagePart::Left), "第 3 页左半");
+    }
+
+    #[test]
+    fn space_scrolls_then_pages_at_bottom() {
+        // 内容高 2000，视口高 800，底部在 1200
+        assert_eq!(space_scroll_target(0., 2000., 800., 800.), Some(720.));
+        assert_eq!(space_scroll_target(720., 2000., 800., 800.), Some(1200.));
+        // 到底部后交给翻页；取整误差不会卡住
+        assert_eq!(space_scroll_target(1200., 2000., 800., 800.), None);
+        assert_eq!(space_scroll_target(1199.5, 2000., 800., 800.), None);
+        // 内容不足一屏时直接翻页
+        assert_eq!(space_scroll_target(0., 600., 800., 800.), None);
+    }
+}
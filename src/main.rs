@@ -1,13 +1,23 @@
 mod app;
+mod settings;
 
 use app::*;
+use settings::*;
 use leptos::prelude::*;
 
 fn main() {
     console_error_panic_hook::set_once();
-    mount_to_body(|| {
-        view! {
-            <App/>
-        }
-    })
+    if is_settings_window() {
+        mount_to_body(|| {
+            view! {
+                <Settings/>
+            }
+        })
+    } else {
+        mount_to_body(|| {
+            view! {
+                <App/>
+            }
+        })
+    }
 }
@@ -0,0 +1,258 @@
+use leptos::{
+    prelude::*,
+    task::spawn_local,
+    ev,
+};
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use shared::config::{Config, RightClickAction};
+
+use crate::app::{mouse_button_code, wheel_code};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(catch, js_namespace = ["window", "__TAURI__", "core"], js_name = invoke)]
+    async fn try_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"])]
+    fn emit(event: &str, payload: &str);
+}
+
+#[derive(Serialize)]
+struct SaveConfigPayload {
+    config: Config,
+}
+
+/// 设置窗口打开时由后端注入 `window.__SETTINGS_WINDOW__`
+pub fn is_settings_window() -> bool {
+    js_sys::Reflect::get(&window(), &JsValue::from_str("__SETTINGS_WINDOW__")).is_ok_and(|x| x.is_truthy())
+}
+
+/// 表单共用的状态
+#[derive(Clone, Copy)]
+struct Editor {
+    config: RwSignal<Value>,
+    status: RwSignal<String>,
+    /// 正在录入按键的键位项
+    capturing: RwSignal<Option<String>>,
+}
+
+impl Editor {
+    fn set(&self, path: &str, new: Value) {
+        self.config.update(|x| if let Some(x) = x.pointer_mut(path) {
+            *x = new;
+        });
+    }
+
+    /// 把录入的按键加到正在录入的键位项，已绑定到其他动作时提示
+    fn finish_capture(&self, code: &str) {
+        let Some(path) = self.capturing.get_untracked() else {
+            return;
+        };
+        self.capturing.set(None);
+        let action = path.rsplit('/').next().unwrap_or_default().to_string();
+        let others = self.config.with_untracked(|x| x.get("key_bind").map(|x| bound_actions(x, code, action.as_str())).unwrap_or_default());
+        self.config.update(|x| if let Some(Value::Array(keys)) = x.pointer_mut(path.as_str()) {
+            if !keys.iter().any(|x| x.as_str() == Some(code)) {
+                keys.push(Value::from(code));
+            }
+        });
+        if others.is_empty() {
+            self.status.set(format!("{} 已加入 {}", code, action));
+        } else {
+            self.status.set(format!("{} 已绑定到 {}，保存前需从其中移除", code, others.join("、")));
+        }
+    }
+}
+
+/// `key_bind` 中除 `except` 外绑定了 `code` 的动作
+fn bound_actions(key_bind: &Value, code: &str, except: &str) -> Vec<String> {
+    key_bind.as_object().into_iter().flatten()
+        .filter(|(action, keys)| action.as_str() != except && keys.as_array().is_some_and(|x| x.iter().any(|x| x.as_str() == Some(code))))
+        .map(|(action, _)| action.clone())
+        .collect()
+}
+
+/// 按 JSON 值的类型生成输入框：布尔值用勾选框，数字、字符串用输入框，数组等其余类型直接编辑 JSON 文本；键位项另有录入按钮
+///
+/// `path` 为 JSON Pointer，修改后写回 `config` 中对应的位置
+fn field_view(path: String, key: String, value: &Value, editor: Editor) -> AnyView {
+    let Editor { config, status, capturing } = editor;
+    let set = move |path: &str, new: Value| editor.set(path, new);
+
+    match value {
+        Value::Object(map) => view! {
+            <fieldset>
+                <legend>{key}</legend>
+                {map.iter().map(|(k, v)| field_view(format!("{}/{}", path, k), k.clone(), v, editor)).collect_view()}
+            </fieldset>
+        }.into_any(),
+        Value::Array(_) if path.starts_with("/key_bind/") => {
+            let (value_path, capture_path) = (path.clone(), path.clone());
+            view! {
+                <label>
+                    <span>{key.clone()}</span>
+                    <input type="text" prop:value=move || config.with(|x| x.pointer(value_path.as_str()).map(Value::to_string).unwrap_or_default()) on:change=move |ev| {
+                        let text = event_target_value(&ev);
+                        match serde_json::from_str(text.as_str()) {
+                            Ok(x) => set(path.as_str(), x),
+                            Err(e) => status.set(format!("{} 格式不对：{}", key, e)),
+                        }
+                    } />
+                    <button on:click=move |_| capturing.set(Some(capture_path.clone()))>"录入"</button>
+                </label>
+            }.into_any()
+        },
+        Value::Bool(checked) => view! {
+            <label>
+                <span>{key}</span>
+                <input type="checkbox" prop:checked=*checked on:change=move |ev| set(path.as_str(), Value::Bool(event_target_checked(&ev))) />
+            </label>
+        }.into_any(),
+        Value::Number(n) => view! {
+            <label>
+                <span>{key.clone()}</span>
+                <input type="number" step="any" prop:value=n.to_string() on:change=move |ev| {
+                    let text = event_target_value(&ev);
+                    match parse_number(text.as_str()) {
+                        Some(n) => set(path.as_str(), n),
+                        None => status.set(format!("{} = {} 不是数字", key, text)),
+                    }
+                } />
+            </label>
+        }.into_any(),
+        Value::String(s) => view! {
+            <label>
+                <span>{key}</span>
+                <input type="text" prop:value=s.clone() on:change=move |ev| set(path.as_str(), Value::String(event_target_value(&ev))) />
+            </label>
+        }.into_any(),
+        _ => view! {
+            <label>
+                <span>{key.clone()}</span>
+                <input type="text" prop:value=value.to_string() on:change=move |ev| {
+                    let text = event_target_value(&ev);
+                    match serde_json::from_str(text.as_str()) {
+                        Ok(x) => set(path.as_str(), x),
+                        Err(e) => status.set(format!("{} 格式不对：{}", key, e)),
+                    }
+                } />
+            </label>
+        }.into_any(),
+    }
+}
+
+/// 整数保持整数，否则按小数解析
+fn parse_number(text: &str) -> Option<Value> {
+    let text = text.trim();
+    text.parse::<i64>().map(Value::from).ok()
+        .or_else(|| text.parse::<f64>().ok().filter(|x| x.is_finite()).map(Value::from))
+}
+
+/// 配置编辑窗口：字段由配置本身生成，保存时由后端检查并写入配置文件，主窗口随文件监视热重载
+#[component]
+pub fn Settings() -> impl IntoView {
+    let config = RwSignal::new(Value::Null);
+    let status = RwSignal::new(String::new());
+    let capturing = RwSignal::new(None);
+    let editor = Editor { config, status, capturing };
+    let (loaded, set_loaded) = signal(false);
+
+    // 录入时按键、点击、滚轮都换算成与主窗口 `cmd_map` 相同的按键名
+    let handle = window_event_listener(ev::keydown, move |ev| {
+        if capturing.get_untracked().is_some() {
+            ev.prevent_default();
+            editor.finish_capture(ev.code().as_str());
+        }
+    });
+    on_cleanup(move || handle.remove());
+
+    let on_capture_mousedown = move |ev: ev::MouseEvent| {
+        ev.prevent_default();
+        if let Some(code) = mouse_button_code(ev.button(), RightClickAction::Page) {
+            editor.finish_capture(code);
+        }
+    };
+
+    let on_capture_wheel = move |ev: ev::WheelEvent| {
+        let invert = config.with_untracked(|x| x.get("invert_scroll").and_then(Value::as_bool).unwrap_or(false));
+        if let Some(code) = wheel_code(ev.delta_y(), 0., invert) {
+            editor.finish_capture(code);
+        }
+    };
+
+    spawn_local(async move {
+        match try_invoke("get_config", JsValue::null()).await.map(serde_wasm_bindgen::from_value::<Config>) {
+            Ok(Ok(x)) => {
+                config.set(serde_json::to_value(x).unwrap_or_default());
+                set_loaded.set(true);
+            },
+            Ok(Err(e)) => status.set(format!("读取配置失败：{}", e)),
+            Err(e) => status.set(format!("读取配置失败：{}", e.as_string().unwrap_or_default())),
+        }
+    });
+
+    let save = move |_| {
+        let parsed: Config = match serde_json::from_value(config.get_untracked()) {
+            Ok(x) => x,
+            Err(e) => {
+                status.set(format!("配置项格式不对：{}", e));
+                return;
+            },
+        };
+        status.set(String::from("保存中……"));
+        spawn_local(async move {
+            let args = SaveConfigPayload { config: parsed }.serialize(&serde_wasm_bindgen::Serializer::json_compatible()).unwrap();
+            match try_invoke("save_config", args).await {
+                Ok(_) => status.set(String::from("已保存")),
+                Err(e) => {
+                    let message = e.as_string().unwrap_or_default();
+                    emit("toast", format!("E保存配置失败：{}", message).as_str());
+                    status.set(message);
+                },
+            }
+        });
+    };
+
+    view! {
+        <div class="settings">
+            {move || loaded.get().then(|| config.with_untracked(|x| x.as_object().map(|map| {
+                // 版本号由后端维护，不提供修改
+                map.iter().filter(|(k, _)| k.as_str() != "version")
+                    .map(|(k, v)| field_view(format!("/{}", k), k.clone(), v, editor))
+                    .collect_view()
+            })))}
+            <div class="settings-actions">
+                <button on:click=save disabled=move || !loaded.get()>"保存"</button>
+                <span>{move || status.get()}</span>
+            </div>
+            <Show when=move || capturing.with(Option::is_some)>
+                <div class="key-capture" on:mousedown=on_capture_mousedown on:wheel=on_capture_wheel on:contextmenu=|ev| ev.prevent_default()>
+                    <p>"按下要绑定的按键，或在此处点击、滚动滚轮"</p>
+                    <button on:mousedown=|ev| ev.stop_propagation() on:click=move |_| capturing.set(None)>"取消"</button>
+                </div>
+            </Show>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::config::Preset;
+
+    #[test]
+    fn capture_warns_about_keys_bound_elsewhere() {
+        let key_bind = serde_json::to_value(Config::preset().key_bind).unwrap();
+        let code = key_bind["page_next"][0].as_str().unwrap().to_string();
+        assert_eq!(bound_actions(&key_bind, code.as_str(), "page_jump"), ["page_next"]);
+        // 录入的正是自己已有的键时不提示
+        assert!(bound_actions(&key_bind, code.as_str(), "page_next").is_empty());
+        assert!(bound_actions(&key_bind, "F9", "page_jump").is_empty());
+        // 录入的按键名与键位表一致
+        assert_eq!(mouse_button_code(2, RightClickAction::Page), Some("RightClick"));
+        assert_eq!(wheel_code(-120., 0., false), Some("WheelUp"));
+    }
+}